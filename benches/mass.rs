@@ -0,0 +1,70 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Benchmark for cached stack mass
+//!
+//! Run with `cargo bench --features server`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use solar_dawn_common::stack::{Engine, FuelTank, Gun, Stack};
+use solar_dawn_common::vec2::{Displacement, Position};
+use solar_dawn_common::EntityIdGenerator;
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let mut id_generator = EntityIdGenerator::new();
+    let mut stack = Stack::new(
+        "Warship",
+        &mut id_generator,
+        Position::new(0, 0),
+        Displacement::new(0, 0),
+        0.into(),
+    );
+    for _ in 0..64 {
+        let gun = Gun::new(&mut id_generator);
+        stack.guns_mut().insert(gun.id, gun);
+        let engine = Engine::new(&mut id_generator);
+        stack.engines_mut().insert(engine.id, engine);
+        let tank = FuelTank::new(&mut id_generator);
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+    }
+    for tank in stack.fuel_tanks_mut().values_mut() {
+        tank.fuel = FuelTank::CAPACITY / 2;
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(black_box(&stack).mass());
+    }
+    let cached = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        // Any mutable access to the components drops the cached mass
+        stack.fuel_tanks_mut();
+        black_box(black_box(&stack).mass());
+    }
+    let invalidated = start.elapsed();
+
+    println!("mass, cached:      {:?}/iter", cached / ITERATIONS);
+    println!("mass, invalidated: {:?}/iter", invalidated / ITERATIONS);
+}
@@ -17,11 +17,19 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::Deserialize;
+#[cfg(feature = "server")]
+use serde::Serialize;
 
 #[cfg(feature = "server")]
 use crate::EntityIdGenerator;
-use crate::{vec2, EntityId};
+use crate::{
+    stack::{CargoList, Stack},
+    vec2, EntityId, GameState,
+};
 
 /// A major astronomical body
 ///
@@ -29,13 +37,18 @@ use crate::{vec2, EntityId};
 /// on
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
 pub struct MajorBody {
     pub name: String,
     pub id: EntityId,
     pub position: vec2::Position,
     pub radius: f64,
     pub colour: String,
+    /// The body this one orbits, for moons
+    pub parent: Option<EntityId>,
+    /// How many hexes/turn the body pulls things in adjacent hexes towards it
+    pub gravity: u64,
 }
 impl MajorBody {
     #[cfg(feature = "server")]
@@ -52,6 +65,8 @@ impl MajorBody {
             position,
             radius,
             colour: colour.into(),
+            parent: None,
+            gravity: 1,
         }
     }
 }
@@ -62,7 +77,8 @@ impl MajorBody {
 /// landed on
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
 pub struct MinorBody {
     pub name: String,
     pub id: EntityId,
@@ -90,12 +106,545 @@ impl MinorBody {
             ore_abundance,
         }
     }
+
+    /// The colour to draw the body in, as a hex colour code
+    ///
+    /// Icier bodies are bluer and more ore-rich bodies are redder, so every
+    /// client draws the same body the same way
+    pub fn colour(&self) -> String {
+        let channel = |abundance: u64| 0x60 + abundance.min(6) * 0x18;
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            channel(self.ore_abundance),
+            channel(0),
+            channel(self.ice_abundance)
+        )
+    }
+}
+
+/// A named region of the solar system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// Closer to Sol than the asteroid belt
+    Inner,
+    /// The main asteroid belt
+    Belt,
+    /// Asteroids leading Jupiter
+    Trojan,
+    /// Asteroids trailing Jupiter
+    Greek,
+    /// Asteroids opposite Jupiter
+    Hilda,
+    /// Further from Sol than the asteroid belt
+    Outer,
+}
+
+/// Half the angle the Trojans, Greeks, and Hildas are spread over, with some
+/// slack for rounding to hexes
+const LOBE_HALF_WIDTH: f64 = 20.0 / 180.0 * PI;
+
+impl GameState {
+    /// Which region a hex is in
+    ///
+    /// Uses the same distance bands as the generator; the Trojans, Greeks, and
+    /// Hildas are told apart from the rest of the belt by their angle from
+    /// Jupiter, so there are none if there's no major body named Jupiter
+    pub fn region_of(&self, position: vec2::Position) -> Region {
+        let jupiter = self
+            .major_bodies
+            .values()
+            .find(|body| body.name == "Jupiter")
+            .map(|body| body.position);
+        region_relative_to(position, jupiter)
+    }
+}
+
+/// Which region a hex is in, given where Jupiter is, if anywhere
+fn region_relative_to(position: vec2::Position, jupiter: Option<vec2::Position>) -> Region {
+    let distance = position.distance(vec2::Position::new(0, 0));
+    let near = |centre: f64| {
+        jupiter.is_some_and(|jupiter| {
+            let angle = position.to_displacement().bearing() - jupiter.to_displacement().bearing();
+            let offset = (angle - centre + PI).rem_euclid(2.0 * PI) - PI;
+            offset.abs() <= LOBE_HALF_WIDTH
+        })
+    };
+    match distance {
+        0..29 => Region::Inner,
+        32..38 if near(PI) => Region::Hilda,
+        38..=42 if near(PI / 3.0) => Region::Trojan,
+        38..=42 if near(-PI / 3.0) => Region::Greek,
+        29..=36 => Region::Belt,
+        _ => Region::Outer,
+    }
+}
+
+impl GameState {
+    /// Move a major body, carrying its moons along with it
+    ///
+    /// Moons keep their offset from their parent, so they stay in orbit
+    /// however the parent moves
+    pub fn move_major_body(&mut self, id: EntityId, position: vec2::Position) {
+        let Some(body) = self.major_bodies.get_mut(&id) else {
+            return;
+        };
+        let offset = position - body.position;
+        body.position = position;
+        let moons = self
+            .major_bodies
+            .values()
+            .filter(|moon| moon.parent == Some(id))
+            .map(|moon| (moon.id, moon.position + offset))
+            .collect::<Vec<_>>();
+        for (moon, position) in moons {
+            self.move_major_body(moon, position);
+        }
+    }
+}
+
+impl GameState {
+    /// The change in velocity from gravity for something in this hex
+    ///
+    /// Each major body pulls anything in an adjacent hex towards it by its
+    /// gravity
+    pub fn gravity_at(&self, position: vec2::Position) -> vec2::Displacement {
+        self.major_bodies
+            .values()
+            .filter(|body| body.position.distance(position) == 1)
+            .fold(vec2::Displacement::new(0, 0), |gravity, body| {
+                gravity + (body.position - position) * body.gravity as i64
+            })
+    }
+
+    /// The stack's velocity next turn if it doesn't burn, or `None` if the
+    /// stack doesn't exist
+    pub fn predicted_velocity(&self, stack: EntityId) -> Option<vec2::Displacement> {
+        let stack = self.stacks.get(&stack)?;
+        Some(stack.velocity + self.gravity_at(stack.position))
+    }
+}
+
+impl GameState {
+    /// The minor body in a hex, if there is one
+    pub fn body_at(&self, position: vec2::Position) -> Option<&MinorBody> {
+        self.minor_bodies
+            .values()
+            .find(|body| body.position == position)
+    }
+
+    /// The (ice, ore) abundances of the minor body in the stack's hex, if
+    /// there is one
+    pub fn abundance_under(&self, stack: EntityId) -> Option<(u64, u64)> {
+        let body = self.body_at(self.stacks.get(&stack)?.position)?;
+        Some((body.ice_abundance, body.ore_abundance))
+    }
+
+    /// Is the stack landed on the minor body?
+    ///
+    /// A stack is landed if it's in the body's hex and not moving relative to
+    /// it. Minor bodies don't move, so this means the stack is stationary; if
+    /// bodies ever move, the stack's velocity must match the body's instead
+    pub fn is_landed(&self, stack: EntityId, body: EntityId) -> bool {
+        let (Some(stack), Some(body)) = (self.stacks.get(&stack), self.minor_bodies.get(&body))
+        else {
+            return false;
+        };
+        stack.position == body.position && stack.velocity == vec2::Displacement::new(0, 0)
+    }
+
+    /// What the stack's miners produce from the minor body this economic
+    /// phase
+    ///
+    /// Each working miner produces the body's abundances of ice and ore; a
+    /// stack that isn't landed on the body, or has no working miners, gets
+    /// nothing. If the rules let mining reach further, the stack also draws
+    /// from every other body within that range. Each body's yield is split
+    /// evenly among the landed mining stacks in range of it, rounded down
+    pub fn mining_yield(&self, stack: EntityId, body: EntityId) -> CargoList {
+        if !self.is_landed(stack, body) {
+            return CargoList::default();
+        }
+        let working_miners = |stack: &Stack| {
+            stack
+                .miners()
+                .values()
+                .filter(|miner| !miner.damaged)
+                .count() as u64
+        };
+        let stack = &self.stacks[&stack];
+        let miners = working_miners(stack);
+        if miners == 0 {
+            return CargoList::default();
+        }
+
+        let range = self.rules.mining_range;
+        let mut cargo = CargoList::default();
+        for other in self.minor_bodies.values() {
+            if other.position.distance(stack.position) > range {
+                continue;
+            }
+            let sharers = self
+                .stacks
+                .values()
+                .filter(|sharer| {
+                    self.body_at(sharer.position)
+                        .is_some_and(|landed_on| self.is_landed(sharer.id, landed_on.id))
+                        && sharer.position.distance(other.position) <= range
+                        && working_miners(sharer) > 0
+                })
+                .count() as u64;
+            if sharers == 0 {
+                continue;
+            }
+            cargo.ice += miners * other.ice_abundance / sharers;
+            cargo.ore += miners * other.ore_abundance / sharers;
+        }
+        cargo
+    }
+
+    /// Whether a straight line between two hexes is clear of major bodies
+    ///
+    /// The hexes at either end don't count
+    pub fn line_of_sight(&self, from: vec2::Position, to: vec2::Position) -> bool {
+        let line = from.line_to(to);
+        let between = line.len().saturating_sub(2);
+        line.iter()
+            .skip(1)
+            .take(between)
+            .all(|hex| self.major_bodies.values().all(|body| body.position != *hex))
+    }
+
+    /// The summed (ice, ore) abundances of every minor body
+    pub fn total_abundance(&self) -> (u64, u64) {
+        self.minor_bodies.values().fold((0, 0), |(ice, ore), body| {
+            (ice + body.ice_abundance, ore + body.ore_abundance)
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "server")]
+    use std::collections::HashMap;
+
     use super::*;
+    #[cfg(feature = "server")]
+    use crate::{
+        scenario::{MinorBodySpec, ScenarioSpec},
+        stack::{Miner, Stack},
+        EntityIdGenerator,
+    };
 
+    #[cfg(feature = "server")]
     #[test]
-    fn test() {}
+    fn test_landed() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let body = MinorBody::new(
+            "Ceres",
+            &mut id_generator,
+            vec2::Position::new(3, 0),
+            0.2,
+            1,
+            2,
+        );
+        let body_id = body.id;
+        state.minor_bodies.insert(body.id, body);
+        let mut ids = Vec::new();
+        for velocity in [vec2::Displacement::new(0, 0), vec2::Displacement::new(1, 0)] {
+            let mut stack = Stack::new(
+                "Prospector",
+                &mut id_generator,
+                vec2::Position::new(3, 0),
+                velocity,
+                0.into(),
+            );
+            let miner = Miner::new(&mut id_generator);
+            stack.miners_mut().insert(miner.id, miner);
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+
+        assert!(state.is_landed(ids[0], body_id));
+        assert_eq!(
+            state.mining_yield(ids[0], body_id),
+            CargoList::new(1, 2, 0, 0)
+        );
+        assert!(!state.is_landed(ids[1], body_id));
+        assert_eq!(state.mining_yield(ids[1], body_id), CargoList::default());
+
+        // a second stack landed on the body takes half its yield
+        state.stacks.get_mut(&ids[1]).unwrap().velocity = vec2::Displacement::new(0, 0);
+        let miner = Miner::new(&mut id_generator);
+        let stack = state.stacks.get_mut(&ids[0]).unwrap();
+        stack.miners_mut().insert(miner.id, miner);
+        assert_eq!(
+            state.mining_yield(ids[0], body_id),
+            CargoList::new(1, 2, 0, 0)
+        );
+        assert_eq!(
+            state.mining_yield(ids[1], body_id),
+            CargoList::new(0, 1, 0, 0)
+        );
+        state.stacks.get_mut(&ids[1]).unwrap().velocity = vec2::Displacement::new(1, 0);
+
+        assert_eq!(state.abundance_under(ids[0]), Some((1, 2)));
+        state.stacks.get_mut(&ids[1]).unwrap().position = vec2::Position::new(4, 0);
+        assert_eq!(state.abundance_under(ids[1]), None);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_no_miners_yield_nothing() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.mining_range = 1;
+        let mut bodies = Vec::new();
+        for position in [vec2::Position::new(3, 0), vec2::Position::new(4, 0)] {
+            let body = MinorBody::new("Rock", &mut id_generator, position, 0.2, 3, 3);
+            bodies.push(body.id);
+            state.minor_bodies.insert(body.id, body);
+        }
+        let stack = Stack::new(
+            "Hulk",
+            &mut id_generator,
+            vec2::Position::new(3, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        assert!(state.is_landed(stack_id, bodies[0]));
+        assert_eq!(
+            state.mining_yield(stack_id, bodies[0]),
+            CargoList::default()
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_mining_range() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut bodies = Vec::new();
+        for (position, ice, ore) in [
+            (vec2::Position::new(3, 0), 1, 2),
+            (vec2::Position::new(4, 0), 4, 0),
+            (vec2::Position::new(5, 0), 8, 8),
+        ] {
+            let body = MinorBody::new("Rock", &mut id_generator, position, 0.2, ice, ore);
+            bodies.push(body.id);
+            state.minor_bodies.insert(body.id, body);
+        }
+        let mut stacks = Vec::new();
+        for position in [vec2::Position::new(3, 0), vec2::Position::new(5, 0)] {
+            let mut stack = Stack::new(
+                "Prospector",
+                &mut id_generator,
+                position,
+                vec2::Displacement::new(0, 0),
+                0.into(),
+            );
+            let miner = Miner::new(&mut id_generator);
+            stack.miners_mut().insert(miner.id, miner);
+            stacks.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+
+        assert_eq!(
+            state.mining_yield(stacks[0], bodies[0]),
+            CargoList::new(1, 2, 0, 0)
+        );
+        // the neighbour at (4, 0) is shared with the stack landed at (5, 0)
+        state.rules.mining_range = 1;
+        assert_eq!(
+            state.mining_yield(stacks[0], bodies[0]),
+            CargoList::new(1 + 2, 2, 0, 0)
+        );
+        // a stack that isn't landed anywhere isn't paid, so doesn't share
+        state.minor_bodies.remove(&bodies[2]);
+        assert_eq!(
+            state.mining_yield(stacks[1], bodies[2]),
+            CargoList::default()
+        );
+        assert_eq!(
+            state.mining_yield(stacks[0], bodies[0]),
+            CargoList::new(1 + 4, 2, 0, 0)
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_total_abundance() {
+        let bodies = vec![
+            MinorBodySpec::new("Ceres", vec2::Position::new(30, 0), 0.2, 3, 1),
+            MinorBodySpec::new("Vesta", vec2::Position::new(-31, 2), 0.2, 0, 5),
+            MinorBodySpec::new("Pallas", vec2::Position::new(4, 29), 0.2, 2, 2),
+        ];
+        let ice = bodies.iter().map(|body| body.ice_abundance).sum::<u64>();
+        let ore = bodies.iter().map(|body| body.ore_abundance).sum::<u64>();
+        let scenario = ScenarioSpec {
+            minor_bodies: Some(bodies),
+            ..Default::default()
+        };
+        let state = GameState::from_scenario(&[0; 32], 2, &scenario, &mut EntityIdGenerator::new())
+            .unwrap();
+        assert_eq!(state.total_abundance(), (ice, ore));
+        assert_eq!(state.total_abundance(), (5, 8));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_moons_follow_parent() {
+        let mut state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let find = |state: &GameState, name: &str| {
+            state
+                .major_bodies
+                .values()
+                .find(|body| body.name == name)
+                .map(|body| (body.id, body.position))
+                .unwrap()
+        };
+        let (terra, terra_position) = find(&state, "Terra");
+        let (_, luna_position) = find(&state, "Luna");
+        let (_, venus_position) = find(&state, "Venus");
+        let offset = luna_position - terra_position;
+
+        for step in [
+            vec2::Displacement::new(0, 1),
+            vec2::Displacement::new(-2, 1),
+        ] {
+            let position = find(&state, "Terra").1 + step;
+            state.move_major_body(terra, position);
+            assert_eq!(find(&state, "Terra").1, position);
+            assert_eq!(find(&state, "Luna").1 - find(&state, "Terra").1, offset);
+        }
+        assert_eq!(find(&state, "Venus").1, venus_position);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_gravity_strength() {
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let pull_beside = |name: &str| {
+            let body = state
+                .major_bodies
+                .values()
+                .find(|body| body.name == name)
+                .unwrap();
+            state
+                .gravity_at(body.position + vec2::Displacement::new(0, 1))
+                .norm()
+        };
+        assert_eq!(pull_beside("Mercury"), 1);
+        assert!(pull_beside("Jupiter") > pull_beside("Mercury"));
+        assert!(pull_beside("Sol") > pull_beside("Jupiter"));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_predicted_velocity() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let body = MajorBody::new(
+            "Mars",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            0.5,
+            "#c1440e",
+        );
+        state.major_bodies.insert(body.id, body);
+        let velocity = vec2::Displacement::new(2, 0);
+        let mut stack = |position| {
+            let stack = Stack::new("Scout", &mut id_generator, position, velocity, 0.into());
+            let id = stack.id;
+            state.stacks.insert(id, stack);
+            id
+        };
+        let near = stack(vec2::Position::new(0, 1));
+        let far = stack(vec2::Position::new(10, 10));
+
+        assert_eq!(
+            state.predicted_velocity(near),
+            Some(velocity + vec2::Displacement::new(0, -1))
+        );
+        assert_eq!(state.predicted_velocity(far), Some(velocity));
+        assert_eq!(state.predicted_velocity(EntityId::from(9999)), None);
+    }
+
+    #[test]
+    fn test_region_of() {
+        let jupiter = Some(vec2::Position::new(40, 0));
+        for (position, region) in [
+            (vec2::Position::new(0, 0), Region::Inner),
+            (vec2::Position::new(16, 0), Region::Inner),
+            (vec2::Position::new(30, 0), Region::Belt),
+            (vec2::Position::new(0, -34), Region::Belt),
+            (vec2::Position::new(-34, 0), Region::Hilda),
+            (vec2::Position::new(0, 40), Region::Trojan),
+            (vec2::Position::new(40, -40), Region::Greek),
+            (vec2::Position::new(37, 0), Region::Outer),
+            (vec2::Position::new(-40, 0), Region::Outer),
+            (vec2::Position::new(60, 0), Region::Outer),
+        ] {
+            assert_eq!(
+                region_relative_to(position, jupiter),
+                region,
+                "{position:?}"
+            );
+        }
+        assert_eq!(
+            region_relative_to(vec2::Position::new(0, 40), None),
+            Region::Outer
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_region_of_generated_bodies() {
+        for seed in 0..3 {
+            let state = GameState::new(&[seed; 32], 2, &mut EntityIdGenerator::new());
+            let mut counts = HashMap::<Region, usize>::new();
+            for body in state.minor_bodies.values() {
+                if body.name == "Phobos" || body.name == "Deimos" {
+                    continue;
+                }
+                let region = state.region_of(body.position);
+                *counts.entry(region).or_default() += 1;
+                // only the Trojans and Greeks are generated past the belt and
+                // the Hildas
+                match body.position.distance(vec2::Position::new(0, 0)) {
+                    37 => assert_eq!(region, Region::Hilda, "{body:?}"),
+                    38.. => assert!(matches!(region, Region::Trojan | Region::Greek), "{body:?}"),
+                    _ => assert!(matches!(region, Region::Belt | Region::Hilda), "{body:?}"),
+                }
+            }
+            for region in [Region::Belt, Region::Trojan, Region::Greek, Region::Hilda] {
+                assert!(counts.get(&region).copied().unwrap_or(0) > 50, "{seed}");
+            }
+        }
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_minor_body_colour() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut body = |ice, ore| {
+            MinorBody::new(
+                "Rock",
+                &mut id_generator,
+                vec2::Position::new(0, 0),
+                0.2,
+                ice,
+                ore,
+            )
+        };
+        let (icy, also_icy, rocky, bare) = (body(3, 0), body(3, 0), body(0, 3), body(0, 0));
+        assert_eq!(icy.colour(), also_icy.colour());
+        assert_ne!(icy.colour(), rocky.colour());
+        assert_ne!(icy.colour(), bare.colour());
+        assert_eq!(bare.colour(), "#606060");
+        assert_eq!(icy.colour(), "#6060a8");
+    }
 }
@@ -0,0 +1,174 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Compact encoding for warheads
+//!
+//! Late in the game there can be a great many warheads in flight, most of them
+//! launched in volleys by a few players. This stores what a volley has in
+//! common once per group, and each warhead's id and position relative to the
+//! rest of its group. Used for the warheads in a [`crate::view::PlayerView`]
+//! with `#[serde(with = "crate::compact")]`
+
+use std::collections::HashMap;
+
+#[cfg(feature = "client")]
+use serde::{Deserialize, Deserializer};
+#[cfg(feature = "server")]
+use serde::{Serialize, Serializer};
+
+use crate::{sorted_by_id, stack::Warhead, vec2, EntityId, Phase, PlayerId};
+
+/// Warheads grouped by everything but their id, position, and velocity
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactWarheads {
+    pub groups: Vec<WarheadGroup>,
+}
+
+/// Warheads with the same owner, blast radius, damage, and launch time
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarheadGroup {
+    pub owner: PlayerId,
+    pub blast_radius: u64,
+    pub damage: u8,
+    pub created: Option<(u64, Phase)>,
+    /// Position of the first warhead in the group
+    pub base: vec2::Position,
+    /// Each warhead in id order, as `[id, q, r, velocity q, velocity r]`,
+    /// where the id is relative to the previous warhead's and the position is
+    /// relative to the base
+    pub entries: Vec<[i64; 5]>,
+}
+
+impl CompactWarheads {
+    pub fn new(warheads: &HashMap<EntityId, Warhead>) -> Self {
+        let mut groups: Vec<(WarheadGroup, u64)> = Vec::new();
+        for (id, warhead) in sorted_by_id(warheads) {
+            let index = match groups.iter().position(|(group, _)| {
+                group.owner == warhead.owner
+                    && group.blast_radius == warhead.blast_radius
+                    && group.damage == warhead.damage
+                    && group.created == warhead.created
+            }) {
+                Some(index) => index,
+                None => {
+                    groups.push((
+                        WarheadGroup {
+                            owner: warhead.owner,
+                            blast_radius: warhead.blast_radius,
+                            damage: warhead.damage,
+                            created: warhead.created,
+                            base: warhead.position,
+                            entries: Vec::new(),
+                        },
+                        0,
+                    ));
+                    groups.len() - 1
+                }
+            };
+            let (group, last_id) = &mut groups[index];
+            let offset = warhead.position - group.base;
+            group.entries.push([
+                id.0.wrapping_sub(*last_id) as i64,
+                offset.q,
+                offset.r,
+                warhead.velocity.q,
+                warhead.velocity.r,
+            ]);
+            *last_id = id.0;
+        }
+        Self {
+            groups: groups.into_iter().map(|(group, _)| group).collect(),
+        }
+    }
+
+    /// Expand back into the full map of warheads
+    pub fn to_map(&self) -> HashMap<EntityId, Warhead> {
+        let mut warheads = HashMap::new();
+        for group in &self.groups {
+            let mut id = 0_u64;
+            for [id_delta, q, r, velocity_q, velocity_r] in &group.entries {
+                id = id.wrapping_add(*id_delta as u64);
+                let warhead = Warhead {
+                    id: EntityId(id),
+                    position: group.base + vec2::Displacement::new(*q, *r),
+                    velocity: vec2::Displacement::new(*velocity_q, *velocity_r),
+                    owner: group.owner,
+                    blast_radius: group.blast_radius,
+                    damage: group.damage,
+                    created: group.created,
+                };
+                warheads.insert(warhead.id, warhead);
+            }
+        }
+        warheads
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn serialize<S: Serializer>(
+    warheads: &HashMap<EntityId, Warhead>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    CompactWarheads::new(warheads).serialize(serializer)
+}
+
+#[cfg(feature = "client")]
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<EntityId, Warhead>, D::Error> {
+    CompactWarheads::deserialize(deserializer).map(|compact| compact.to_map())
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::EntityIdGenerator;
+
+    #[test]
+    fn test_round_trip() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut warheads = HashMap::new();
+        for index in 0..100_i64 {
+            let mut warhead = Warhead::new(
+                &mut id_generator,
+                vec2::Position::new(20 + index % 7, -10 - index % 5),
+                vec2::Displacement::new(index % 3 - 1, 2),
+                ((index % 3) as u8).into(),
+            );
+            warhead.created = Some((4 + (index % 2) as u64, Phase::Ordnance));
+            if index % 10 == 0 {
+                warhead.blast_radius = 1;
+            }
+            warheads.insert(warhead.id, warhead);
+        }
+
+        let compact = CompactWarheads::new(&warheads);
+        assert_eq!(compact.to_map(), warheads);
+
+        let full = serde_json::to_string(&warheads).unwrap().len();
+        let compact = serde_json::to_string(&compact).unwrap().len();
+        assert!(compact * 2 < full, "{compact} bytes compact vs {full} full");
+    }
+}
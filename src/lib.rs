@@ -32,8 +32,9 @@
 //! phase, and orders are received from all players to be resolved
 //! simultaneously.
 //!
-//! 1. The economic phase is when production, cargo transfer, fuel transfer,
-//!    reload, repair, and stack transfer orders are issued and resolved
+//! 1. The economic phase is when production, conversion, cargo transfer, fuel
+//!    transfer, reload, repair, and stack transfer orders are issued and
+//!    resolved
 //! 2. The ordnance phase is when ordnance launching and arming orders are
 //!    issued and resolved
 //! 3. The combat phase is when direct-fire weapons are issued and resolved
@@ -42,24 +43,51 @@
 #![forbid(unsafe_code)]
 
 use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::collections::HashSet;
 
 use astronomical::{MajorBody, MinorBody};
 #[cfg(feature = "server")]
-use rand::SeedableRng;
+use lobby::GameCreationError;
+#[cfg(feature = "server")]
+use rand::{Rng, SeedableRng};
 #[cfg(feature = "server")]
 use rand_chacha::ChaCha20Rng;
-use serde::{Deserialize, Serialize};
+use rules::RuleSet;
+#[cfg(feature = "server")]
+use scenario::{MajorBodySpec, ScenarioSpec};
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::Deserialize;
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::Serialize;
 use stack::{Stack, Warhead};
 
 pub mod astronomical;
+#[cfg(feature = "compact-warheads")]
+pub mod compact;
+pub mod lobby;
 pub mod order;
+pub mod report;
+#[cfg(feature = "server")]
+pub mod resolution;
+pub mod rules;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod save;
+pub mod scenario;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod spatial;
 pub mod stack;
+#[cfg(feature = "server")]
+pub mod submission;
 pub mod vec2;
+pub mod view;
 
 /// The current phase within the round
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Phase {
     Economic,
     Ordnance,
@@ -67,98 +95,259 @@ pub enum Phase {
     Movement,
 }
 
+/// How the game ended
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    /// Only this player has anything left
+    Win(PlayerId),
+    /// Every remaining player was eliminated at once
+    Draw,
+}
+
+/// How much changed between two states of the game
+///
+/// Levels are ordered from least to most activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActivityLevel {
+    /// Nothing changed but the round and phase
+    None,
+    /// Stacks and warheads moved, but nothing else changed
+    Movement,
+    /// Something was built, used, damaged, destroyed, or changed hands, or a
+    /// player was eliminated
+    Meaningful,
+}
+
 /// The state of the game
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GameState {
     pub major_bodies: HashMap<EntityId, MajorBody>,
     pub minor_bodies: HashMap<EntityId, MinorBody>,
     pub stacks: HashMap<EntityId, Stack>,
     pub warheads: HashMap<EntityId, Warhead>,
+    pub round: u64,
     pub phase: Phase,
+    pub num_players: u8,
+    pub rules: RuleSet,
+    /// Set once the game has ended
+    pub outcome: Option<GameOutcome>,
 }
 impl GameState {
     #[cfg(feature = "server")]
     /// Generate a new game with random solar system configuration
     ///
     /// 1 hex = 1/16 AU
+    ///
+    /// # Panics
+    ///
+    /// If there aren't 2 to 6 players
     pub fn new(
         seed: &<ChaCha20Rng as SeedableRng>::Seed,
         num_players: u8,
         id_generator: &mut EntityIdGenerator,
     ) -> Self {
-        use std::{
-            cmp::{max, min},
-            f64::consts::{PI, TAU},
+        Self::from_scenario(seed, num_players, &ScenarioSpec::default(), id_generator)
+            .expect("did not have 2-6 players")
+    }
+
+    #[cfg(feature = "server")]
+    /// Generate a new game, using the scenario to fix parts of the solar
+    /// system configuration
+    ///
+    /// Anything the scenario leaves unspecified is randomly generated, as in
+    /// [`GameState::new`]
+    ///
+    /// Fails if there aren't 2 to 6 players, or if the scenario's starting
+    /// loadout can't be built, would put a starting stack in a major body's
+    /// hex or in the hex of one of the scenario's minor bodies, or would put
+    /// two starting stacks in the same hex. Randomly generated minor bodies in
+    /// a starting stack's hex are moved out of the way instead
+    pub fn from_scenario(
+        seed: &<ChaCha20Rng as SeedableRng>::Seed,
+        num_players: u8,
+        scenario: &ScenarioSpec,
+        id_generator: &mut EntityIdGenerator,
+    ) -> Result<Self, GameCreationError> {
+        if !(2..=6).contains(&num_players) {
+            return Err(GameCreationError::InvalidPlayerCount);
+        }
+        if !scenario.loadout.is_valid() {
+            return Err(GameCreationError::InvalidLoadout);
+        }
+
+        let mut rng = ChaCha20Rng::from_seed(*seed);
+
+        let mut stacks = HashMap::new();
+
+        // the major bodies are generated even if the scenario fixes them, so
+        // that the rest of the system is generated the same way; the ids
+        // they'd have used aren't taken
+        let (major_bodies, mars_position, jupiter_angle) = match &scenario.major_bodies {
+            Some(specs) => {
+                let (_, mars_position, jupiter_angle) =
+                    Self::generate_major_bodies(&mut rng, &mut EntityIdGenerator::new());
+                (
+                    MajorBodySpec::build_all(specs, id_generator),
+                    mars_position,
+                    jupiter_angle,
+                )
+            }
+            None => Self::generate_major_bodies(&mut rng, id_generator),
         };
+        let terra_position = major_bodies
+            .values()
+            .find(|body| body.name == "Terra")
+            .map_or(vec2::Position::from_polar(16, 0.0), |terra| terra.position);
 
-        use rand::distributions::{weighted::WeightedIndex, Distribution, Uniform};
+        // generate minor bodies
+        let mut minor_bodies = match &scenario.minor_bodies {
+            Some(specs) => specs
+                .iter()
+                .map(|spec| {
+                    let body = spec.build(id_generator);
+                    (body.id, body)
+                })
+                .collect(),
+            None => {
+                Self::generate_minor_bodies(&mut rng, id_generator, mars_position, jupiter_angle)
+            }
+        };
 
-        use crate::stack::{CargoHold, Factory, FuelTank, Habitat};
+        // check where the starting stacks go before building any
+        let mut starting_hexes = HashSet::new();
+        for player in 0..num_players {
+            let (offset, _) = starting_orbit(player, num_players);
+            for spec in &scenario.loadout.stacks {
+                let position = spec.position(
+                    terra_position + offset,
+                    starting_sixths(player, num_players),
+                );
+                if major_bodies.values().any(|body| body.position == position) {
+                    return Err(GameCreationError::StartingStackInMajorBody);
+                }
+                if !starting_hexes.insert(position) {
+                    return Err(GameCreationError::StartingStacksOverlap);
+                }
+                if scenario.minor_bodies.is_some()
+                    && minor_bodies.values().any(|body| body.position == position)
+                {
+                    return Err(GameCreationError::StartingStackOnMinorBody);
+                }
+            }
+        }
+
+        // generate starting stacks
+        for player in 0..num_players {
+            let (offset, velocity) = starting_orbit(player, num_players);
+            for spec in &scenario.loadout.stacks {
+                let stack = spec.build(
+                    id_generator,
+                    player.into(),
+                    STARTING_STATION_NAMES[player as usize],
+                    (terra_position + offset, velocity),
+                    starting_sixths(player, num_players),
+                );
+                stacks.insert(stack.id, stack);
+            }
+        }
 
-        assert!((2..=6).contains(&num_players), "did not have 2-6 players");
+        if scenario.minor_bodies.is_none() {
+            Self::clear_starting_hexes(&major_bodies, &mut minor_bodies, &stacks);
+        }
 
-        let mut rng = ChaCha20Rng::from_seed(*seed);
+        Ok(Self {
+            major_bodies,
+            minor_bodies,
+            stacks,
+            warheads: HashMap::new(),
+            round: 1,
+            phase: Phase::Economic,
+            num_players,
+            rules: scenario.rules.clone(),
+            outcome: None,
+        })
+    }
+
+    #[cfg(feature = "server")]
+    /// Randomly generate the major bodies - the sun, planets, and their moons
+    ///
+    /// Also returns the position of Mars and the angle of Jupiter, which the
+    /// minor bodies are placed around
+    fn generate_major_bodies(
+        rng: &mut ChaCha20Rng,
+        id_generator: &mut EntityIdGenerator,
+    ) -> (HashMap<EntityId, MajorBody>, vec2::Position, f64) {
+        use std::f64::consts::TAU;
+
+        use rand::distributions::{Distribution, Uniform};
 
         let mut major_bodies = HashMap::new();
-        let mut minor_bodies = HashMap::new();
-        let mut stacks = HashMap::new();
 
-        // generate major bodies
         let angle_distribution = Uniform::from(0.0..TAU);
 
         // sol
-        let sol = MajorBody::new(
+        let mut sol = MajorBody::new(
             "Sol",
             id_generator,
             vec2::Position::new(0, 0),
             0.8,
             "#ffff00",
         );
+        sol.gravity = 3;
         major_bodies.insert(sol.id, sol);
 
         // mercury
-        let mercury_angle = angle_distribution.sample(&mut rng);
+        let mercury_angle = angle_distribution.sample(rng);
         let mercury = MajorBody::new(
             "Mercury",
             id_generator,
-            (6.0 * mercury_angle.cos(), 6.0 * mercury_angle.sin()).into(),
+            vec2::Position::from_polar(6, mercury_angle),
             0.3,
             "#404040",
         );
         major_bodies.insert(mercury.id, mercury);
 
         // venus
-        let venus_angle = angle_distribution.sample(&mut rng);
+        let venus_angle = angle_distribution.sample(rng);
         let venus = MajorBody::new(
             "Venus",
             id_generator,
-            (12.0 * venus_angle.cos(), 12.0 * venus_angle.sin()).into(),
+            vec2::Position::from_polar(12, venus_angle),
             0.6,
             "#ffc000",
         );
         major_bodies.insert(venus.id, venus);
 
         // terra + luna - always at 3 o'clock
-        let terra = MajorBody::new("Terra", id_generator, (16.0, 0.0).into(), 0.6, "#0000ff");
-        let luna = MajorBody::new(
+        let terra = MajorBody::new(
+            "Terra",
+            id_generator,
+            vec2::Position::from_polar(16, 0.0),
+            0.6,
+            "#0000ff",
+        );
+        let mut luna = MajorBody::new(
             "Luna",
             id_generator,
             terra.position + vec2::Displacement::new(3, 2),
             0.4,
             "#808080",
         );
-        let terra_position = terra.position;
+        luna.parent = Some(terra.id);
         major_bodies.insert(terra.id, terra);
         major_bodies.insert(luna.id, luna);
 
         // mars
-        let mars_angle = angle_distribution.sample(&mut rng);
+        let mars_angle = angle_distribution.sample(rng);
         let mars = MajorBody::new(
             "Mars",
             id_generator,
-            (24.0 * mars_angle.cos(), 24.0 * mars_angle.sin()).into(),
+            vec2::Position::from_polar(24, mars_angle),
             0.5,
             "#ff0000",
         );
@@ -166,41 +355,99 @@ impl GameState {
         major_bodies.insert(mars.id, mars);
 
         // jupiter + moons
-        let jupiter_angle = angle_distribution.sample(&mut rng);
-        let jupiter = MajorBody::new(
+        let jupiter_angle = angle_distribution.sample(rng);
+        let mut jupiter = MajorBody::new(
             "Jupiter",
             id_generator,
-            (40.0 * jupiter_angle.cos(), 40.0 * jupiter_angle.sin()).into(),
+            vec2::Position::from_polar(40, jupiter_angle),
             0.8,
             "#ffc000",
         );
-        let europa = MajorBody::new(
+        let mut europa = MajorBody::new(
             "Europa",
             id_generator,
             jupiter.position + vec2::Displacement::new(0, 3),
             0.3,
             "#a0a0ff",
         );
-        let callisto = MajorBody::new(
+        let mut callisto = MajorBody::new(
             "Callisto",
             id_generator,
             jupiter.position + vec2::Displacement::new(-4, 0),
             0.3,
             "#404040",
         );
-        let ganymede = MajorBody::new(
+        let mut ganymede = MajorBody::new(
             "Ganymede",
             id_generator,
             jupiter.position + vec2::Displacement::new(4, -2),
             0.3,
             "#404040",
         );
+        jupiter.gravity = 2;
+        for moon in [&mut europa, &mut callisto, &mut ganymede] {
+            moon.parent = Some(jupiter.id);
+        }
         major_bodies.insert(jupiter.id, jupiter);
         major_bodies.insert(europa.id, europa);
         major_bodies.insert(callisto.id, callisto);
         major_bodies.insert(ganymede.id, ganymede);
 
-        // generate minor bodies
+        (major_bodies, mars_position, jupiter_angle)
+    }
+
+    #[cfg(feature = "server")]
+    /// Move any randomly generated minor body sitting on a starting stack's
+    /// hex
+    ///
+    /// The body is pushed directly away from Sol, one hex at a time, until it
+    /// reaches a hex holding no stack and no other body
+    fn clear_starting_hexes(
+        major_bodies: &HashMap<EntityId, MajorBody>,
+        minor_bodies: &mut HashMap<EntityId, MinorBody>,
+        stacks: &HashMap<EntityId, Stack>,
+    ) {
+        let overlapping = sorted_by_id(minor_bodies)
+            .into_iter()
+            .filter(|(_, body)| stacks.values().any(|stack| stack.position == body.position))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in overlapping {
+            let mut position = minor_bodies[&id].position;
+            let step = vec2::Displacement::from(
+                position
+                    .to_displacement()
+                    .nearest_direction()
+                    .unwrap_or(vec2::Direction::East),
+            );
+            let occupied = |position: vec2::Position| {
+                stacks.values().any(|stack| stack.position == position)
+                    || major_bodies.values().any(|body| body.position == position)
+                    || minor_bodies
+                        .iter()
+                        .any(|(other, body)| *other != id && body.position == position)
+            };
+            while occupied(position) {
+                position += step;
+            }
+            minor_bodies.get_mut(&id).unwrap().position = position;
+        }
+    }
+
+    #[cfg(feature = "server")]
+    /// Randomly generate the minor bodies - the moons of Mars and the asteroids
+    fn generate_minor_bodies(
+        rng: &mut ChaCha20Rng,
+        id_generator: &mut EntityIdGenerator,
+        mars_position: vec2::Position,
+        jupiter_angle: f64,
+    ) -> HashMap<EntityId, MinorBody> {
+        use std::{
+            cmp::{max, min},
+            f64::consts::PI,
+        };
+
+        let mut minor_bodies = HashMap::new();
 
         // phobos, deimos
         let phobos = MinorBody::new(
@@ -240,15 +487,13 @@ impl GameState {
         let mut asteroid_name_generator = AsteroidNameGenerator::new();
 
         // asteroid belt = radius 29 - 36
-        let resource_values = [0, 1, 2, 3, 4, 5, 6];
-        let resource_index_distribution = WeightedIndex::new([7, 6, 5, 4, 3, 2, 1]).unwrap();
         for q in -36_i64..=36 {
             for r in max(-36, -q - 36)..=min(36, -q + 36) {
                 if (q.unsigned_abs() + r.unsigned_abs() + (q + r).unsigned_abs()) / 2 < 29 {
                     continue;
                 }
-                let ice_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
-                let ore_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
+                let ice_abundance = sample_abundance(rng);
+                let ore_abundance = sample_abundance(rng);
                 if ice_abundance == 0 && ore_abundance == 0 {
                     continue;
                 }
@@ -267,16 +512,15 @@ impl GameState {
         // trojans
         for distance in 38..=42 {
             for step in -15..=15 {
-                let ice_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
-                let ore_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
+                let ice_abundance = sample_abundance(rng);
+                let ore_abundance = sample_abundance(rng);
                 if ice_abundance == 0 && ore_abundance == 0 {
                     continue;
                 }
 
                 let angle_delta = step as f64 / 180.0 * PI;
                 let angle = jupiter_angle + angle_delta + PI / 3.0;
-                let position =
-                    (distance as f64 * angle.cos(), distance as f64 * angle.sin()).into();
+                let position = vec2::Position::from_polar(distance, angle);
                 if minor_bodies
                     .iter()
                     .any(|(_, body)| body.position == position)
@@ -299,16 +543,15 @@ impl GameState {
         // greeks
         for distance in 38..=42 {
             for step in -15..=15 {
-                let ice_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
-                let ore_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
+                let ice_abundance = sample_abundance(rng);
+                let ore_abundance = sample_abundance(rng);
                 if ice_abundance == 0 && ore_abundance == 0 {
                     continue;
                 }
 
                 let angle_delta = step as f64 / 180.0 * PI;
                 let angle = jupiter_angle + angle_delta - PI / 3.0;
-                let position =
-                    (distance as f64 * angle.cos(), distance as f64 * angle.sin()).into();
+                let position = vec2::Position::from_polar(distance, angle);
                 if minor_bodies
                     .iter()
                     .any(|(_, body)| body.position == position)
@@ -331,16 +574,15 @@ impl GameState {
         // additional hildas
         for distance in 32..38 {
             for step in -15..=15 {
-                let ice_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
-                let ore_abundance = resource_values[resource_index_distribution.sample(&mut rng)];
+                let ice_abundance = sample_abundance(rng);
+                let ore_abundance = sample_abundance(rng);
                 if ice_abundance == 0 && ore_abundance == 0 {
                     continue;
                 }
 
                 let angle_delta = step as f64 / 180.0 * PI;
                 let angle = jupiter_angle + angle_delta + PI;
-                let position =
-                    (distance as f64 * angle.cos(), distance as f64 * angle.sin()).into();
+                let position = vec2::Position::from_polar(distance, angle);
                 if minor_bodies
                     .iter()
                     .any(|(_, body)| body.position == position)
@@ -360,149 +602,413 @@ impl GameState {
             }
         }
 
-        // generate starting stacks
-        const STARTING_STATION_NAMES: [&str; 6] = [
-            "Space Station Freedom",
-            "Mir",
-            "Tiangong",
-            "Bharatiya Antariksha Station",
-            "Tokyo Gateway",
-            "Berlin Highport",
-        ];
-        let starting_station_orbital_elements = match num_players {
-            2 => vec![
-                (
-                    vec2::Displacement::new(0, -1),
-                    vec2::Displacement::new(1, 1),
-                ),
-                (
-                    vec2::Displacement::new(0, 1),
-                    vec2::Displacement::new(-1, -1),
-                ),
-            ],
-            3 => vec![
-                (
-                    vec2::Displacement::new(0, -1),
-                    vec2::Displacement::new(1, 1),
-                ),
-                (
-                    vec2::Displacement::new(1, 1),
-                    vec2::Displacement::new(-1, 0),
-                ),
-                (
-                    vec2::Displacement::new(-1, 0),
-                    vec2::Displacement::new(0, -1),
-                ),
-            ],
-            4 => vec![
-                (
-                    vec2::Displacement::new(0, -1),
-                    vec2::Displacement::new(1, 1),
-                ),
-                (vec2::Displacement::new(1, 0), vec2::Displacement::new(0, 1)),
-                (
-                    vec2::Displacement::new(0, 1),
-                    vec2::Displacement::new(-1, -1),
-                ),
-                (
-                    vec2::Displacement::new(-1, 0),
-                    vec2::Displacement::new(0, -1),
-                ),
-            ],
-            5 => vec![
-                (
-                    vec2::Displacement::new(0, -1),
-                    vec2::Displacement::new(1, 1),
-                ),
-                (vec2::Displacement::new(1, 0), vec2::Displacement::new(0, 1)),
-                (
-                    vec2::Displacement::new(1, 1),
-                    vec2::Displacement::new(-1, 0),
-                ),
-                (
-                    vec2::Displacement::new(0, 1),
-                    vec2::Displacement::new(-1, -1),
-                ),
-                (
-                    vec2::Displacement::new(-1, 0),
-                    vec2::Displacement::new(0, -1),
-                ),
-            ],
-            6 => vec![
-                (
-                    vec2::Displacement::new(0, -1),
-                    vec2::Displacement::new(1, 1),
-                ),
-                (vec2::Displacement::new(1, 0), vec2::Displacement::new(0, 1)),
-                (
-                    vec2::Displacement::new(1, 1),
-                    vec2::Displacement::new(-1, 0),
-                ),
-                (
-                    vec2::Displacement::new(0, 1),
-                    vec2::Displacement::new(-1, -1),
-                ),
-                (
-                    vec2::Displacement::new(-1, 0),
-                    vec2::Displacement::new(0, -1),
-                ),
-                (
-                    vec2::Displacement::new(-1, -1),
-                    vec2::Displacement::new(1, 0),
-                ),
-            ],
-            _ => panic!("unexpected number of players"),
+        minor_bodies
+    }
+}
+
+/// The default name of each player's starting stacks
+#[cfg(feature = "server")]
+const STARTING_STATION_NAMES: [&str; 6] = [
+    "Space Station Freedom",
+    "Mir",
+    "Tiangong",
+    "Bharatiya Antariksha Station",
+    "Tokyo Gateway",
+    "Berlin Highport",
+];
+
+/// Relative likelihood of each resource abundance for an asteroid, indexed by
+/// abundance
+#[cfg(feature = "server")]
+const ABUNDANCE_WEIGHTS: [u64; 7] = [7, 6, 5, 4, 3, 2, 1];
+
+/// The starting position relative to Terra and velocity of a player's station
+///
+/// Stations are placed by rotating the first station's orbit around Terra by
+/// `player * 6 / num_players` sixths of a turn, rounded down. When the number
+/// of players divides 6 this is exactly symmetric; with 4 players the stations
+/// are at sixths 0, 1, 3, and 4, and with 5 at sixths 0 through 4
+#[cfg(feature = "server")]
+fn starting_orbit(player: u8, num_players: u8) -> (vec2::Displacement, vec2::Displacement) {
+    let sixths = starting_sixths(player, num_players);
+    (
+        vec2::Displacement::new(0, -1).rotate_clockwise(sixths),
+        vec2::Displacement::new(1, 1).rotate_clockwise(sixths),
+    )
+}
+
+/// How many sixths of a turn the player's starting orbit is rotated from the
+/// first player's
+#[cfg(feature = "server")]
+fn starting_sixths(player: u8, num_players: u8) -> usize {
+    assert!(
+        (1..=6).contains(&num_players),
+        "unexpected number of players"
+    );
+    player as usize * 6 / num_players as usize
+}
+
+/// Where something moving along `path` at a steady pace is, `step` steps
+/// into a move of `steps` steps
+fn position_partway(path: &[vec2::Position], (step, steps): (usize, usize)) -> vec2::Position {
+    path[(step * (path.len() - 1) * 2 + steps) / (steps * 2)]
+}
+
+/// The fewest hexes between two things moving along `path` and `other` at
+/// a steady pace over the same move, with where the first was when it first
+/// got that close, and how far through the move that was
+fn closest_along(
+    path: &[vec2::Position],
+    other: &[vec2::Position],
+) -> (u64, vec2::Position, (usize, usize)) {
+    let steps = (path.len().max(other.len()) - 1).max(1);
+    (0..=steps)
+        .map(|step| {
+            let position = position_partway(path, (step, steps));
+            let distance = position.distance(position_partway(other, (step, steps)));
+            (distance, position, (step, steps))
+        })
+        .min_by_key(|(distance, _, _)| *distance)
+        .expect("there is always at least one step")
+}
+
+/// Randomly pick a resource abundance for an asteroid
+#[cfg(feature = "server")]
+fn sample_abundance(rng: &mut impl Rng) -> u64 {
+    let mut choice = rng.gen_range(0..ABUNDANCE_WEIGHTS.iter().sum::<u64>());
+    for (abundance, weight) in ABUNDANCE_WEIGHTS.iter().enumerate() {
+        if choice < *weight {
+            return abundance as u64;
+        }
+        choice -= weight;
+    }
+    unreachable!("choice is less than the total weight")
+}
+
+impl GameState {
+    /// Totals of everything resolution should only move around, for checking
+    /// that resolution neither creates nor destroys matter
+    pub fn conserved_quantities(&self) -> Conserved {
+        let mut conserved = Conserved {
+            ice: 0,
+            ore: 0,
+            materials: 0,
+            fuel: 0,
+            warheads: self.warheads.len() as u64,
+            component_mass: 0,
         };
-        for player in 0..num_players {
-            let mut station = Stack::new(
-                STARTING_STATION_NAMES[player as usize],
-                id_generator,
-                terra_position + starting_station_orbital_elements[player as usize].0,
-                starting_station_orbital_elements[player as usize].1,
-                player.into(),
-            );
+        for stack in self.stacks.values() {
+            let cargo = stack.cargo();
+            conserved.ice += cargo.ice;
+            conserved.ore += cargo.ore;
+            conserved.materials += cargo.materials;
+            conserved.warheads += stack.total_warheads();
+            conserved.fuel += stack.fuel();
+            conserved.component_mass += stack.component_mass();
+        }
+        conserved
+    }
+}
 
-            let factory = Factory::new(id_generator);
-            station.factories.insert(factory.id, factory);
-
-            let habitat = Habitat::new(id_generator, player.into());
-            station.habitats.insert(habitat.id, habitat);
-
-            let mut fuel_tank = FuelTank::new(id_generator);
-            fuel_tank.fuel = 20;
-            station.fuel_tanks.insert(fuel_tank.id, fuel_tank);
-            let mut fuel_tank = FuelTank::new(id_generator);
-            fuel_tank.fuel = 20;
-            station.fuel_tanks.insert(fuel_tank.id, fuel_tank);
-
-            let mut cargo_hold = CargoHold::new(id_generator);
-            cargo_hold.inventory.materials = 20;
-            station.cargo_holds.insert(cargo_hold.id, cargo_hold);
-            let mut cargo_hold = CargoHold::new(id_generator);
-            cargo_hold.inventory.materials = 20;
-            station.cargo_holds.insert(cargo_hold.id, cargo_hold);
-            let mut cargo_hold = CargoHold::new(id_generator);
-            cargo_hold.inventory.materials = 20;
-            station.cargo_holds.insert(cargo_hold.id, cargo_hold);
-
-            stacks.insert(station.id, station);
+impl GameState {
+    /// Take a copy of the game state to try orders out on
+    ///
+    /// The client can resolve a player's own orders onto the snapshot to
+    /// preview their results, then throw the snapshot away
+    pub fn snapshot(&self) -> GameState {
+        self.clone()
+    }
+
+    /// Put back a previously taken snapshot
+    pub fn restore(&mut self, snapshot: GameState) {
+        *self = snapshot;
+    }
+}
+
+impl GameState {
+    /// The players in the game, in ascending order
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> {
+        (0..self.num_players).map(PlayerId)
+    }
+
+    /// Players still in the game - those controlling at least one stack
+    pub fn surviving_players(&self) -> Vec<PlayerId> {
+        self.players()
+            .filter(|player| {
+                self.stacks
+                    .values()
+                    .any(|stack| stack.controller() == Some(*player))
+            })
+            .collect()
+    }
+
+    /// The player's score: the mass of every component in the stacks they
+    /// control
+    ///
+    /// A damaged component is worth half its mass, rounded down, so wearing
+    /// an enemy down shows up even when nothing is destroyed
+    pub fn score(&self, player: PlayerId) -> u64 {
+        self.stacks
+            .values()
+            .filter(|stack| stack.controller() == Some(player))
+            .flat_map(|stack| stack.components_with_ids())
+            .map(|(_, component)| {
+                if component.damaged() {
+                    component.mass() / 2
+                } else {
+                    component.mass()
+                }
+            })
+            .sum()
+    }
+
+    /// Has the game ended?
+    pub fn is_over(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// How much has changed since an earlier state of this game
+    ///
+    /// For spotting stalled games: anything beyond stacks and warheads
+    /// coasting or burning counts as meaningful
+    pub fn activity_since(&self, previous: &GameState) -> ActivityLevel {
+        let summary = |stack: &stack::Stack| {
+            (
+                stack.owner,
+                stack.controller(),
+                stack.component_ids(),
+                stack.damaged_components(),
+                stack.cargo(),
+                stack.total_warheads(),
+            )
+        };
+        let same_stacks = self.stacks.len() == previous.stacks.len()
+            && self.stacks.iter().all(|(id, stack)| {
+                previous
+                    .stacks
+                    .get(id)
+                    .is_some_and(|before| summary(stack) == summary(before))
+            });
+        let same_warheads = self.warheads.len() == previous.warheads.len()
+            && self
+                .warheads
+                .keys()
+                .all(|id| previous.warheads.contains_key(id));
+        if !same_stacks
+            || !same_warheads
+            || self.surviving_players() != previous.surviving_players()
+            || self.outcome != previous.outcome
+        {
+            return ActivityLevel::Meaningful;
         }
 
+        let moved = self.stacks.values().any(|stack| {
+            let before = &previous.stacks[&stack.id];
+            stack.position != before.position
+                || stack.velocity != before.velocity
+                || stack.fuel() != before.fuel()
+        }) || self.warheads.values().any(|warhead| {
+            let before = &previous.warheads[&warhead.id];
+            warhead.position != before.position || warhead.velocity != before.velocity
+        });
+        if moved {
+            ActivityLevel::Movement
+        } else {
+            ActivityLevel::None
+        }
+    }
+
+    /// The (min, max) corners of the box enclosing every body, stack, and
+    /// warhead in the game
+    ///
+    /// An empty game's extent is just the origin
+    pub fn extent(&self) -> (vec2::Position, vec2::Position) {
+        let positions = self
+            .major_bodies
+            .values()
+            .map(|body| body.position)
+            .chain(self.minor_bodies.values().map(|body| body.position))
+            .chain(self.stacks.values().map(|stack| stack.position))
+            .chain(self.warheads.values().map(|warhead| warhead.position));
+        vec2::bounding_box(positions)
+            .unwrap_or((vec2::Position::new(0, 0), vec2::Position::new(0, 0)))
+    }
+
+    /// Velocity of `b` relative to `a`
+    ///
+    /// Only stacks and warheads have velocities; astronomical bodies don't
+    /// move, so this is `None` if either is a body or doesn't exist
+    pub fn relative_velocity(&self, a: EntityId, b: EntityId) -> Option<vec2::Displacement> {
+        Some(self.velocity_of(b)? - self.velocity_of(a)?)
+    }
+
+    fn velocity_of(&self, id: EntityId) -> Option<vec2::Displacement> {
+        self.stacks
+            .get(&id)
+            .map(|stack| stack.velocity)
+            .or_else(|| self.warheads.get(&id).map(|warhead| warhead.velocity))
+    }
+
+    /// The fewest hexes between a warhead and a stack over the move they just
+    /// made, or `None` if either doesn't exist
+    ///
+    /// Both are taken to have moved in a straight line at a steady pace from
+    /// where they were a turn ago to where they are now
+    pub fn closest_approach(&self, warhead: EntityId, target: EntityId) -> Option<u64> {
+        self.closest_point(warhead, target)
+            .map(|(distance, _, _)| distance)
+    }
+
+    /// The closest approach, with where the warhead was when it first got
+    /// that close, and how far through the move that was, as a number of
+    /// steps out of a total
+    pub(crate) fn closest_point(
+        &self,
+        warhead: EntityId,
+        target: EntityId,
+    ) -> Option<(u64, vec2::Position, (usize, usize))> {
+        let warhead = self.warheads.get(&warhead)?;
+        let target = self.stacks.get(&target)?;
+        let warhead_path = (warhead.position - warhead.velocity).line_to(warhead.position);
+        let target_path = (target.position - target.velocity).line_to(target.position);
+        Some(closest_along(&warhead_path, &target_path))
+    }
+
+    /// Every component in the game, with the ids of its stack and itself
+    pub fn all_components(
+        &self,
+    ) -> impl Iterator<Item = (EntityId, EntityId, &dyn stack::Component)> {
+        self.stacks.values().flat_map(|stack| {
+            stack
+                .components_with_ids()
+                .map(|(id, component)| (stack.id, id, component))
+        })
+    }
+
+    /// Every id in use - bodies, stacks, their components, and warheads
+    pub fn entity_ids(&self) -> Vec<EntityId> {
+        let mut ids = self
+            .major_bodies
+            .keys()
+            .chain(self.minor_bodies.keys())
+            .chain(self.stacks.keys())
+            .chain(self.warheads.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        for stack in self.stacks.values() {
+            ids.extend(stack.component_ids());
+        }
+        ids.sort();
+        ids
+    }
+
+    /// A checksum of the whole state, for checking that two copies agree
+    ///
+    /// Doesn't depend on `HashMap` iteration order
+    #[cfg(feature = "server")]
+    pub fn checksum(&self) -> u64 {
+        // converting to a value first sorts object keys
+        let canonical = serde_json::to_value(self)
+            .expect("game state should serialize")
+            .to_string();
+        // FNV-1a, which unlike the std hasher is fixed across versions
+        canonical.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Is the position on the map?
+    pub fn in_bounds(&self, position: vec2::Position) -> bool {
+        self.rules
+            .map_radius
+            .is_none_or(|radius| position.distance(vec2::Position::new(0, 0)) <= radius)
+    }
+
+    /// The centre of mass of the stacks the player controls, rounded to a
+    /// hex, or `None` if they control nothing
+    pub fn fleet_barycentre(&self, player: PlayerId) -> Option<vec2::Position> {
+        let (mass, q, r) = self
+            .stacks
+            .values()
+            .filter(|stack| stack.controller() == Some(player))
+            .fold((0.0, 0.0, 0.0), |(mass, q, r), stack| {
+                let weight = stack.mass() as f64;
+                (
+                    mass + weight,
+                    q + weight * stack.position.q as f64,
+                    r + weight * stack.position.r as f64,
+                )
+            });
+        if mass == 0.0 {
+            return None;
+        }
+        Some(vec2::Position::from_fractional(q / mass, r / mass))
+    }
+
+    /// The stacks within `radius` hexes of `center`, in id order
+    ///
+    /// This checks every stack, which is the fastest way to answer one query;
+    /// for many queries against the same state, use
+    /// [`GameState::spatial_index`]
+    pub fn stacks_within(&self, center: vec2::Position, radius: u64) -> Vec<EntityId> {
+        let mut ids = self
+            .stacks
+            .values()
+            .filter(|stack| stack.position.distance(center) <= radius)
+            .map(|stack| stack.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+}
+#[cfg(all(test, feature = "server"))]
+impl GameState {
+    /// An empty solar system to set up test cases in
+    pub(crate) fn empty() -> Self {
         Self {
-            major_bodies,
-            minor_bodies,
-            stacks,
+            major_bodies: HashMap::new(),
+            minor_bodies: HashMap::new(),
+            stacks: HashMap::new(),
             warheads: HashMap::new(),
+            round: 1,
             phase: Phase::Economic,
+            num_players: 2,
+            rules: RuleSet::default(),
+            outcome: None,
         }
     }
 }
 
+/// Totals of resources and mass across the whole game
+///
+/// Warheads include those in cargo, loaded into mounts, and in flight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conserved {
+    pub ice: u64,
+    pub ore: u64,
+    pub materials: u64,
+    pub fuel: u64,
+    pub warheads: u64,
+    pub component_mass: u64,
+}
+
 /// A player ID
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PlayerId(u8);
+impl PlayerId {
+    /// Default colours for players, in player order
+    const COLOURS: [&'static str; 6] = [
+        "#ff4040", "#4040ff", "#40c040", "#ffff40", "#c040ff", "#40ffff",
+    ];
+
+    /// The default colour to show the player's things in
+    pub fn colour(&self) -> &'static str {
+        Self::COLOURS[self.0 as usize % Self::COLOURS.len()]
+    }
+}
 impl From<u8> for PlayerId {
     fn from(value: u8) -> Self {
         Self(value)
@@ -521,7 +1027,8 @@ impl From<PlayerId> for u8 {
     any(feature = "client", feature = "server"),
     derive(Serialize, Deserialize)
 )]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EntityId(u64);
 impl From<u64> for EntityId {
     fn from(value: u64) -> Self {
@@ -534,7 +1041,24 @@ impl From<EntityId> for u64 {
     }
 }
 
+/// The entries of a map in order of id
+///
+/// `HashMap` iteration order changes from run to run, so resolution visits
+/// entities through this to resolve the same way every time
+pub(crate) fn sorted_by_id<T>(map: &HashMap<EntityId, T>) -> Vec<(EntityId, &T)> {
+    let mut entries = map
+        .iter()
+        .map(|(id, value)| (*id, value))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(id, _)| *id);
+    entries
+}
+
 /// An entity ID generator - server requires this as additional state
+///
+/// A saved game must store the generator alongside the [`GameState`], or
+/// resume it with [`EntityIdGenerator::current`] from when it was saved;
+/// otherwise ids of live entities will be handed out again
 #[cfg(feature = "server")]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EntityIdGenerator {
@@ -545,6 +1069,23 @@ impl EntityIdGenerator {
     pub fn new() -> Self {
         Self { next_id: 1 }
     }
+
+    /// Continue generating ids from `next`, as returned by
+    /// [`EntityIdGenerator::current`]
+    pub fn resume(next: u64) -> Self {
+        Self { next_id: next }
+    }
+
+    /// The next id to be generated
+    pub fn current(&self) -> u64 {
+        self.next_id
+    }
+}
+#[cfg(feature = "server")]
+impl Default for EntityIdGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 #[cfg(feature = "server")]
 impl Iterator for EntityIdGenerator {
@@ -563,8 +1104,353 @@ impl Iterator for EntityIdGenerator {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "server")]
+    use std::collections::HashSet;
+
+    #[cfg(feature = "server")]
     use super::*;
+    use crate::stack::CargoList;
+    #[cfg(feature = "server")]
+    use crate::{
+        scenario::MinorBodySpec,
+        stack::{Factory, Habitat},
+    };
+
+    #[test]
+    fn test_cargo_list_default() {
+        assert_eq!(CargoList::default(), CargoList::new(0, 0, 0, 0));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_id_generator_default() {
+        let mut id_generator = crate::EntityIdGenerator::default();
+        assert_eq!(id_generator.next(), Some(1.into()));
+        assert_eq!(id_generator.next(), Some(2.into()));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_players() {
+        let state = GameState::new(&[0; 32], 3, &mut EntityIdGenerator::new());
+        assert_eq!(
+            state.players().collect::<Vec<_>>(),
+            vec![PlayerId(0), PlayerId(1), PlayerId(2)]
+        );
+        assert_ne!(PlayerId(0).colour(), PlayerId(1).colour());
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_relative_velocity() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut ids = Vec::new();
+        for velocity in [
+            vec2::Displacement::new(2, -1),
+            vec2::Displacement::new(-1, 3),
+        ] {
+            let stack = Stack::new(
+                "Probe",
+                &mut id_generator,
+                vec2::Position::new(0, 0),
+                velocity,
+                0.into(),
+            );
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let body = MinorBody::new(
+            "Ceres",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            0.2,
+            1,
+            1,
+        );
+        let body_id = body.id;
+        state.minor_bodies.insert(body.id, body);
+
+        assert_eq!(
+            state.relative_velocity(ids[0], ids[1]),
+            Some(vec2::Displacement::new(-3, 4))
+        );
+        assert_eq!(
+            state.relative_velocity(ids[1], ids[0]),
+            Some(vec2::Displacement::new(3, -4))
+        );
+        assert_eq!(state.relative_velocity(ids[0], body_id), None);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_resume_id_generator() {
+        let mut id_generator = EntityIdGenerator::new();
+        let state = GameState::new(&[3; 32], 4, &mut id_generator);
+        let saved = serde_json::to_string(&id_generator).unwrap();
+        let current = id_generator.current();
+
+        let existing = state.entity_ids();
+        let mut loaded: EntityIdGenerator = serde_json::from_str(&saved).unwrap();
+        let mut resumed = EntityIdGenerator::resume(current);
+        for _ in 0..100 {
+            let id = resumed.next().unwrap();
+            assert_eq!(loaded.next(), Some(id));
+            assert!(!existing.contains(&id));
+        }
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_all_components() {
+        let state = GameState::new(&[5; 32], 3, &mut EntityIdGenerator::new());
+        let visited = state
+            .all_components()
+            .map(|(stack, id, component)| {
+                assert_eq!(component.id(), id);
+                assert!(state.stacks[&stack].find_component(id).is_some());
+                id
+            })
+            .collect::<Vec<_>>();
+        let unique = visited.iter().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), visited.len());
+        assert_eq!(
+            visited.len(),
+            state
+                .stacks
+                .values()
+                .map(|stack| stack.component_ids().len())
+                .sum::<usize>()
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_stacks_within() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut ids = Vec::new();
+        for position in [
+            vec2::Position::new(2, 2),
+            vec2::Position::new(3, 2),
+            vec2::Position::new(4, 2),
+        ] {
+            let stack = Stack::new(
+                "Probe",
+                &mut id_generator,
+                position,
+                vec2::Displacement::new(0, 0),
+                0.into(),
+            );
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+
+        assert_eq!(state.stacks_within(vec2::Position::new(2, 2), 1), ids[..2]);
+        assert_eq!(state.stacks_within(vec2::Position::new(2, 2), 0), ids[..1]);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_abundance_distribution() {
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let samples = 28_000;
+        let mut histogram = [0_u64; 7];
+        for _ in 0..samples {
+            histogram[sample_abundance(&mut rng) as usize] += 1;
+        }
+
+        let total_weight = ABUNDANCE_WEIGHTS.iter().sum::<u64>() as f64;
+        for (count, weight) in histogram.iter().zip(ABUNDANCE_WEIGHTS) {
+            let expected = samples as f64 * weight as f64 / total_weight;
+            assert!((*count as f64 - expected).abs() < expected * 0.1);
+        }
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_starting_stations_clear_of_bodies() {
+        let assert_clear = |state: &GameState| {
+            for stack in state.stacks.values() {
+                assert!(state
+                    .major_bodies
+                    .values()
+                    .all(|body| body.position != stack.position));
+                assert!(state
+                    .minor_bodies
+                    .values()
+                    .all(|body| body.position != stack.position));
+            }
+        };
+
+        for seed in 0..16 {
+            for num_players in 2..=6 {
+                let state = GameState::new(&[seed; 32], num_players, &mut EntityIdGenerator::new());
+                assert_clear(&state);
+            }
+        }
 
+        // a generated body right on a station is pushed outwards
+        let mut state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let station = vec2::Position::from_polar(16, 0.0) + crate::starting_orbit(0, 2).0;
+        let rock = MinorBody::new("Rock", &mut EntityIdGenerator::new(), station, 0.2, 1, 1);
+        let rock_id = rock.id;
+        state.minor_bodies = HashMap::from([(rock.id, rock)]);
+        GameState::clear_starting_hexes(
+            &state.major_bodies,
+            &mut state.minor_bodies,
+            &state.stacks,
+        );
+        assert_clear(&state);
+        let rock = &state.minor_bodies[&rock_id];
+        assert!(rock.position.to_displacement().norm() > station.to_displacement().norm());
+
+        // but a scenario's own bodies are where the scenario wants them
+        let scenario = ScenarioSpec {
+            minor_bodies: Some(vec![MinorBodySpec::new("Rock", station, 0.2, 1, 1)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            GameState::from_scenario(&[0; 32], 2, &scenario, &mut EntityIdGenerator::new())
+                .unwrap_err(),
+            GameCreationError::StartingStackOnMinorBody
+        );
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_extent() {
+        assert_eq!(
+            GameState::empty().extent(),
+            (vec2::Position::new(0, 0), vec2::Position::new(0, 0))
+        );
+
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let (min, max) = state.extent();
+        let encloses = |position: vec2::Position| {
+            (min.q..=max.q).contains(&position.q) && (min.r..=max.r).contains(&position.r)
+        };
+        assert!(encloses(vec2::Position::new(0, 0)));
+        let farthest = state
+            .minor_bodies
+            .values()
+            .max_by_key(|body| body.position.to_displacement().norm())
+            .unwrap();
+        assert!(encloses(farthest.position));
+        assert!(state.stacks.values().all(|stack| encloses(stack.position)));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_score() {
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let intact = state.score(1.into());
+        assert!(intact > 0);
+        assert_eq!(state.score(0.into()), intact);
+        assert_eq!(state.score(2.into()), 0);
+
+        let mut damaged = state.clone();
+        let factory = damaged
+            .stacks
+            .values_mut()
+            .find(|stack| stack.controller() == Some(1.into()) && !stack.factories().is_empty())
+            .unwrap()
+            .factories_mut()
+            .values_mut()
+            .next()
+            .unwrap();
+        factory.damaged = true;
+        assert_eq!(damaged.score(1.into()), intact - Factory::MASS / 2);
+        assert_eq!(damaged.score(0.into()), intact);
+    }
+
+    #[cfg(feature = "server")]
     #[test]
-    fn test() {}
+    fn test_fleet_barycentre() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        assert_eq!(state.fleet_barycentre(0.into()), None);
+        for (name, position, factories) in [
+            ("Station", vec2::Position::new(0, 0), 4),
+            ("Scout", vec2::Position::new(10, 0), 0),
+        ] {
+            let mut stack = Stack::new(
+                name,
+                &mut id_generator,
+                position,
+                vec2::Displacement::new(0, 0),
+                0.into(),
+            );
+            let habitat = Habitat::new(&mut id_generator, 0.into());
+            stack.habitats_mut().insert(habitat.id, habitat);
+            for _ in 0..factories {
+                let factory = Factory::new(&mut id_generator);
+                stack.factories_mut().insert(factory.id, factory);
+            }
+            state.stacks.insert(stack.id, stack);
+        }
+
+        // 210 mass at the origin against 10 mass ten hexes away
+        assert_eq!(
+            state.fleet_barycentre(0.into()),
+            Some(vec2::Position::new(0, 0))
+        );
+        assert_eq!(state.fleet_barycentre(1.into()), None);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_activity_since() {
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let mut next = state.clone();
+        next.phase = Phase::Ordnance;
+        assert_eq!(next.activity_since(&state), ActivityLevel::None);
+        assert_eq!(state.activity_since(&state), ActivityLevel::None);
+
+        let id = *state.stacks.keys().next().unwrap();
+        next.stacks.get_mut(&id).unwrap().position += vec2::Displacement::new(1, 0);
+        assert_eq!(next.activity_since(&state), ActivityLevel::Movement);
+
+        let station = next.stacks.get_mut(&id).unwrap();
+        let cargo = station.cargo();
+        station.withdraw_cargo(cargo).unwrap();
+        assert_eq!(next.activity_since(&state), ActivityLevel::Meaningful);
+
+        let mut next = state.clone();
+        next.stacks.remove(&id);
+        assert_eq!(next.activity_since(&state), ActivityLevel::Meaningful);
+        assert_eq!(state.activity_since(&next), ActivityLevel::Meaningful);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_symmetric_starting_stations() {
+        for num_players in [2, 3, 6] {
+            let state = GameState::new(&[0; 32], num_players, &mut EntityIdGenerator::new());
+            let terra = state
+                .major_bodies
+                .values()
+                .find(|body| body.name == "Terra")
+                .unwrap()
+                .position;
+            let orbits = state
+                .players()
+                .map(|player| {
+                    let station = state
+                        .stacks
+                        .values()
+                        .find(|stack| stack.controller() == Some(player))
+                        .unwrap();
+                    (station.position - terra, station.velocity)
+                })
+                .collect::<Vec<_>>();
+
+            let step = 6 / num_players as usize;
+            for (index, (offset, velocity)) in orbits.iter().enumerate() {
+                let (next_offset, next_velocity) = orbits[(index + 1) % orbits.len()];
+                assert_eq!(offset.rotate_clockwise(step), next_offset);
+                assert_eq!(velocity.rotate_clockwise(step), next_velocity);
+            }
+        }
+    }
 }
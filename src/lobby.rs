@@ -0,0 +1,134 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Game creation
+//!
+//! The messages exchanged to set up a new game
+
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::{Deserialize, Serialize};
+
+use crate::{scenario::ScenarioSpec, view::PlayerView};
+#[cfg(feature = "server")]
+use crate::{EntityIdGenerator, GameState};
+
+/// A request to create a new game
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[derive(Debug, Clone)]
+pub struct GameCreationRequest {
+    pub num_players: u8,
+    pub seed: [u8; 32],
+    /// The scenario to play, or `None` for a randomly generated game
+    pub scenario: Option<ScenarioSpec>,
+}
+impl GameCreationRequest {
+    /// Create the requested game
+    ///
+    /// Returns the new game state, to be kept by the server, and the response
+    /// to send back
+    #[cfg(feature = "server")]
+    pub fn create(
+        &self,
+        id_generator: &mut EntityIdGenerator,
+    ) -> Result<(GameState, GameCreationResponse), GameCreationError> {
+        let state = GameState::from_scenario(
+            &self.seed,
+            self.num_players,
+            self.scenario.as_ref().unwrap_or(&ScenarioSpec::default()),
+            id_generator,
+        )?;
+        let response = GameCreationResponse {
+            seed: self.seed,
+            views: state
+                .players()
+                .map(|player| state.view_for(player))
+                .collect(),
+        };
+        Ok((state, response))
+    }
+}
+
+/// The response to a successful [`GameCreationRequest`]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[derive(Debug, Clone)]
+pub struct GameCreationResponse {
+    pub seed: [u8; 32],
+    /// The starting view for each player, in player order
+    pub views: Vec<PlayerView>,
+}
+
+/// Why a game could not be created
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameCreationError {
+    /// Games must have 2 to 6 players
+    InvalidPlayerCount,
+    /// The scenario's starting loadout can't be built
+    InvalidLoadout,
+    /// The scenario's starting loadout puts a stack in a major body's hex
+    StartingStackInMajorBody,
+    /// The scenario's starting loadout puts two stacks in the same hex
+    StartingStacksOverlap,
+    /// The scenario puts one of its minor bodies in a starting stack's hex
+    StartingStackOnMinorBody,
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_game() {
+        let request = GameCreationRequest {
+            num_players: 4,
+            seed: [7; 32],
+            scenario: None,
+        };
+        let (state, response) = request.create(&mut EntityIdGenerator::new()).unwrap();
+        assert_eq!(response.seed, [7; 32]);
+        assert_eq!(response.views.len(), 4);
+        for (player, view) in state.players().zip(&response.views) {
+            assert_eq!(view.player, player);
+        }
+
+        let request = GameCreationRequest {
+            num_players: 1,
+            ..request
+        };
+        assert_eq!(
+            request.create(&mut EntityIdGenerator::new()).unwrap_err(),
+            GameCreationError::InvalidPlayerCount
+        );
+
+        let mut scenario = ScenarioSpec::default();
+        scenario.loadout.stacks.clear();
+        let request = GameCreationRequest {
+            num_players: 2,
+            scenario: Some(scenario),
+            ..request
+        };
+        assert_eq!(
+            request.create(&mut EntityIdGenerator::new()).unwrap_err(),
+            GameCreationError::InvalidLoadout
+        );
+    }
+}
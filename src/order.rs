@@ -17,12 +17,161 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
+#[cfg(feature = "server")]
+use rand::Rng;
+#[cfg(any(feature = "client", feature = "server"))]
 use serde::{Deserialize, Serialize};
 
-use crate::{stack::CargoList, vec2, EntityId};
+use std::collections::HashMap;
+
+use crate::{
+    closest_along,
+    rules::RuleSet,
+    sorted_by_id,
+    stack::{
+        ArmourPlate, CargoHold, CargoList, Engine, Factory, FuelTank, Gun, Habitat, Miner,
+        MobilityStatus, Stack, WarheadMount,
+    },
+    vec2, EntityId, GameState, Phase, PlayerId,
+};
+#[cfg(feature = "server")]
+use crate::{
+    stack::{AnyComponent, HitOutcome, Warhead},
+    EntityIdGenerator,
+};
+
+/// Why an order could not be carried out
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderError {
+    /// The stack doesn't exist
+    NoSuchStack,
+    /// The target of the order doesn't exist (anymore)
+    ///
+    /// Since orders are resolved one at a time, the target may have been
+    /// destroyed by an order resolved earlier in the same phase; the order
+    /// then does nothing
+    TargetGone,
+    /// The component doesn't exist in the stack
+    NoSuchComponent,
+    /// The ordering player doesn't control the stack
+    NotControlled,
+    /// The stacks aren't close enough in position and velocity to transfer
+    NotRendezvoused,
+    /// The target is beyond the gun's effective range
+    OutOfRange,
+    /// A major body is in the way of the shot
+    NoLineOfSight,
+    /// The component is damaged and can't act
+    ComponentDamaged,
+    /// There isn't enough fuel
+    InsufficientFuel,
+    /// There isn't enough cargo
+    InsufficientCargo,
+    /// There isn't enough room for the result
+    CapacityExceeded,
+    /// The engines can't provide enough thrust
+    InsufficientThrust,
+    /// The component has already acted this phase
+    AlreadyActed,
+    /// The warhead mount isn't loaded
+    MountEmpty,
+    /// The warhead mount is already loaded
+    MountLoaded,
+    /// The component can't be produced
+    NotProducible,
+    /// The order was submitted for a different phase than the current one
+    WrongPhase,
+    /// More orders were submitted in one phase than the rules allow
+    TooManyOrders,
+    /// The order succeeded, but was undone because another order in the same
+    /// batch failed
+    RolledBack,
+    /// A quantity in the order is too large to work with
+    Overflow,
+    /// The game is over
+    GameOver,
+}
+
+/// All of one player's orders for a phase, in the order they were submitted
+///
+/// An empty list of orders is a pass
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub enum PlayerTurn {
+    Economic(Vec<EconomicOrder>),
+    Ordnance(Vec<Launch>),
+    Combat(Vec<Shoot>),
+    Movement(Vec<Burn>),
+}
+impl PlayerTurn {
+    /// The phase the orders are for
+    pub fn phase(&self) -> Phase {
+        match self {
+            PlayerTurn::Economic(_) => Phase::Economic,
+            PlayerTurn::Ordnance(_) => Phase::Ordnance,
+            PlayerTurn::Combat(_) => Phase::Combat,
+            PlayerTurn::Movement(_) => Phase::Movement,
+        }
+    }
+
+    /// Number of orders submitted
+    pub fn len(&self) -> usize {
+        match self {
+            PlayerTurn::Economic(orders) => orders.len(),
+            PlayerTurn::Ordnance(orders) => orders.len(),
+            PlayerTurn::Combat(orders) => orders.len(),
+            PlayerTurn::Movement(orders) => orders.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check that the orders can be accepted for resolution in the phase
+    ///
+    /// The orders must be for that phase, and there must be no more of them
+    /// than the rules allow
+    pub fn validate_phase(&self, phase: Phase, rules: &RuleSet) -> Result<(), OrderError> {
+        if self.phase() != phase {
+            return Err(OrderError::WrongPhase);
+        }
+        if self.len() > rules.max_orders_per_phase {
+            return Err(OrderError::TooManyOrders);
+        }
+        Ok(())
+    }
+}
+
+/// A single order for any phase
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[derive(Debug, Clone)]
+pub enum PhaseOrder {
+    Economic(EconomicOrder),
+    Ordnance(Launch),
+    Combat(Shoot),
+    Movement(Burn),
+}
+impl PhaseOrder {
+    /// The phase the order is for
+    pub fn phase(&self) -> Phase {
+        match self {
+            PhaseOrder::Economic(_) => Phase::Economic,
+            PhaseOrder::Ordnance(_) => Phase::Ordnance,
+            PhaseOrder::Combat(_) => Phase::Combat,
+            PhaseOrder::Movement(_) => Phase::Movement,
+        }
+    }
+}
 
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub enum EconomicOrder {
     Production(Production),
@@ -32,6 +181,26 @@ pub enum EconomicOrder {
     FactoryRepair(FactoryRepair),
     HabitatRepair(HabitatRepair),
     StackTransfer(StackTransfer),
+    Conversion(Conversion),
+    Jettison(Jettison),
+    Unload(Unload),
+}
+impl EconomicOrder {
+    /// The stack the order is given to
+    pub fn stack(&self) -> EntityId {
+        match self {
+            EconomicOrder::Production(order) => order.stack,
+            EconomicOrder::CargoTransfer(order) => order.stack,
+            EconomicOrder::FuelTransfer(order) => order.stack,
+            EconomicOrder::Reload(order) => order.stack,
+            EconomicOrder::FactoryRepair(order) => order.stack,
+            EconomicOrder::HabitatRepair(order) => order.stack,
+            EconomicOrder::StackTransfer(order) => order.stack,
+            EconomicOrder::Conversion(order) => order.stack,
+            EconomicOrder::Jettison(order) => order.stack,
+            EconomicOrder::Unload(order) => order.stack,
+        }
+    }
 }
 
 /// Produce a component
@@ -39,15 +208,19 @@ pub enum EconomicOrder {
 /// Materials are drawn from the cargo holds in the stack
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct Production {
     pub stack: EntityId,
     pub factory: EntityId,
     pub component: StackComponent,
 }
-#[cfg_attr(feature = "server", derive(Deserialize))]
-#[cfg_attr(feature = "client", derive(Serialize))]
-#[derive(Debug, Clone)]
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StackComponent {
     FuelTank,
     CargoHold,
@@ -61,38 +234,302 @@ pub enum StackComponent {
     ArmourPlate,
 }
 
+impl StackComponent {
+    /// Every kind of component
+    pub const ALL: [StackComponent; 10] = [
+        StackComponent::FuelTank,
+        StackComponent::CargoHold,
+        StackComponent::Engine,
+        StackComponent::Gun,
+        StackComponent::LaunchClamp,
+        StackComponent::WarheadBus,
+        StackComponent::Habitat,
+        StackComponent::Miner,
+        StackComponent::Factory,
+        StackComponent::ArmourPlate,
+    ];
+
+    /// Points of materials needed to produce the component, if it can be
+    /// produced and the cost doesn't overflow
+    pub fn production_cost(&self, rules: &RuleSet) -> Option<u64> {
+        self.mass()?.checked_mul(rules.materials_per_mass)
+    }
+
+    /// Mass of the component, if it can be produced
+    pub fn mass(&self) -> Option<u64> {
+        match self {
+            StackComponent::FuelTank => Some(FuelTank::MASS),
+            StackComponent::CargoHold => Some(CargoHold::MASS),
+            StackComponent::Engine => Some(Engine::MASS),
+            StackComponent::Gun => Some(Gun::MASS),
+            StackComponent::LaunchClamp => Some(WarheadMount::MASS),
+            StackComponent::WarheadBus => None,
+            StackComponent::Habitat => Some(Habitat::MASS),
+            StackComponent::Miner => Some(Miner::MASS),
+            StackComponent::Factory => Some(Factory::MASS),
+            StackComponent::ArmourPlate => Some(ArmourPlate::MASS),
+        }
+    }
+
+    /// Create a new component of this type
+    ///
+    /// Habitats are owned by `player`
+    #[cfg(feature = "server")]
+    pub(crate) fn build(
+        &self,
+        id_generator: &mut EntityIdGenerator,
+        player: PlayerId,
+    ) -> Option<AnyComponent> {
+        Some(match self {
+            StackComponent::FuelTank => AnyComponent::FuelTank(FuelTank::new(id_generator)),
+            StackComponent::CargoHold => AnyComponent::CargoHold(CargoHold::new(id_generator)),
+            StackComponent::Engine => AnyComponent::Engine(Engine::new(id_generator)),
+            StackComponent::Gun => AnyComponent::Gun(Gun::new(id_generator)),
+            StackComponent::LaunchClamp => {
+                AnyComponent::WarheadMount(WarheadMount::new(id_generator))
+            }
+            StackComponent::WarheadBus => return None,
+            StackComponent::Habitat => AnyComponent::Habitat(Habitat::new(id_generator, player)),
+            StackComponent::Miner => AnyComponent::Miner(Miner::new(id_generator)),
+            StackComponent::Factory => AnyComponent::Factory(Factory::new(id_generator)),
+            StackComponent::ArmourPlate => {
+                AnyComponent::ArmourPlate(ArmourPlate::new(id_generator))
+            }
+        })
+    }
+}
+impl Production {
+    #[cfg(feature = "server")]
+    pub fn resolve(
+        &self,
+        player: PlayerId,
+        state: &mut GameState,
+        id_generator: &mut EntityIdGenerator,
+    ) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        working_factory(stack, self.factory)?;
+        self.component.mass().ok_or(OrderError::NotProducible)?;
+        let materials = self
+            .component
+            .production_cost(&state.rules)
+            .ok_or(OrderError::Overflow)?;
+        let cost = CargoList::new(0, 0, materials, 0);
+        if !stack.cargo().contains(&cost) {
+            return Err(OrderError::InsufficientCargo);
+        }
+
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        stack.withdraw_cargo(cost)?;
+        stack.insert_component(self.component.build(id_generator, player).unwrap());
+        Ok(())
+    }
+}
+
 /// Transfer materials from one stack's cargo holds to another stack's
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct CargoTransfer {
     pub stack: EntityId,
     pub destination: EntityId,
-    pub amount: CargoList,
+    pub amount: TransferAmount<CargoList>,
+}
+
+impl CargoTransfer {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let amount = match &self.amount {
+            TransferAmount::Exactly(amount) => amount.clone(),
+            TransferAmount::All => match transfer_room(state, self.stack, self.destination) {
+                Some((source, room)) => source.cargo().capped(room.cargo),
+                None => CargoList::default(),
+            },
+        };
+        state.resolve_transfer(
+            player,
+            self.stack,
+            self.destination,
+            &TransferRequest::Cargo(amount),
+        )
+    }
 }
 
 /// Transfer fuel from one stack's fuel tanks to another stack's
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct FuelTransfer {
     pub stack: EntityId,
     pub destination: EntityId,
-    pub amount: u64,
+    pub amount: TransferAmount<u64>,
+}
+
+impl FuelTransfer {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let amount = match self.amount {
+            TransferAmount::Exactly(amount) => amount,
+            TransferAmount::All => match transfer_room(state, self.stack, self.destination) {
+                Some((source, room)) => source.fuel().min(room.fuel),
+                None => 0,
+            },
+        };
+        state.resolve_transfer(
+            player,
+            self.stack,
+            self.destination,
+            &TransferRequest::Fuel(amount),
+        )
+    }
+}
+
+/// How much of something a transfer order moves
+///
+/// An exact amount is written as just the amount, as it was before `All`
+/// existed, so orders from older clients still parse
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferAmount<T> {
+    /// As much as the source has and the destination has room for
+    All,
+    #[cfg_attr(any(feature = "client", feature = "server"), serde(untagged))]
+    Exactly(T),
+}
+// written by hand, since the derive doesn't understand untagged variants
+#[cfg(feature = "schema")]
+impl<T: schemars::JsonSchema> schemars::JsonSchema for TransferAmount<T> {
+    fn schema_name() -> String {
+        format!("TransferAmount_for_{}", T::schema_name())
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, SchemaObject, SubschemaValidation};
+
+        let all = SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            enum_values: Some(vec!["All".into()]),
+            ..Default::default()
+        };
+        SchemaObject {
+            subschemas: Some(Box::new(SubschemaValidation {
+                any_of: Some(vec![all.into(), generator.subschema_for::<T>()]),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+/// Free space left in a transfer's destination
+struct Room {
+    cargo: u64,
+    fuel: u64,
+}
+
+/// The source of a transfer and the room in its destination, if both exist
+///
+/// A stack transferring to itself has room for everything it has
+fn transfer_room(state: &GameState, from: EntityId, to: EntityId) -> Option<(&Stack, Room)> {
+    let source = state.stacks.get(&from)?;
+    let destination = state.stacks.get(&to)?;
+    let room = if from == to {
+        Room {
+            cargo: u64::MAX,
+            fuel: u64::MAX,
+        }
+    } else {
+        Room {
+            cargo: destination
+                .cargo_capacity()
+                .saturating_sub(destination.cargo().total()),
+            fuel: destination
+                .fuel_capacity()
+                .saturating_sub(destination.fuel()),
+        }
+    };
+    Some((source, room))
 }
 
 /// Reload a warhead mount using a warhead carried by the stack
+///
+/// The warhead leaves the stack's cargo holds
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct Reload {
     pub stack: EntityId,
     pub mount: EntityId,
 }
 
+impl Reload {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        let mount = stack
+            .launch_clamps()
+            .get(&self.mount)
+            .ok_or(OrderError::NoSuchComponent)?;
+        if mount.damaged {
+            return Err(OrderError::ComponentDamaged);
+        }
+        if mount.loaded {
+            return Err(OrderError::MountLoaded);
+        }
+
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        stack.withdraw_cargo(CargoList::new(0, 0, 0, 1))?;
+        stack
+            .launch_clamps_mut()
+            .get_mut(&self.mount)
+            .unwrap()
+            .loaded = true;
+        Ok(())
+    }
+}
+
+/// Unload a warhead mount, returning its warhead to the stack's cargo holds
+///
+/// The inverse of [`Reload`]
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub struct Unload {
+    pub stack: EntityId,
+    pub mount: EntityId,
+}
+
+impl Unload {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        let mount = stack
+            .launch_clamps()
+            .get(&self.mount)
+            .ok_or(OrderError::NoSuchComponent)?;
+        if mount.damaged {
+            return Err(OrderError::ComponentDamaged);
+        }
+        if !mount.loaded {
+            return Err(OrderError::MountEmpty);
+        }
+
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        stack.deposit_cargo(CargoList::new(0, 0, 0, 1))?;
+        stack
+            .launch_clamps_mut()
+            .get_mut(&self.mount)
+            .unwrap()
+            .loaded = false;
+        Ok(())
+    }
+}
+
 /// Repair components using a factory
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct FactoryRepair {
     pub stack: EntityId,
@@ -100,9 +537,18 @@ pub struct FactoryRepair {
     pub components: Vec<EntityId>,
 }
 
+impl FactoryRepair {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        working_factory(stack, self.factory)?;
+        repair(state, self.stack, &self.components)
+    }
+}
+
 /// Repair a component using a habitat
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct HabitatRepair {
     pub stack: EntityId,
@@ -110,9 +556,98 @@ pub struct HabitatRepair {
     pub component: EntityId,
 }
 
+impl HabitatRepair {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        let habitat = stack
+            .habitats()
+            .get(&self.habitat)
+            .ok_or(OrderError::NoSuchComponent)?;
+        if habitat.damaged {
+            return Err(OrderError::ComponentDamaged);
+        }
+        repair(state, self.stack, &[self.component])
+    }
+}
+
+/// Repair the damaged components among those listed, at the rules' cost in
+/// materials each
+fn repair(
+    state: &mut GameState,
+    stack: EntityId,
+    components: &[EntityId],
+) -> Result<(), OrderError> {
+    let cost = state.rules.repair_material_cost;
+    let stack = state.stacks.get_mut(&stack).unwrap();
+    if components
+        .iter()
+        .any(|id| stack.find_component(*id).is_none())
+    {
+        return Err(OrderError::NoSuchComponent);
+    }
+    let damaged = damaged_among(stack, components);
+    let materials = (damaged.len() as u64)
+        .checked_mul(cost)
+        .ok_or(OrderError::Overflow)?;
+
+    stack.withdraw_cargo(CargoList::new(0, 0, materials, 0))?;
+    for id in damaged {
+        stack.find_component_mut(id).unwrap().set_damaged(false);
+    }
+    Ok(())
+}
+
+/// The distinct damaged components among those listed, ignoring any not in
+/// the stack
+fn damaged_among(stack: &Stack, components: &[EntityId]) -> Vec<EntityId> {
+    let mut damaged = Vec::new();
+    for id in components {
+        if stack
+            .find_component(*id)
+            .is_some_and(|component| component.damaged())
+            && !damaged.contains(id)
+        {
+            damaged.push(*id);
+        }
+    }
+    damaged
+}
+
+impl GameState {
+    /// Materials needed to repair the listed components of a stack
+    ///
+    /// Each distinct damaged component costs the rules' repair cost in
+    /// materials; undamaged or unknown components are free. A cost too large
+    /// to count is given as `u64::MAX`
+    pub fn repair_cost(&self, stack: EntityId, components: &[EntityId]) -> u64 {
+        self.stacks.get(&stack).map_or(0, |stack| {
+            (damaged_among(stack, components).len() as u64)
+                .saturating_mul(self.rules.repair_material_cost)
+        })
+    }
+
+    /// The damaged stacks the player controls that can't repair themselves, in
+    /// id order
+    ///
+    /// These need to get to a stack with a working factory or habitat to be
+    /// repaired
+    pub fn unrepairable_stacks(&self, player: PlayerId) -> Vec<EntityId> {
+        sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, stack)| {
+                stack.controller() == Some(player)
+                    && !stack.damaged_components().is_empty()
+                    && !stack.can_self_repair()
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
 /// Transfer components between rendezvoused stacks or to a new stack
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct StackTransfer {
     pub stack: EntityId,
@@ -121,45 +656,2528 @@ pub struct StackTransfer {
 }
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub enum StackTransferTarget {
     Existing(EntityId),
     New(u64),
 }
+impl StackTransfer {
+    /// Resolve the transfer
+    ///
+    /// `new_stacks` maps the keys of the ordering player's new stacks to the
+    /// stacks created for them so far this phase
+    #[cfg(feature = "server")]
+    pub fn resolve(
+        &self,
+        player: PlayerId,
+        state: &mut GameState,
+        id_generator: &mut EntityIdGenerator,
+        new_stacks: &mut HashMap<u64, EntityId>,
+    ) -> Result<(), OrderError> {
+        let source = controlled_stack(state, self.stack, player)?;
+        if self
+            .components
+            .iter()
+            .any(|id| source.find_component(*id).is_none())
+        {
+            return Err(OrderError::NoSuchComponent);
+        }
+        if self.components.is_empty() {
+            return Ok(());
+        }
+
+        let existing = match self.destination {
+            StackTransferTarget::Existing(id) => Some(id),
+            StackTransferTarget::New(key) => new_stacks
+                .get(&key)
+                .copied()
+                .filter(|id| state.stacks.contains_key(id)),
+        };
+        let destination = match existing {
+            Some(id) => id,
+            None => {
+                let StackTransferTarget::New(key) = self.destination else {
+                    unreachable!("existing destination should have been found");
+                };
+                let mut stack = Stack::new(
+                    &source.name,
+                    id_generator,
+                    source.position,
+                    source.velocity,
+                    player,
+                );
+                stack.created = Some((state.round, state.phase));
+                let id = stack.id;
+                state.stacks.insert(id, stack);
+                new_stacks.insert(key, id);
+                id
+            }
+        };
+        state.resolve_transfer(
+            player,
+            self.stack,
+            destination,
+            &TransferRequest::Components(self.components.clone()),
+        )
+    }
+}
+
+/// Convert resources using a factory
+///
+/// `amount` is the number of points of output produced
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub stack: EntityId,
+    pub factory: EntityId,
+    pub kind: ConversionKind,
+    pub amount: u64,
+}
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy)]
+pub enum ConversionKind {
+    OreToMaterials,
+    IceToFuel,
+    MaterialsToWarheads,
+}
+impl Conversion {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        working_factory(stack, self.factory)?;
+
+        let rules = &state.rules;
+        let input = |per_output: u64| {
+            self.amount
+                .checked_mul(per_output)
+                .ok_or(OrderError::Overflow)
+        };
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        match self.kind {
+            ConversionKind::OreToMaterials => {
+                let input = CargoList::new(0, input(rules.ore_per_material)?, 0, 0);
+                let output = CargoList::new(0, 0, self.amount, 0);
+                if !stack.cargo().contains(&input) {
+                    return Err(OrderError::InsufficientCargo);
+                }
+                stack.withdraw_cargo(input)?;
+                stack.deposit_cargo(output)
+            }
+            ConversionKind::IceToFuel => {
+                let input = CargoList::new(input(rules.ice_per_fuel)?, 0, 0, 0);
+                if !stack.cargo().contains(&input) {
+                    return Err(OrderError::InsufficientCargo);
+                }
+                let fuel = stack
+                    .fuel()
+                    .checked_add(self.amount)
+                    .ok_or(OrderError::Overflow)?;
+                if fuel > stack.fuel_capacity() {
+                    return Err(OrderError::CapacityExceeded);
+                }
+                stack.withdraw_cargo(input)?;
+                stack.add_fuel(self.amount)
+            }
+            ConversionKind::MaterialsToWarheads => {
+                let input = CargoList::new(0, 0, input(rules.materials_per_warhead)?, 0);
+                let output = CargoList::new(0, 0, 0, self.amount);
+                if !stack.cargo().contains(&input) {
+                    return Err(OrderError::InsufficientCargo);
+                }
+                stack.withdraw_cargo(input)?;
+                stack.deposit_cargo(output)
+            }
+        }
+    }
+}
+
+/// Dump cargo and fuel out of a stack, destroying it
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub struct Jettison {
+    pub stack: EntityId,
+    pub cargo: CargoList,
+    pub fuel: u64,
+}
+impl Jettison {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        if !stack.cargo().contains(&self.cargo) {
+            return Err(OrderError::InsufficientCargo);
+        }
+        if stack.fuel() < self.fuel {
+            return Err(OrderError::InsufficientFuel);
+        }
+
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        stack.withdraw_cargo(self.cargo.clone())?;
+        stack.remove_fuel(self.fuel)
+    }
+}
+
+impl GameState {
+    /// Fuel transfers that top off the stacks in transfer range of the source
+    /// and controlled by the same player, in id order, for as long as the
+    /// source's fuel lasts
+    ///
+    /// Empty if nobody controls the source or it doesn't exist
+    pub fn plan_fuel_distribution(&self, source: EntityId) -> Vec<FuelTransfer> {
+        let Some(stack) = self.stacks.get(&source) else {
+            return Vec::new();
+        };
+        let Some(controller) = stack.controller() else {
+            return Vec::new();
+        };
+        let mut remaining = stack.fuel();
+        sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(id, destination)| {
+                *id != source
+                    && destination.controller() == Some(controller)
+                    && stack
+                        .within_transfer_range(destination, self.rules.transfer_max_relative_speed)
+            })
+            .filter_map(|(id, destination)| {
+                let amount = destination
+                    .fuel_capacity()
+                    .saturating_sub(destination.fuel())
+                    .min(remaining);
+                remaining -= amount;
+                (amount > 0).then_some(FuelTransfer {
+                    stack: source,
+                    destination: id,
+                    amount: TransferAmount::Exactly(amount),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Something to move from one stack to another
+#[cfg_attr(feature = "server", derive(Deserialize))]
+#[cfg_attr(feature = "client", derive(Serialize))]
+#[derive(Debug, Clone)]
+pub enum TransferRequest {
+    Cargo(CargoList),
+    Fuel(u64),
+    Components(Vec<EntityId>),
+}
+
+impl GameState {
+    /// Check whether a transfer could happen right now, without doing it
+    ///
+    /// Checks everything the transfer resolvers check: that the player
+    /// controls the source, that both stacks exist and are in transfer range,
+    /// that the source has what is to be transferred, and that the destination
+    /// has room for it
+    pub fn transfer_feasibility(
+        &self,
+        player: PlayerId,
+        from: EntityId,
+        to: EntityId,
+        what: &TransferRequest,
+    ) -> Result<(), OrderError> {
+        let source = controlled_stack(self, from, player)?;
+        let destination = self.stacks.get(&to).ok_or(OrderError::TargetGone)?;
+        if !source.within_transfer_range(destination, self.rules.transfer_max_relative_speed) {
+            return Err(OrderError::NotRendezvoused);
+        }
+
+        let same_stack = from == to;
+        match what {
+            TransferRequest::Cargo(amount) => {
+                if !source.cargo().contains(amount) {
+                    return Err(OrderError::InsufficientCargo);
+                }
+                let total = amount
+                    .checked_total()
+                    .and_then(|incoming| destination.cargo().total().checked_add(incoming))
+                    .ok_or(OrderError::Overflow)?;
+                if !same_stack && total > destination.cargo_capacity() {
+                    return Err(OrderError::CapacityExceeded);
+                }
+            }
+            TransferRequest::Fuel(amount) => {
+                if source.fuel() < *amount {
+                    return Err(OrderError::InsufficientFuel);
+                }
+                let total = destination
+                    .fuel()
+                    .checked_add(*amount)
+                    .ok_or(OrderError::Overflow)?;
+                if !same_stack && total > destination.fuel_capacity() {
+                    return Err(OrderError::CapacityExceeded);
+                }
+            }
+            TransferRequest::Components(components) => {
+                if components
+                    .iter()
+                    .any(|id| source.find_component(*id).is_none())
+                {
+                    return Err(OrderError::NoSuchComponent);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GameState {
+    /// Move something from one stack to another
+    ///
+    /// A stack left with no components after giving them all away is removed.
+    /// A derelict given a habitat is captured by the player controlling it
+    pub fn resolve_transfer(
+        &mut self,
+        player: PlayerId,
+        from: EntityId,
+        to: EntityId,
+        what: &TransferRequest,
+    ) -> Result<(), OrderError> {
+        self.transfer_feasibility(player, from, to, what)?;
+        if from == to {
+            return Ok(());
+        }
+
+        let mut source = self.stacks.remove(&from).unwrap();
+        let destination = self.stacks.get_mut(&to).unwrap();
+        let derelict = destination.is_derelict();
+        let result = match what {
+            TransferRequest::Cargo(amount) => source
+                .withdraw_cargo(amount.clone())
+                .and_then(|()| destination.deposit_cargo(amount.clone())),
+            TransferRequest::Fuel(amount) => source
+                .remove_fuel(*amount)
+                .and_then(|()| destination.add_fuel(*amount)),
+            TransferRequest::Components(components) => {
+                for id in components {
+                    if let Some(component) = source.remove_component(*id) {
+                        destination.insert_component(component);
+                    }
+                }
+                if derelict {
+                    if let Some(captor) = destination.controller() {
+                        destination.owner = captor;
+                    }
+                }
+                Ok(())
+            }
+        };
+        if !source.component_ids().is_empty() {
+            self.stacks.insert(source.id, source);
+        }
+        result
+    }
+}
+
+/// Get a stack, checking that the player controls it
+fn controlled_stack(
+    state: &GameState,
+    stack: EntityId,
+    player: PlayerId,
+) -> Result<&Stack, OrderError> {
+    let stack = state.stacks.get(&stack).ok_or(OrderError::NoSuchStack)?;
+    if stack.controller() != Some(player) {
+        return Err(OrderError::NotControlled);
+    }
+    Ok(stack)
+}
+
+/// Check that the stack has the factory and that it's in working order
+fn working_factory(stack: &Stack, factory: EntityId) -> Result<(), OrderError> {
+    let factory = stack
+        .factories()
+        .get(&factory)
+        .ok_or(OrderError::NoSuchComponent)?;
+    if factory.damaged {
+        return Err(OrderError::ComponentDamaged);
+    }
+    Ok(())
+}
 
 /// Launch a warhead from a mount
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct Launch {
     pub stack: EntityId,
     pub mount: EntityId,
     pub delta: vec2::Displacement,
 }
+impl Launch {
+    #[cfg(feature = "server")]
+    pub fn resolve(
+        &self,
+        player: PlayerId,
+        state: &mut GameState,
+        id_generator: &mut EntityIdGenerator,
+    ) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        let mount = stack
+            .launch_clamps()
+            .get(&self.mount)
+            .ok_or(OrderError::NoSuchComponent)?;
+        if mount.damaged {
+            return Err(OrderError::ComponentDamaged);
+        }
+        if !mount.loaded {
+            return Err(OrderError::MountEmpty);
+        }
+        if self.delta.checked_norm().ok_or(OrderError::Overflow)? > 1 {
+            return Err(OrderError::InsufficientThrust);
+        }
+        let velocity = stack
+            .velocity
+            .checked_add(self.delta)
+            .ok_or(OrderError::Overflow)?;
+
+        let mut warhead = Warhead::new(id_generator, stack.position, velocity, player);
+        warhead.created = Some((state.round, state.phase));
+        state.warheads.insert(warhead.id, warhead);
+        state
+            .stacks
+            .get_mut(&self.stack)
+            .unwrap()
+            .launch_clamps_mut()
+            .get_mut(&self.mount)
+            .unwrap()
+            .loaded = false;
+        Ok(())
+    }
+}
 
 /// Shoot a gun at another stack
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct Shoot {
     pub stack: EntityId,
     pub gun: EntityId,
     pub target: EntityId,
 }
+impl Shoot {
+    /// Resolve the shot, returning what it did to the target and the roll
+    /// that decided whether it hit
+    ///
+    /// `guns_fired` is the number of guns the stack has already fired this
+    /// phase, for overheating
+    #[cfg(feature = "server")]
+    pub fn resolve(
+        &self,
+        player: PlayerId,
+        state: &mut GameState,
+        guns_fired: u64,
+        rng: &mut impl Rng,
+    ) -> Result<(HitOutcome, HitRoll), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        let gun = stack
+            .guns()
+            .get(&self.gun)
+            .ok_or(OrderError::NoSuchComponent)?;
+        if gun.damaged {
+            return Err(OrderError::ComponentDamaged);
+        }
+        let target = state
+            .stacks
+            .get(&self.target)
+            .ok_or(OrderError::TargetGone)?;
+
+        let distance = stack.position.distance(target.position);
+        if distance > Gun::EFFECTIVE_RANGE {
+            return Err(OrderError::OutOfRange);
+        }
+        if !state.line_of_sight(stack.position, target.position) {
+            return Err(OrderError::NoLineOfSight);
+        }
+        let roll = HitRoll {
+            roll: rng.gen(),
+            threshold: Gun::hit_chance(distance) * Gun::overheat_factor(guns_fired),
+        };
+        if !roll.hit() {
+            return Ok((HitOutcome::MISS, roll));
+        }
+        let target = state.stacks.get_mut(&self.target).unwrap();
+        let outcome = target.apply_hit(rng);
+        if target.component_ids().is_empty() {
+            state.stacks.remove(&self.target);
+        }
+        Ok((outcome, roll))
+    }
+}
+
+impl GameState {
+    /// The stacks the stack's guns could usefully shoot at, with the chance to
+    /// hit each, in id order
+    ///
+    /// These are stacks not controlled by the stack's controller, within
+    /// [`Gun::EFFECTIVE_RANGE`], and not behind a major body. A stack nobody
+    /// controls can't shoot, so has no targets
+    pub fn targets_in_range(&self, stack: EntityId) -> Vec<(EntityId, f64)> {
+        let Some(shooter) = self.stacks.get(&stack) else {
+            return Vec::new();
+        };
+        let Some(controller) = shooter.controller() else {
+            return Vec::new();
+        };
+        sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, target)| target.controller() != Some(controller))
+            .filter(|(_, target)| {
+                shooter.position.distance(target.position) <= Gun::EFFECTIVE_RANGE
+                    && self.line_of_sight(shooter.position, target.position)
+            })
+            .map(|(id, target)| {
+                (
+                    id,
+                    Gun::hit_chance(shooter.position.distance(target.position)),
+                )
+            })
+            .collect()
+    }
+
+    /// Expected number of hits the stack would take if every enemy fired on
+    /// it where everything is now
+    ///
+    /// Each enemy stack in range with line of sight fires all its working
+    /// guns, overheating past [`Gun::GUNS_PER_TURN`]; each enemy warhead adds
+    /// the hits it would deal at its closest approach next turn, if it and the
+    /// stack both coast under gravity. Zero if the stack doesn't exist
+    pub fn incoming_threat(&self, stack: EntityId) -> f64 {
+        let Some(target) = self.stacks.get(&stack) else {
+            return 0.0;
+        };
+        let controller = target.controller();
+        let guns = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, shooter)| {
+                shooter.controller().is_some() && shooter.controller() != controller
+            })
+            .filter(|(_, shooter)| {
+                shooter.position.distance(target.position) <= Gun::EFFECTIVE_RANGE
+                    && self.line_of_sight(shooter.position, target.position)
+            })
+            .map(|(_, shooter)| {
+                let working = shooter.guns().values().filter(|gun| !gun.damaged).count() as u64;
+                let chance = Gun::hit_chance(shooter.position.distance(target.position));
+                (0..working)
+                    .map(|guns_fired| chance * Gun::overheat_factor(guns_fired))
+                    .sum::<f64>()
+            })
+            .sum::<f64>();
+        let target_path = target
+            .position
+            .line_to(target.position + target.velocity + self.gravity_at(target.position));
+        let warheads = sorted_by_id(&self.warheads)
+            .into_iter()
+            .filter(|(_, warhead)| Some(warhead.owner) != controller)
+            .map(|(_, warhead)| {
+                let warhead_path = warhead.position.line_to(
+                    warhead.position + warhead.velocity + self.gravity_at(warhead.position),
+                );
+                let (distance, _, _) = closest_along(&warhead_path, &target_path);
+                warhead.hits_at(distance) as f64
+            })
+            .sum::<f64>();
+        guns + warheads
+    }
+}
+
+/// The roll deciding whether a shot hit
+///
+/// `roll` is the first number drawn from the RNG for the shot, uniform in
+/// [0, 1); the shot hits if it's below `threshold`
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitRoll {
+    pub roll: f64,
+    pub threshold: f64,
+}
+impl HitRoll {
+    pub fn hit(&self) -> bool {
+        self.roll < self.threshold
+    }
+}
+
+/// A hit roll, with the order it was rolled for, so that anyone with the seed
+/// can check the roll was fair
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RollRecord {
+    pub round: u64,
+    pub phase: Phase,
+    /// The index of the shot among all the shots resolved in the phase
+    pub order: usize,
+    pub roll: HitRoll,
+    pub hit: bool,
+}
 
 /// Burn engines
 #[cfg_attr(feature = "server", derive(Deserialize))]
 #[cfg_attr(feature = "client", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[derive(Debug, Clone)]
 pub struct Burn {
     pub stack: EntityId,
     pub delta: vec2::Displacement,
 }
+impl Burn {
+    pub fn resolve(&self, player: PlayerId, state: &mut GameState) -> Result<(), OrderError> {
+        let stack = controlled_stack(state, self.stack, player)?;
+        if self.delta.is_zero() {
+            return Ok(());
+        }
+        let norm = self.delta.checked_norm().ok_or(OrderError::Overflow)?;
+        let fuel = stack
+            .thrust_fuel(norm)
+            .ok_or(OrderError::InsufficientThrust)?;
+
+        let stack = state.stacks.get_mut(&self.stack).unwrap();
+        stack.remove_fuel(fuel)?;
+        stack.velocity += self.delta;
+        Ok(())
+    }
+}
+
+/// A hex a stack could end up in after the next movement phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReachableHex {
+    pub position: vec2::Position,
+    /// The burn that gets the stack there
+    pub burn: vec2::Displacement,
+    /// Whether the stack would be destroyed there, by crashing into a major
+    /// body or leaving the map
+    pub lost: bool,
+}
+
+impl GameState {
+    /// The hexes the stack could move to in the next movement phase
+    ///
+    /// Gravity is felt where the stack starts, after its burn, so it shifts
+    /// every reachable hex by the same amount: hexes on the far side of a
+    /// gravity well can only be reached by burning against it. Hexes where
+    /// the stack would be lost are included but flagged. Empty if the stack
+    /// doesn't exist
+    pub fn reachable_next_turn(&self, stack: EntityId) -> Vec<ReachableHex> {
+        let Some(stack) = self.stacks.get(&stack) else {
+            return Vec::new();
+        };
+        let coast = stack.position + stack.velocity + self.gravity_at(stack.position);
+        coast
+            .hexes_within(stack.max_delta())
+            .into_iter()
+            .filter(|position| {
+                let burn = *position - coast;
+                burn.is_zero() || stack.burn_cost(burn).is_some()
+            })
+            .map(|position| ReachableHex {
+                position,
+                burn: position - coast,
+                lost: self.is_lost_at(position),
+            })
+            .collect()
+    }
+
+    /// Would something that ends its move here be destroyed, by crashing into
+    /// a major body or leaving the map?
+    fn is_lost_at(&self, position: vec2::Position) -> bool {
+        !self.in_bounds(position)
+            || self
+                .major_bodies
+                .values()
+                .any(|body| body.position == position)
+    }
 
-#[cfg(test)]
+    /// The least fuel the stack needs to end a movement phase in the goal
+    /// hex within `max_turns` turns, or `None` if it can't get there in time
+    ///
+    /// Searches every sequence of burns, turn by turn, keeping the cheapest
+    /// way to each position and velocity, and accounting for gravity and
+    /// the stack getting lighter as it burns fuel. Courses that crash or leave
+    /// the map are skipped, as are ones that can't reach the goal in the turns
+    /// left, and the search stops once no course is left
+    pub fn fuel_to_reach(
+        &self,
+        stack: EntityId,
+        goal: vec2::Position,
+        max_turns: u64,
+    ) -> Option<u64> {
+        let stack = self.stacks.get(&stack)?;
+        if stack.position == goal {
+            return Some(0);
+        }
+        let fuel = stack.fuel();
+        let mass = stack.mass();
+        // the largest burn the stack could make once it's burned all its fuel
+        let max_burn = (0..)
+            .take_while(|norm| {
+                stack
+                    .burn_cost_with_mass(vec2::Displacement::new(*norm, 0), mass - fuel)
+                    .is_some()
+            })
+            .last()
+            .unwrap_or(0) as u64;
+        let max_gravity = self
+            .major_bodies
+            .values()
+            .fold(0_u64, |total, body| total.saturating_add(body.gravity));
+
+        let mut best: Option<u64> = None;
+        let mut frontier = HashMap::from([((stack.position, stack.velocity), 0)]);
+        for turn in 1..=max_turns {
+            let turns_left = max_turns - turn;
+            // saturates, since a slack that large can't rule anything out
+            let slack = max_burn
+                .saturating_add(max_gravity)
+                .saturating_mul(turns_left.saturating_mul(turns_left + 1) / 2);
+            let mut next = HashMap::new();
+            for ((position, velocity), used) in frontier {
+                let gravity = self.gravity_at(position);
+                let burns = (0..)
+                    .map_while(|norm| {
+                        stack
+                            .burn_cost_with_mass(vec2::Displacement::new(norm, 0), mass - used)
+                            .filter(|cost| used + cost <= fuel)
+                            .map(|_| norm as u64)
+                    })
+                    .last()
+                    .unwrap_or(0);
+                for burn in vec2::Position::new(0, 0).hexes_within(burns) {
+                    let burn = burn.to_displacement();
+                    let Some(cost) = stack.burn_cost_with_mass(burn, mass - used) else {
+                        continue;
+                    };
+                    let used = used + cost;
+                    if best.is_some_and(|best| used >= best) {
+                        continue;
+                    }
+                    let velocity = velocity + burn + gravity;
+                    let position = position + velocity;
+                    if self.is_lost_at(position) {
+                        continue;
+                    }
+                    if position == goal {
+                        best = Some(used);
+                        continue;
+                    }
+                    // how far off the goal coasting the rest of the way ends up;
+                    // one too far to work out can't be ruled out
+                    let off_course = i64::try_from(turns_left)
+                        .ok()
+                        .and_then(|turns| velocity.checked_mul(turns))
+                        .and_then(|coast| (goal - position).checked_sub(coast))
+                        .and_then(|miss| miss.checked_norm())
+                        .is_some_and(|miss| miss > slack);
+                    if off_course {
+                        continue;
+                    }
+                    next.entry((position, velocity))
+                        .and_modify(|cheapest: &mut u64| *cheapest = (*cheapest).min(used))
+                        .or_insert(used);
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        best
+    }
+}
+
+impl GameState {
+    /// A sample of orders the player could legally give this phase
+    ///
+    /// Each order is legal on its own, on the current state; together they may
+    /// not be. At most a handful of orders are generated per stack: one
+    /// reload per empty mount, one repair of all damaged components, the
+    /// smallest of each conversion, and the cheapest production in the
+    /// economic phase; one launch per loaded mount in the ordnance phase; one
+    /// shot per gun at the nearest enemy stack in the combat phase; and one
+    /// burn against the current velocity in the movement phase
+    pub fn legal_orders(&self, player: PlayerId) -> Vec<PhaseOrder> {
+        if self.is_over() {
+            return Vec::new();
+        }
+
+        let ids = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, stack)| stack.controller() == Some(player))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        let mut orders = Vec::new();
+        for id in ids {
+            let stack = &self.stacks[&id];
+            match self.phase {
+                Phase::Economic => self.legal_economic_orders(stack, &mut orders),
+                Phase::Ordnance => orders.extend(
+                    sorted_ids(stack.launch_clamps(), |mount| {
+                        !mount.damaged && mount.loaded
+                    })
+                    .into_iter()
+                    .map(|mount| {
+                        PhaseOrder::Ordnance(Launch {
+                            stack: id,
+                            mount,
+                            delta: vec2::Displacement::ZERO,
+                        })
+                    }),
+                ),
+                Phase::Combat => {
+                    let target = self
+                        .stacks
+                        .values()
+                        .filter(|target| target.controller() != Some(player))
+                        .min_by_key(|target| (stack.position.distance(target.position), target.id));
+                    if let Some(target) = target {
+                        orders.extend(
+                            sorted_ids(stack.guns(), |gun| !gun.damaged)
+                                .into_iter()
+                                .map(|gun| {
+                                    PhaseOrder::Combat(Shoot {
+                                        stack: id,
+                                        gun,
+                                        target: target.id,
+                                    })
+                                }),
+                        );
+                    }
+                }
+                Phase::Movement => {
+                    if let MobilityStatus::Mobile { .. } = stack.mobility_status() {
+                        let direction = (-stack.velocity)
+                            .nearest_direction()
+                            .unwrap_or(vec2::Direction::East);
+                        orders.push(PhaseOrder::Movement(Burn {
+                            stack: id,
+                            delta: direction.into(),
+                        }));
+                    }
+                }
+            }
+        }
+        orders
+    }
+
+    /// Legal economic orders for one stack
+    fn legal_economic_orders(&self, stack: &Stack, orders: &mut Vec<PhaseOrder>) {
+        let cargo = stack.cargo();
+        if cargo.warheads > 0 {
+            for mount in sorted_ids(stack.launch_clamps(), |mount| {
+                !mount.damaged && !mount.loaded
+            }) {
+                orders.push(PhaseOrder::Economic(EconomicOrder::Reload(Reload {
+                    stack: stack.id,
+                    mount,
+                })));
+            }
+        }
+
+        let Some(factory) = sorted_ids(stack.factories(), |factory| !factory.damaged)
+            .first()
+            .copied()
+        else {
+            return;
+        };
+        let damaged = stack.damaged_components();
+        if !damaged.is_empty() && cargo.materials >= self.repair_cost(stack.id, &damaged) {
+            orders.push(PhaseOrder::Economic(EconomicOrder::FactoryRepair(
+                FactoryRepair {
+                    stack: stack.id,
+                    factory,
+                    components: damaged,
+                },
+            )));
+        }
+
+        let conversions = [
+            (
+                ConversionKind::OreToMaterials,
+                cargo.ore >= self.rules.ore_per_material,
+            ),
+            (
+                ConversionKind::IceToFuel,
+                cargo.ice >= self.rules.ice_per_fuel && stack.fuel() < stack.fuel_capacity(),
+            ),
+            (
+                ConversionKind::MaterialsToWarheads,
+                cargo.materials >= self.rules.materials_per_warhead,
+            ),
+        ];
+        for (kind, legal) in conversions {
+            if legal {
+                orders.push(PhaseOrder::Economic(EconomicOrder::Conversion(
+                    Conversion {
+                        stack: stack.id,
+                        factory,
+                        kind,
+                        amount: 1,
+                    },
+                )));
+            }
+        }
+
+        if StackComponent::ArmourPlate
+            .production_cost(&self.rules)
+            .is_some_and(|cost| cargo.materials >= cost)
+        {
+            orders.push(PhaseOrder::Economic(EconomicOrder::Production(
+                Production {
+                    stack: stack.id,
+                    factory,
+                    component: StackComponent::ArmourPlate,
+                },
+            )));
+        }
+    }
+}
+
+impl GameState {
+    /// Every component the factory could produce right now, with its cost in
+    /// materials
+    ///
+    /// Empty if the stack doesn't exist or the factory isn't in working
+    /// order. New components don't take up cargo space, so only the
+    /// materials in the stack's cargo holds limit what's listed
+    pub fn affordable_components(
+        &self,
+        stack: EntityId,
+        factory: EntityId,
+    ) -> Vec<(StackComponent, u64)> {
+        let Some(stack) = self.stacks.get(&stack) else {
+            return Vec::new();
+        };
+        if working_factory(stack, factory).is_err() {
+            return Vec::new();
+        }
+        let materials = stack.cargo().materials;
+        StackComponent::ALL
+            .into_iter()
+            .filter_map(|component| Some((component, component.production_cost(&self.rules)?)))
+            .filter(|(_, cost)| *cost <= materials)
+            .collect()
+    }
+}
+
+impl GameState {
+    /// Check a whole turn against the current state without resolving it
+    ///
+    /// Returns the index and error of every order that refers to a missing or
+    /// uncontrolled stack, or to a component the stack doesn't have. Problems
+    /// with the turn as a whole, like being for the wrong phase, are reported
+    /// against every order. Orders that pass may still fail when resolved,
+    /// since earlier orders can change the state they act on
+    pub fn validate_turn(&self, player: PlayerId, turn: &PlayerTurn) -> Vec<(usize, OrderError)> {
+        let turn_error = if self.is_over() {
+            Some(OrderError::GameOver)
+        } else {
+            turn.validate_phase(self.phase, &self.rules).err()
+        };
+        if let Some(error) = turn_error {
+            return (0..turn.len()).map(|index| (index, error)).collect();
+        }
+
+        let results = match turn {
+            PlayerTurn::Economic(orders) => orders
+                .iter()
+                .enumerate()
+                .map(|(index, order)| {
+                    // an earlier unload in the same turn empties the mount
+                    let unloaded_first = |reload: &Reload| {
+                        orders[..index].iter().any(|earlier| {
+                            matches!(earlier, EconomicOrder::Unload(unload)
+                                if unload.stack == reload.stack && unload.mount == reload.mount)
+                        })
+                    };
+                    match (order, self.validate_economic_order(player, order)) {
+                        (EconomicOrder::Reload(reload), Err(OrderError::MountLoaded))
+                            if unloaded_first(reload) =>
+                        {
+                            Ok(())
+                        }
+                        (_, result) => result,
+                    }
+                })
+                .collect::<Vec<_>>(),
+            PlayerTurn::Ordnance(orders) => orders
+                .iter()
+                .map(|order| {
+                    let stack = controlled_stack(self, order.stack, player)?;
+                    has(stack.launch_clamps(), order.mount)
+                })
+                .collect(),
+            PlayerTurn::Combat(orders) => orders
+                .iter()
+                .map(|order| {
+                    let stack = controlled_stack(self, order.stack, player)?;
+                    has(stack.guns(), order.gun)?;
+                    if !self.stacks.contains_key(&order.target) {
+                        return Err(OrderError::TargetGone);
+                    }
+                    Ok(())
+                })
+                .collect(),
+            PlayerTurn::Movement(orders) => orders
+                .iter()
+                .map(|order| controlled_stack(self, order.stack, player).map(|_| ()))
+                .collect(),
+        };
+        results
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.err().map(|error| (index, error)))
+            .collect()
+    }
+
+    /// Check the stacks and components an economic order refers to
+    fn validate_economic_order(
+        &self,
+        player: PlayerId,
+        order: &EconomicOrder,
+    ) -> Result<(), OrderError> {
+        let stack = controlled_stack(self, order.stack(), player)?;
+        let component = |id: EntityId| {
+            stack
+                .find_component(id)
+                .map(|_| ())
+                .ok_or(OrderError::NoSuchComponent)
+        };
+        let destination = |id: EntityId| {
+            if self.stacks.contains_key(&id) {
+                Ok(())
+            } else {
+                Err(OrderError::NoSuchStack)
+            }
+        };
+        match order {
+            EconomicOrder::Production(order) => has(stack.factories(), order.factory),
+            EconomicOrder::CargoTransfer(order) => destination(order.destination),
+            EconomicOrder::FuelTransfer(order) => destination(order.destination),
+            EconomicOrder::Reload(order) => {
+                // a loaded mount stays loaded unless it's unloaded first;
+                // reloading it would duplicate a warhead
+                has(stack.launch_clamps(), order.mount)?;
+                if stack.launch_clamps()[&order.mount].loaded {
+                    return Err(OrderError::MountLoaded);
+                }
+                Ok(())
+            }
+            EconomicOrder::FactoryRepair(order) => {
+                has(stack.factories(), order.factory)?;
+                order.components.iter().try_for_each(|id| component(*id))
+            }
+            EconomicOrder::HabitatRepair(order) => {
+                has(stack.habitats(), order.habitat)?;
+                component(order.component)
+            }
+            EconomicOrder::StackTransfer(order) => {
+                if let StackTransferTarget::Existing(id) = order.destination {
+                    destination(id)?;
+                }
+                order.components.iter().try_for_each(|id| component(*id))
+            }
+            EconomicOrder::Conversion(order) => has(stack.factories(), order.factory),
+            EconomicOrder::Jettison(_) => Ok(()),
+            EconomicOrder::Unload(order) => has(stack.launch_clamps(), order.mount),
+        }
+    }
+}
+
+/// Check that a component of the expected kind exists
+fn has<T>(components: &HashMap<EntityId, T>, id: EntityId) -> Result<(), OrderError> {
+    if components.contains_key(&id) {
+        Ok(())
+    } else {
+        Err(OrderError::NoSuchComponent)
+    }
+}
+
+/// The ids of the components matching the filter, in order
+fn sorted_ids<T>(components: &HashMap<EntityId, T>, filter: impl Fn(&T) -> bool) -> Vec<EntityId> {
+    sorted_by_id(components)
+        .into_iter()
+        .filter(|(_, component)| filter(component))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+#[cfg(all(test, feature = "server"))]
 mod tests {
     use super::*;
+    use crate::{
+        stack::{CargoHold, Factory, Habitat, WarheadMount},
+        Phase,
+    };
+
+    fn station(id_generator: &mut EntityIdGenerator, player: PlayerId) -> Stack {
+        let mut stack = Stack::new(
+            "Station",
+            id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            player,
+        );
+        let habitat = Habitat::new(id_generator, player);
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let factory = Factory::new(id_generator);
+        stack.factories_mut().insert(factory.id, factory);
+        for _ in 0..2 {
+            let hold = CargoHold::new(id_generator);
+            stack.cargo_holds_mut().insert(hold.id, hold);
+        }
+        stack
+    }
+
+    #[test]
+    fn test_stack_component_variants() {
+        // adding a variant breaks this match until it's listed here and given
+        // a mass and a constructor
+        let all = [
+            StackComponent::FuelTank,
+            StackComponent::CargoHold,
+            StackComponent::Engine,
+            StackComponent::Gun,
+            StackComponent::LaunchClamp,
+            StackComponent::WarheadBus,
+            StackComponent::Habitat,
+            StackComponent::Miner,
+            StackComponent::Factory,
+            StackComponent::ArmourPlate,
+        ];
+        let indices = all
+            .iter()
+            .map(|component| match component {
+                StackComponent::FuelTank => 0,
+                StackComponent::CargoHold => 1,
+                StackComponent::Engine => 2,
+                StackComponent::Gun => 3,
+                StackComponent::LaunchClamp => 4,
+                StackComponent::WarheadBus => 5,
+                StackComponent::Habitat => 6,
+                StackComponent::Miner => 7,
+                StackComponent::Factory => 8,
+                StackComponent::ArmourPlate => 9,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (0..all.len()).collect::<Vec<_>>());
+
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = station(&mut id_generator, 0.into());
+        for component in all {
+            match (
+                component.mass(),
+                component.build(&mut id_generator, 0.into()),
+            ) {
+                (Some(mass), Some(built)) => {
+                    let id = built.id();
+                    stack.insert_component(built);
+                    assert_eq!(stack.find_component(id).unwrap().mass(), mass);
+                }
+                (None, None) => {}
+                _ => panic!("{component:?} has a mass but no constructor or vice versa"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_affordable_components() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = station(&mut id_generator, 0.into());
+        let cargo = stack.cargo();
+        stack.withdraw_cargo(cargo).unwrap();
+        stack.deposit_cargo(CargoList::new(0, 0, 10, 0)).unwrap();
+        let factory = *stack.factories().keys().next().unwrap();
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let affordable = state.affordable_components(id, factory);
+        assert!(affordable.iter().all(|(_, cost)| *cost <= 10));
+        let expected = StackComponent::ALL
+            .into_iter()
+            .filter(|component| component.mass().is_some_and(|mass| mass <= 10))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            affordable
+                .iter()
+                .map(|(component, _)| *component)
+                .collect::<Vec<_>>(),
+            expected
+        );
+        assert!(expected.contains(&StackComponent::Habitat));
+        assert!(!expected.contains(&StackComponent::Factory));
+        for (component, _) in affordable {
+            let mut trial = state.clone();
+            Production {
+                stack: id,
+                factory,
+                component,
+            }
+            .resolve(0.into(), &mut trial, &mut id_generator)
+            .unwrap();
+        }
+
+        state
+            .stacks
+            .get_mut(&id)
+            .unwrap()
+            .factories_mut()
+            .get_mut(&factory)
+            .unwrap()
+            .damaged = true;
+        assert!(state.affordable_components(id, factory).is_empty());
+    }
+
+    #[test]
+    fn test_hostile_amounts() {
+        use rand::SeedableRng;
+
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        let tank = crate::stack::FuelTank::new(&mut id_generator);
+        source.fuel_tanks_mut().insert(tank.id, tank);
+        source.add_fuel(5).unwrap();
+        let engine = crate::stack::Engine::new(&mut id_generator);
+        source.engines_mut().insert(engine.id, engine);
+        let mut mount = WarheadMount::new(&mut id_generator);
+        mount.loaded = true;
+        let mount_id = mount.id;
+        source.launch_clamps_mut().insert(mount.id, mount);
+        let factory = *source.factories().keys().next().unwrap();
+        let destination = station(&mut id_generator, 0.into());
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+        let before = state.conserved_quantities();
+
+        let max = u64::MAX;
+        assert_eq!(
+            CargoTransfer {
+                stack: source_id,
+                destination: destination_id,
+                amount: TransferAmount::Exactly(CargoList::new(max, max, max, max)),
+            }
+            .resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientCargo)
+        );
+        assert_eq!(
+            FuelTransfer {
+                stack: source_id,
+                destination: destination_id,
+                amount: TransferAmount::Exactly(max),
+            }
+            .resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientFuel)
+        );
+        for kind in [
+            ConversionKind::OreToMaterials,
+            ConversionKind::IceToFuel,
+            ConversionKind::MaterialsToWarheads,
+        ] {
+            assert_eq!(
+                Conversion {
+                    stack: source_id,
+                    factory,
+                    kind,
+                    amount: max,
+                }
+                .resolve(0.into(), &mut state),
+                Err(OrderError::Overflow)
+            );
+        }
+        assert_eq!(
+            Jettison {
+                stack: source_id,
+                cargo: CargoList::new(max, max, max, max),
+                fuel: max,
+            }
+            .resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientCargo)
+        );
+        assert_eq!(
+            state.stacks.get_mut(&source_id).unwrap().add_fuel(max),
+            Err(OrderError::Overflow)
+        );
+        assert_eq!(
+            state
+                .stacks
+                .get_mut(&source_id)
+                .unwrap()
+                .deposit_cargo(CargoList::new(max, max, 0, 0)),
+            Err(OrderError::Overflow)
+        );
+        let burn = |q, r| Burn {
+            stack: source_id,
+            delta: vec2::Displacement::new(q, r),
+        };
+        assert_eq!(
+            burn(i64::MAX, i64::MAX).resolve(0.into(), &mut state),
+            Err(OrderError::Overflow)
+        );
+        assert_eq!(
+            burn(i64::MAX, 0).resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientThrust)
+        );
+        assert_eq!(
+            state.stacks[&source_id].burn_cost(vec2::Displacement::new(i64::MIN, -1)),
+            None
+        );
+        for delta in [
+            vec2::Displacement::new(i64::MAX, 1),
+            vec2::Displacement::new(i64::MIN, -1),
+        ] {
+            assert_eq!(
+                Launch {
+                    stack: source_id,
+                    mount: mount_id,
+                    delta,
+                }
+                .resolve(0.into(), &mut state, &mut id_generator),
+                Err(OrderError::Overflow)
+            );
+        }
+        assert!(state.warheads.is_empty());
+        assert_eq!(state.conserved_quantities(), before);
+
+        let results = state.resolve_movement(
+            &[(0.into(), burn(i64::MAX, 0)), (0.into(), burn(i64::MAX, 0))],
+            &mut rand_chacha::ChaCha20Rng::seed_from_u64(0),
+        );
+        assert_eq!(results.burns, [Err(OrderError::Overflow); 2]);
+
+        state.rules.materials_per_mass = max;
+        assert_eq!(StackComponent::Gun.production_cost(&state.rules), None);
+        assert_eq!(
+            Production {
+                stack: source_id,
+                factory,
+                component: StackComponent::Gun,
+            }
+            .resolve(0.into(), &mut state, &mut id_generator),
+            Err(OrderError::Overflow)
+        );
+        state.rules.repair_material_cost = max;
+        let holds = state.stacks[&source_id]
+            .cargo_holds()
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+        for hold in &holds {
+            let stack = state.stacks.get_mut(&source_id).unwrap();
+            stack.cargo_holds_mut().get_mut(hold).unwrap().damaged = true;
+        }
+        assert_eq!(state.repair_cost(source_id, &holds), max);
+        assert_eq!(
+            FactoryRepair {
+                stack: source_id,
+                factory,
+                components: holds,
+            }
+            .resolve(0.into(), &mut state),
+            Err(OrderError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_economic_order_variants() {
+        // adding a variant breaks this match until it's listed here; the
+        // economic resolver must handle it too
+        let stack = EntityId::from(1);
+        let other = EntityId::from(2);
+        let all = [
+            EconomicOrder::Production(Production {
+                stack,
+                factory: other,
+                component: StackComponent::Gun,
+            }),
+            EconomicOrder::CargoTransfer(CargoTransfer {
+                stack,
+                destination: other,
+                amount: TransferAmount::Exactly(CargoList::default()),
+            }),
+            EconomicOrder::FuelTransfer(FuelTransfer {
+                stack,
+                destination: other,
+                amount: TransferAmount::Exactly(0),
+            }),
+            EconomicOrder::Reload(Reload {
+                stack,
+                mount: other,
+            }),
+            EconomicOrder::FactoryRepair(FactoryRepair {
+                stack,
+                factory: other,
+                components: Vec::new(),
+            }),
+            EconomicOrder::HabitatRepair(HabitatRepair {
+                stack,
+                habitat: other,
+                component: other,
+            }),
+            EconomicOrder::StackTransfer(StackTransfer {
+                stack,
+                destination: StackTransferTarget::New(0),
+                components: Vec::new(),
+            }),
+            EconomicOrder::Conversion(Conversion {
+                stack,
+                factory: other,
+                kind: ConversionKind::IceToFuel,
+                amount: 0,
+            }),
+            EconomicOrder::Jettison(Jettison {
+                stack,
+                cargo: CargoList::default(),
+                fuel: 0,
+            }),
+            EconomicOrder::Unload(Unload {
+                stack,
+                mount: other,
+            }),
+        ];
+        let indices = all
+            .iter()
+            .map(|order| match order {
+                EconomicOrder::Production(_) => 0,
+                EconomicOrder::CargoTransfer(_) => 1,
+                EconomicOrder::FuelTransfer(_) => 2,
+                EconomicOrder::Reload(_) => 3,
+                EconomicOrder::FactoryRepair(_) => 4,
+                EconomicOrder::HabitatRepair(_) => 5,
+                EconomicOrder::StackTransfer(_) => 6,
+                EconomicOrder::Conversion(_) => 7,
+                EconomicOrder::Jettison(_) => 8,
+                EconomicOrder::Unload(_) => 9,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (0..all.len()).collect::<Vec<_>>());
+        assert!(all.iter().all(|order| order.stack() == stack));
+    }
+
+    #[test]
+    fn test_new_stack_records_creation() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.round = 3;
+        let source = station(&mut id_generator, 0.into());
+        let hold = *source.cargo_holds().keys().next().unwrap();
+        let source_id = source.id;
+        state.stacks.insert(source.id, source);
+
+        let mut new_stacks = HashMap::new();
+        StackTransfer {
+            stack: source_id,
+            destination: StackTransferTarget::New(7),
+            components: vec![hold],
+        }
+        .resolve(0.into(), &mut state, &mut id_generator, &mut new_stacks)
+        .unwrap();
+        let created = &state.stacks[&new_stacks[&7]];
+        assert_eq!(created.created, Some((3, Phase::Economic)));
+        assert!(created.cargo_holds().contains_key(&hold));
+        assert!(!state.stacks[&source_id].cargo_holds().contains_key(&hold));
+
+        let serialized = serde_json::to_string(created).unwrap();
+        let deserialized: Stack = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.created, Some((3, Phase::Economic)));
+    }
+
+    #[test]
+    fn test_transfer_feasibility() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        source.deposit_cargo(CargoList::new(0, 0, 30, 0)).unwrap();
+        let mut destination = station(&mut id_generator, 1.into());
+        destination
+            .deposit_cargo(CargoList::new(0, 15, 0, 0))
+            .unwrap();
+        let mut distant = station(&mut id_generator, 1.into());
+        distant.velocity = vec2::Displacement::new(1, 0);
+        let (source_id, destination_id, distant_id) = (source.id, destination.id, distant.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+        state.stacks.insert(distant.id, distant);
+
+        let fits = TransferRequest::Cargo(CargoList::new(0, 0, 25, 0));
+        let too_much = TransferRequest::Cargo(CargoList::new(0, 0, 30, 0));
+        assert_eq!(
+            state.transfer_feasibility(0.into(), source_id, destination_id, &fits),
+            Ok(())
+        );
+        assert_eq!(
+            state.transfer_feasibility(0.into(), source_id, destination_id, &too_much),
+            Err(OrderError::CapacityExceeded)
+        );
+        assert_eq!(
+            state.transfer_feasibility(0.into(), source_id, distant_id, &fits),
+            Err(OrderError::NotRendezvoused)
+        );
+        assert_eq!(
+            state.transfer_feasibility(1.into(), source_id, destination_id, &fits),
+            Err(OrderError::NotControlled)
+        );
+        assert_eq!(
+            state.transfer_feasibility(
+                0.into(),
+                source_id,
+                destination_id,
+                &TransferRequest::Fuel(1)
+            ),
+            Err(OrderError::InsufficientFuel)
+        );
+    }
+
+    #[test]
+    fn test_transfer_relative_speed() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let source = station(&mut id_generator, 0.into());
+        let source_id = source.id;
+        state.stacks.insert(source.id, source);
+        let mut destinations = Vec::new();
+        for (position, velocity) in [
+            (vec2::Position::new(0, 0), vec2::Displacement::new(0, 0)),
+            (vec2::Position::new(0, 0), vec2::Displacement::new(1, 0)),
+            (vec2::Position::new(1, 0), vec2::Displacement::new(0, 1)),
+            (vec2::Position::new(0, 0), vec2::Displacement::new(2, 0)),
+            (vec2::Position::new(2, 0), vec2::Displacement::new(0, 0)),
+        ] {
+            let mut destination = station(&mut id_generator, 0.into());
+            destination.position = position;
+            destination.velocity = velocity;
+            destinations.push(destination.id);
+            state.stacks.insert(destination.id, destination);
+        }
+        let what = TransferRequest::Fuel(0);
+        let feasible = |state: &GameState| {
+            destinations
+                .iter()
+                .map(|destination| {
+                    state
+                        .transfer_feasibility(0.into(), source_id, *destination, &what)
+                        .is_ok()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(feasible(&state), [true, false, false, false, false]);
+        state.rules.transfer_max_relative_speed = 1;
+        assert_eq!(feasible(&state), [true, true, true, false, false]);
+        assert_eq!(
+            state.transfer_feasibility(0.into(), source_id, destinations[3], &what),
+            Err(OrderError::NotRendezvoused)
+        );
+    }
+
+    #[test]
+    fn test_transfers_share_validation() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        source.deposit_cargo(CargoList::new(1, 0, 0, 0)).unwrap();
+        let tank = crate::stack::FuelTank::new(&mut id_generator);
+        source.fuel_tanks_mut().insert(tank.id, tank);
+        source.add_fuel(1).unwrap();
+        let hold = *source.cargo_holds().keys().next().unwrap();
+        let mut destination = station(&mut id_generator, 0.into());
+        destination.velocity = vec2::Displacement::new(0, 1);
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+
+        let mut new_stacks = HashMap::new();
+        let results = [
+            CargoTransfer {
+                stack: source_id,
+                destination: destination_id,
+                amount: TransferAmount::Exactly(CargoList::new(1, 0, 0, 0)),
+            }
+            .resolve(0.into(), &mut state),
+            FuelTransfer {
+                stack: source_id,
+                destination: destination_id,
+                amount: TransferAmount::Exactly(1),
+            }
+            .resolve(0.into(), &mut state),
+            StackTransfer {
+                stack: source_id,
+                destination: StackTransferTarget::Existing(destination_id),
+                components: vec![hold],
+            }
+            .resolve(0.into(), &mut state, &mut id_generator, &mut new_stacks),
+        ];
+        assert_eq!(results, [Err(OrderError::NotRendezvoused); 3]);
+
+        state.stacks.get_mut(&destination_id).unwrap().velocity = vec2::Displacement::ZERO;
+        assert_eq!(
+            state.resolve_transfer(
+                1.into(),
+                source_id,
+                destination_id,
+                &TransferRequest::Fuel(1)
+            ),
+            Err(OrderError::NotControlled)
+        );
+        assert_eq!(
+            state.resolve_transfer(
+                0.into(),
+                source_id,
+                destination_id,
+                &TransferRequest::Fuel(1)
+            ),
+            Err(OrderError::CapacityExceeded)
+        );
+        for request in [
+            TransferRequest::Cargo(CargoList::new(1, 0, 0, 0)),
+            TransferRequest::Components(vec![hold]),
+        ] {
+            state
+                .resolve_transfer(0.into(), source_id, destination_id, &request)
+                .unwrap();
+        }
+        let destination = &state.stacks[&destination_id];
+        assert_eq!(destination.cargo().ice, 1);
+        assert!(destination.cargo_holds().contains_key(&hold));
+    }
+
+    #[test]
+    fn test_capture_derelict() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut derelict = station(&mut id_generator, 1.into());
+        derelict.habitats_mut().clear();
+        assert!(derelict.is_derelict());
+        assert_eq!(derelict.controller(), None);
+        let mut captor = station(&mut id_generator, 0.into());
+        let habitat = *captor.habitats().keys().next().unwrap();
+        let extra = Habitat::new(&mut id_generator, 0.into());
+        captor.habitats_mut().insert(extra.id, extra);
+        let (derelict_id, captor_id) = (derelict.id, captor.id);
+        state.stacks.insert(derelict.id, derelict);
+        state.stacks.insert(captor.id, captor);
+
+        // nobody controls a derelict, so nothing can be taken out of it
+        assert_eq!(
+            state.resolve_transfer(
+                1.into(),
+                derelict_id,
+                captor_id,
+                &TransferRequest::Cargo(CargoList::default())
+            ),
+            Err(OrderError::NotControlled)
+        );
+
+        state
+            .resolve_transfer(
+                0.into(),
+                captor_id,
+                derelict_id,
+                &TransferRequest::Components(vec![habitat]),
+            )
+            .unwrap();
+        let captured = &state.stacks[&derelict_id];
+        assert!(!captured.is_derelict());
+        assert_eq!(captured.controller(), Some(0.into()));
+        assert_eq!(captured.owner, 0.into());
+        assert_eq!(state.stacks[&captor_id].controller(), Some(0.into()));
+    }
+
+    #[test]
+    fn test_targets_in_range() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let shooter = station(&mut id_generator, 0.into());
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let mut targets = Vec::new();
+        for (position, player) in [
+            (vec2::Position::new(1, 0), 1),
+            (vec2::Position::new(-2, 0), 1),
+            (vec2::Position::new(0, 1), 0),
+            (vec2::Position::new(4, 0), 1),
+        ] {
+            let mut stack = station(&mut id_generator, player.into());
+            stack.position = position;
+            targets.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let planet = crate::astronomical::MajorBody::new(
+            "Venus",
+            &mut id_generator,
+            vec2::Position::new(-1, 0),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(planet.id, planet);
+
+        // the second target is behind the planet, the third is friendly, and
+        // the fourth is out of range
+        assert_eq!(
+            state.targets_in_range(shooter_id),
+            vec![(targets[0], Gun::hit_chance(1))]
+        );
+        assert_eq!(Gun::hit_chance(1), 2.0 / 3.0);
+        let origin = vec2::Position::new(0, 0);
+        assert!(state.line_of_sight(origin, origin));
+    }
+
+    #[test]
+    fn test_shoot_agrees_with_targets_in_range() {
+        use rand::SeedableRng;
+
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = station(&mut id_generator, 0.into());
+        let gun = Gun::new(&mut id_generator);
+        let gun_id = gun.id;
+        shooter.guns_mut().insert(gun.id, gun);
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let mut targets = Vec::new();
+        for position in [
+            vec2::Position::new(1, 0),
+            vec2::Position::new(-2, 0),
+            vec2::Position::new(0, 3),
+            vec2::Position::new(4, 0),
+        ] {
+            let mut stack = station(&mut id_generator, 1.into());
+            stack.position = position;
+            targets.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let planet = crate::astronomical::MajorBody::new(
+            "Venus",
+            &mut id_generator,
+            vec2::Position::new(-1, 0),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(planet.id, planet);
+
+        let in_range = state
+            .targets_in_range(shooter_id)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(in_range, vec![targets[0], targets[2]]);
+        for (target, error) in targets.into_iter().zip([
+            None,
+            Some(OrderError::NoLineOfSight),
+            None,
+            Some(OrderError::OutOfRange),
+        ]) {
+            let result = Shoot {
+                stack: shooter_id,
+                gun: gun_id,
+                target,
+            }
+            .resolve(
+                0.into(),
+                &mut state.clone(),
+                0,
+                &mut rand_chacha::ChaCha20Rng::seed_from_u64(0),
+            );
+            assert_eq!(result.err(), error);
+            assert_eq!(in_range.contains(&target), error.is_none());
+        }
+    }
+
+    #[test]
+    fn test_incoming_threat() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = station(&mut id_generator, 1.into());
+        for _ in 0..2 {
+            let gun = Gun::new(&mut id_generator);
+            shooter.guns_mut().insert(gun.id, gun);
+        }
+        state.stacks.insert(shooter.id, shooter);
+        let mut target = station(&mut id_generator, 0.into());
+        target.position = vec2::Position::new(5, 0);
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+
+        assert_eq!(state.incoming_threat(target_id), 0.0);
+        let mut threats = Vec::new();
+        for q in [3, 2, 1] {
+            state.stacks.get_mut(&target_id).unwrap().position = vec2::Position::new(q, 0);
+            threats.push(state.incoming_threat(target_id));
+        }
+        assert!(threats[0] > 0.0);
+        assert!(threats[0] < threats[1] && threats[1] < threats[2]);
+        assert_eq!(threats[2], 2.0 * Gun::hit_chance(1));
+
+        let warhead = crate::stack::Warhead::new(
+            &mut id_generator,
+            vec2::Position::new(1, 0),
+            vec2::Displacement::new(0, 0),
+            1.into(),
+        );
+        state.warheads.insert(warhead.id, warhead);
+        assert_eq!(
+            state.incoming_threat(target_id),
+            2.0 * Gun::hit_chance(1) + f64::from(crate::stack::Warhead::DAMAGE)
+        );
+    }
+
+    #[test]
+    fn test_incoming_threat_projects_warheads() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let target = station(&mut id_generator, 0.into());
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+        let warhead = crate::stack::Warhead::new(
+            &mut id_generator,
+            vec2::Position::new(8, 0),
+            vec2::Displacement::new(-8, 0),
+            1.into(),
+        );
+        let warhead_id = warhead.id;
+        state.warheads.insert(warhead.id, warhead);
+
+        // out of blast range now, but on course to pass through next turn
+        assert_eq!(
+            state.incoming_threat(target_id),
+            f64::from(crate::stack::Warhead::DAMAGE)
+        );
+
+        state.warheads.get_mut(&warhead_id).unwrap().velocity = vec2::Displacement::new(8, 0);
+        assert_eq!(state.incoming_threat(target_id), 0.0);
+    }
+
+    #[test]
+    fn test_fuel_to_reach() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = Stack::new(
+            "Tug",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 0.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        for _ in 0..2 {
+            let engine = Engine::new(&mut id_generator);
+            stack.engines_mut().insert(engine.id, engine);
+        }
+        let tank = FuelTank::new(&mut id_generator);
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+        stack.add_fuel(10).unwrap();
+        let straight = stack.burn_cost(vec2::Displacement::new(2, 0)).unwrap();
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        assert_eq!(
+            state.fuel_to_reach(id, vec2::Position::new(0, 0), 0),
+            Some(0)
+        );
+        assert_eq!(
+            state.fuel_to_reach(id, vec2::Position::new(2, 0), 1),
+            Some(straight)
+        );
+        assert_eq!(state.fuel_to_reach(id, vec2::Position::new(20, 0), 1), None);
+        assert_eq!(state.fuel_to_reach(id, vec2::Position::new(2, 0), 0), None);
+        assert_eq!(
+            state.fuel_to_reach(0.into(), vec2::Position::new(2, 0), 1),
+            None
+        );
+
+        // every course leaves the map on the first turn, so however many
+        // turns are allowed, the search ends there
+        state.rules.map_radius = Some(5);
+        let stack = state.stacks.get_mut(&id).unwrap();
+        stack.position = vec2::Position::new(5, 0);
+        stack.velocity = vec2::Displacement::new(4, 0);
+        assert_eq!(
+            state.fuel_to_reach(id, vec2::Position::new(0, 0), u64::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reachable_next_turn() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = Stack::new(
+            "Shuttle",
+            &mut id_generator,
+            vec2::Position::new(1, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 0.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let engine = Engine::new(&mut id_generator);
+        stack.engines_mut().insert(engine.id, engine);
+        let tank = FuelTank::new(&mut id_generator);
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+        stack.add_fuel(5).unwrap();
+        assert_eq!(stack.max_delta(), 1);
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+        let planet = crate::astronomical::MajorBody::new(
+            "Venus",
+            &mut id_generator,
+            vec2::Position::new(2, 0),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(planet.id, planet);
+
+        let reachable = state.reachable_next_turn(id);
+        assert_eq!(reachable.len(), 7);
+        let at = |position: vec2::Position| {
+            reachable
+                .iter()
+                .find(|hex| hex.position == position)
+                .copied()
+        };
+
+        // coasting falls into the planet
+        let coast = at(vec2::Position::new(2, 0)).unwrap();
+        assert!(coast.lost);
+        assert!(coast.burn.is_zero());
+        // staying put takes a full burn against gravity
+        let hover = at(vec2::Position::new(1, 0)).unwrap();
+        assert!(!hover.lost);
+        assert_eq!(hover.burn, vec2::Displacement::new(-1, 0));
+        // backing away from the planet is out of reach
+        assert_eq!(at(vec2::Position::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_order_limit() {
+        let rules = RuleSet {
+            max_orders_per_phase: 2,
+            ..Default::default()
+        };
+        let burn = Burn {
+            stack: 1.into(),
+            delta: vec2::Displacement::ZERO,
+        };
+        let at_limit = PlayerTurn::Movement(vec![burn.clone(); 2]);
+        let over_limit = PlayerTurn::Movement(vec![burn; 3]);
+        assert_eq!(at_limit.validate_phase(Phase::Movement, &rules), Ok(()));
+        assert_eq!(
+            over_limit.validate_phase(Phase::Movement, &rules),
+            Err(OrderError::TooManyOrders)
+        );
+        assert_eq!(
+            at_limit.validate_phase(Phase::Combat, &rules),
+            Err(OrderError::WrongPhase)
+        );
+    }
+
+    #[test]
+    fn test_jettison() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = Stack::new(
+            "Tanker",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 0.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let engine = Engine::new(&mut id_generator);
+        stack.engines_mut().insert(engine.id, engine);
+        let hold = CargoHold::new(&mut id_generator);
+        stack.cargo_holds_mut().insert(hold.id, hold);
+        for _ in 0..2 {
+            let tank = FuelTank::new(&mut id_generator);
+            stack.fuel_tanks_mut().insert(tank.id, tank);
+        }
+        stack.add_fuel(40).unwrap();
+        stack.deposit_cargo(CargoList::new(3, 0, 0, 0)).unwrap();
+        assert_eq!(stack.mobility_status(), MobilityStatus::Overweight);
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let too_much = Jettison {
+            stack: id,
+            cargo: CargoList::new(4, 0, 0, 0),
+            fuel: 0,
+        };
+        assert_eq!(
+            too_much.resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientCargo)
+        );
+        let too_much = Jettison {
+            stack: id,
+            cargo: CargoList::default(),
+            fuel: 41,
+        };
+        assert_eq!(
+            too_much.resolve(0.into(), &mut state),
+            Err(OrderError::InsufficientFuel)
+        );
+        assert_eq!(state.stacks[&id].fuel(), 40);
+
+        Jettison {
+            stack: id,
+            cargo: CargoList::new(3, 0, 0, 0),
+            fuel: 35,
+        }
+        .resolve(0.into(), &mut state)
+        .unwrap();
+        let stack = &state.stacks[&id];
+        assert_eq!(stack.fuel(), 5);
+        assert_eq!(stack.cargo(), CargoList::default());
+        assert!(matches!(
+            stack.mobility_status(),
+            MobilityStatus::Mobile { .. }
+        ));
+    }
 
     #[test]
-    fn test() {}
+    fn test_repair_cost() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = station(&mut id_generator, 0.into());
+        let holds = sorted_ids(stack.cargo_holds(), |_| true);
+        stack.cargo_holds_mut().get_mut(&holds[1]).unwrap().damaged = true;
+        let factory = *stack.factories().keys().next().unwrap();
+        stack.factories_mut().get_mut(&factory).unwrap().damaged = true;
+        stack.deposit_cargo(CargoList::new(0, 0, 5, 0)).unwrap();
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let mut expected = vec![holds[1], factory];
+        expected.sort();
+        assert_eq!(state.stacks[&id].damaged_components(), expected);
+
+        assert_eq!(state.repair_cost(id, &expected), 2);
+        assert_eq!(state.repair_cost(id, &[holds[1], holds[1]]), 1);
+        assert_eq!(state.repair_cost(id, &[holds[0]]), 0);
+        assert_eq!(state.repair_cost(id, &[]), 0);
+
+        let habitat = *state.stacks[&id].habitats().keys().next().unwrap();
+        HabitatRepair {
+            stack: id,
+            habitat,
+            component: holds[1],
+        }
+        .resolve(0.into(), &mut state)
+        .unwrap();
+        let stack = &state.stacks[&id];
+        assert_eq!(stack.damaged_components(), vec![factory]);
+        assert_eq!(stack.cargo().materials, 4);
+    }
+
+    #[test]
+    fn test_unrepairable_stacks() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut station = station(&mut id_generator, 0.into());
+        let hold = *station.cargo_holds().keys().next().unwrap();
+        station.cargo_holds_mut().get_mut(&hold).unwrap().damaged = true;
+        let mut drone = Stack::new(
+            "Drone",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            1.into(),
+        );
+        // captured, so player 0 has to see to its repairs
+        drone.captor = Some(0.into());
+        let mut gun = crate::stack::Gun::new(&mut id_generator);
+        gun.damaged = true;
+        drone.guns_mut().insert(gun.id, gun);
+        let drone_id = drone.id;
+        state.stacks.insert(station.id, station);
+        state.stacks.insert(drone.id, drone);
+
+        assert_eq!(state.unrepairable_stacks(0.into()), vec![drone_id]);
+        assert!(state.unrepairable_stacks(1.into()).is_empty());
+    }
+
+    #[test]
+    fn test_custom_repair_rules() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.repair_material_cost = 3;
+        state.rules.habitat_repairs_per_phase = 2;
+        let mut stack = station(&mut id_generator, 0.into());
+        let holds = sorted_ids(stack.cargo_holds(), |_| true);
+        for hold in &holds {
+            stack.cargo_holds_mut().get_mut(hold).unwrap().damaged = true;
+        }
+        stack.deposit_cargo(CargoList::new(0, 0, 10, 0)).unwrap();
+        let factory = *stack.factories().keys().next().unwrap();
+        let habitat = *stack.habitats().keys().next().unwrap();
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let mut habitat_state = state.clone();
+        assert_eq!(state.repair_cost(id, &holds), 6);
+        FactoryRepair {
+            stack: id,
+            factory,
+            components: holds.clone(),
+        }
+        .resolve(0.into(), &mut state)
+        .unwrap();
+        assert!(state.stacks[&id].damaged_components().is_empty());
+        assert_eq!(state.stacks[&id].cargo().materials, 4);
+
+        let repair = |component| {
+            (
+                0.into(),
+                EconomicOrder::HabitatRepair(HabitatRepair {
+                    stack: id,
+                    habitat,
+                    component,
+                }),
+            )
+        };
+        habitat_state
+            .stacks
+            .get_mut(&id)
+            .unwrap()
+            .factories_mut()
+            .get_mut(&factory)
+            .unwrap()
+            .damaged = true;
+        let results = habitat_state.resolve_economic(
+            &[repair(holds[0]), repair(holds[1]), repair(factory)],
+            &mut id_generator,
+        );
+        assert_eq!(
+            results.orders,
+            vec![Ok(()), Ok(()), Err(OrderError::AlreadyActed)]
+        );
+        assert_eq!(habitat_state.stacks[&id].cargo().materials, 4);
+    }
+
+    #[test]
+    fn test_validate_turn() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let stack = station(&mut id_generator, 0.into());
+        let id = stack.id;
+        let factory = *stack.factories().keys().next().unwrap();
+        let habitat = *stack.habitats().keys().next().unwrap();
+        state.stacks.insert(stack.id, stack);
+        let other = station(&mut id_generator, 1.into());
+        let other_id = other.id;
+        state.stacks.insert(other.id, other);
+
+        let turn = PlayerTurn::Economic(vec![
+            EconomicOrder::Conversion(Conversion {
+                stack: id,
+                factory,
+                kind: ConversionKind::OreToMaterials,
+                amount: 1,
+            }),
+            EconomicOrder::HabitatRepair(HabitatRepair {
+                stack: id,
+                habitat,
+                component: other_id,
+            }),
+            EconomicOrder::Jettison(Jettison {
+                stack: id,
+                cargo: CargoList::default(),
+                fuel: 0,
+            }),
+        ]);
+        assert_eq!(
+            state.validate_turn(0.into(), &turn),
+            vec![(1, OrderError::NoSuchComponent)]
+        );
+        assert_eq!(
+            state.validate_turn(1.into(), &turn),
+            vec![
+                (0, OrderError::NotControlled),
+                (1, OrderError::NotControlled),
+                (2, OrderError::NotControlled)
+            ]
+        );
+
+        let turn = PlayerTurn::Movement(vec![Burn {
+            stack: id,
+            delta: vec2::Displacement::new(1, 0),
+        }]);
+        assert_eq!(
+            state.validate_turn(0.into(), &turn),
+            vec![(0, OrderError::WrongPhase)]
+        );
+    }
+
+    #[test]
+    fn test_transfer_amount_wire_format() {
+        let amount = |json: &str| {
+            let order: EconomicOrder = serde_json::from_str(&format!(
+                r#"{{"FuelTransfer": {{"stack": 1, "destination": 2, "amount": {json}}}}}"#
+            ))
+            .unwrap();
+            match order {
+                EconomicOrder::FuelTransfer(transfer) => transfer.amount,
+                _ => unreachable!(),
+            }
+        };
+        // a bare amount, as sent before `All` existed, is exact
+        assert_eq!(amount("5"), TransferAmount::Exactly(5));
+        assert_eq!(amount(r#""All""#), TransferAmount::All);
+
+        let order: EconomicOrder = serde_json::from_str(
+            r#"{"CargoTransfer": {"stack": 1, "destination": 2,
+                "amount": {"ice": 1, "ore": 2, "materials": 3, "warheads": 0}}}"#,
+        )
+        .unwrap();
+        let EconomicOrder::CargoTransfer(transfer) = order else {
+            unreachable!()
+        };
+        assert_eq!(
+            transfer.amount,
+            TransferAmount::Exactly(CargoList::new(1, 2, 3, 0))
+        );
+    }
+
+    #[test]
+    fn test_transfer_all() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        let mut destination = station(&mut id_generator, 0.into());
+        for stack in [&mut source, &mut destination] {
+            let tank = FuelTank::new(&mut id_generator);
+            stack.fuel_tanks_mut().insert(tank.id, tank);
+        }
+        source.deposit_cargo(CargoList::new(5, 10, 0, 0)).unwrap();
+        source.add_fuel(15).unwrap();
+        destination.add_fuel(10).unwrap();
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+        let cargo = CargoTransfer {
+            stack: source_id,
+            destination: destination_id,
+            amount: TransferAmount::All,
+        };
+        let fuel = FuelTransfer {
+            stack: source_id,
+            destination: destination_id,
+            amount: TransferAmount::All,
+        };
+
+        cargo.resolve(0.into(), &mut state).unwrap();
+        assert_eq!(state.stacks[&source_id].cargo(), CargoList::default());
+        assert_eq!(
+            state.stacks[&destination_id].cargo(),
+            CargoList::new(5, 10, 0, 0)
+        );
+        fuel.resolve(0.into(), &mut state).unwrap();
+        assert_eq!(state.stacks[&source_id].fuel(), 5);
+        assert_eq!(state.stacks[&destination_id].fuel(), 20);
+
+        state
+            .stacks
+            .get_mut(&destination_id)
+            .unwrap()
+            .deposit_cargo(CargoList::new(0, 0, 20, 0))
+            .unwrap();
+        state
+            .stacks
+            .get_mut(&source_id)
+            .unwrap()
+            .deposit_cargo(CargoList::new(5, 10, 0, 0))
+            .unwrap();
+        cargo.resolve(0.into(), &mut state).unwrap();
+        assert_eq!(
+            state.stacks[&source_id].cargo(),
+            CargoList::new(0, 10, 0, 0)
+        );
+        assert_eq!(state.stacks[&destination_id].cargo().total(), 40);
+    }
+
+    #[test]
+    fn test_plan_fuel_distribution() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stacks = Vec::new();
+        for (position, fuel) in [((0, 0), 30), ((0, 0), 15), ((0, 0), 5), ((3, 0), 0)] {
+            let mut stack = station(&mut id_generator, 0.into());
+            stack.position = vec2::Position::from_tuple(position);
+            for _ in 0..2 {
+                let tank = FuelTank::new(&mut id_generator);
+                stack.fuel_tanks_mut().insert(tank.id, tank);
+            }
+            stack.add_fuel(fuel).unwrap();
+            stacks.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let mut enemy = station(&mut id_generator, 1.into());
+        let tank = FuelTank::new(&mut id_generator);
+        enemy.fuel_tanks_mut().insert(tank.id, tank);
+        state.stacks.insert(enemy.id, enemy);
+
+        // 25 would go to the first partner and 35 to the second, but the
+        // tanker only has 30
+        let plan = state.plan_fuel_distribution(stacks[0]);
+        assert_eq!(
+            plan.iter()
+                .map(|order| (order.destination, order.amount.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (stacks[1], TransferAmount::Exactly(25)),
+                (stacks[2], TransferAmount::Exactly(5)),
+            ]
+        );
+        for order in plan {
+            order.resolve(0.into(), &mut state).unwrap();
+        }
+        assert_eq!(state.stacks[&stacks[0]].fuel(), 0);
+        assert_eq!(state.stacks[&stacks[1]].fuel(), 40);
+        assert_eq!(state.stacks[&stacks[2]].fuel(), 10);
+        assert_eq!(state.stacks[&stacks[3]].fuel(), 0);
+    }
+
+    #[test]
+    fn test_cargo_transfer_conserves() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        source.deposit_cargo(CargoList::new(5, 25, 3, 1)).unwrap();
+        let destination = station(&mut id_generator, 1.into());
+        let order = CargoTransfer {
+            stack: source.id,
+            destination: destination.id,
+            amount: TransferAmount::Exactly(CargoList::new(5, 20, 0, 1)),
+        };
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+
+        let before = state.conserved_quantities();
+        order.resolve(0.into(), &mut state).unwrap();
+        assert_eq!(state.conserved_quantities(), before);
+        assert_eq!(state.stacks[&source_id].cargo(), CargoList::new(0, 5, 3, 0));
+        assert_eq!(
+            state.stacks[&destination_id].cargo(),
+            CargoList::new(5, 20, 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_reload_keeps_total_warheads() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = station(&mut id_generator, 0.into());
+        stack.deposit_cargo(CargoList::new(0, 0, 0, 2)).unwrap();
+        let mount = WarheadMount::new(&mut id_generator);
+        let mount_id = mount.id;
+        stack.launch_clamps_mut().insert(mount.id, mount);
+        let (id, mass) = (stack.id, stack.mass());
+        assert_eq!(stack.total_warheads(), 2);
+        state.stacks.insert(stack.id, stack);
+
+        let reload = Reload {
+            stack: id,
+            mount: mount_id,
+        };
+        reload.resolve(0.into(), &mut state).unwrap();
+        let stack = &state.stacks[&id];
+        assert_eq!(stack.cargo().warheads, 1);
+        assert_eq!(stack.total_warheads(), 2);
+        assert_eq!(stack.mass(), mass);
+
+        let turn = PlayerTurn::Economic(vec![EconomicOrder::Reload(reload.clone())]);
+        assert_eq!(
+            state.validate_turn(0.into(), &turn),
+            vec![(0, OrderError::MountLoaded)]
+        );
+        assert_eq!(
+            reload.resolve(0.into(), &mut state),
+            Err(OrderError::MountLoaded)
+        );
+        assert_eq!(state.stacks[&id].total_warheads(), 2);
+    }
+
+    #[test]
+    fn test_unload() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = station(&mut id_generator, 0.into());
+        let mut mount = WarheadMount::new(&mut id_generator);
+        mount.loaded = true;
+        let mount_id = mount.id;
+        stack.launch_clamps_mut().insert(mount.id, mount);
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let unload = Unload {
+            stack: id,
+            mount: mount_id,
+        };
+        let reload = Reload {
+            stack: id,
+            mount: mount_id,
+        };
+        let turn = PlayerTurn::Economic(vec![
+            EconomicOrder::Unload(unload.clone()),
+            EconomicOrder::Reload(reload.clone()),
+        ]);
+        assert!(state.validate_turn(0.into(), &turn).is_empty());
+
+        unload.resolve(0.into(), &mut state).unwrap();
+        let stack = &state.stacks[&id];
+        assert!(!stack.launch_clamps()[&mount_id].loaded);
+        assert_eq!(stack.cargo().warheads, 1);
+        assert_eq!(stack.total_warheads(), 1);
+        assert_eq!(
+            unload.resolve(0.into(), &mut state),
+            Err(OrderError::MountEmpty)
+        );
+
+        reload.resolve(0.into(), &mut state).unwrap();
+        let capacity = state.stacks[&id].cargo_capacity();
+        state
+            .stacks
+            .get_mut(&id)
+            .unwrap()
+            .deposit_cargo(CargoList::new(0, 0, capacity, 0))
+            .unwrap();
+        assert_eq!(
+            unload.resolve(0.into(), &mut state),
+            Err(OrderError::CapacityExceeded)
+        );
+        assert!(state.stacks[&id].launch_clamps()[&mount_id].loaded);
+    }
+
+    #[test]
+    fn test_transfer_warheads_then_reload() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        source.deposit_cargo(CargoList::new(0, 0, 0, 3)).unwrap();
+        let mut destination = station(&mut id_generator, 0.into());
+        destination
+            .deposit_cargo(CargoList::new(0, 0, 38, 0))
+            .unwrap();
+        let mount = WarheadMount::new(&mut id_generator);
+        let mount_id = mount.id;
+        destination.launch_clamps_mut().insert(mount.id, mount);
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+
+        let transfer = |warheads| CargoTransfer {
+            stack: source_id,
+            destination: destination_id,
+            amount: TransferAmount::Exactly(CargoList::new(0, 0, 0, warheads)),
+        };
+        assert_eq!(
+            transfer(3).resolve(0.into(), &mut state),
+            Err(OrderError::CapacityExceeded)
+        );
+        state
+            .stacks
+            .get_mut(&destination_id)
+            .unwrap()
+            .withdraw_cargo(CargoList::new(0, 0, 1, 0))
+            .unwrap();
+        let before = state.conserved_quantities();
+        transfer(3).resolve(0.into(), &mut state).unwrap();
+        Reload {
+            stack: destination_id,
+            mount: mount_id,
+        }
+        .resolve(0.into(), &mut state)
+        .unwrap();
+
+        let destination = &state.stacks[&destination_id];
+        assert_eq!(destination.cargo().warheads, 2);
+        assert!(destination.launch_clamps()[&mount_id].loaded);
+        assert_eq!(state.conserved_quantities(), before);
+    }
+
+    #[test]
+    fn test_orders_on_snapshot() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut source = station(&mut id_generator, 0.into());
+        source.deposit_cargo(CargoList::new(0, 10, 0, 0)).unwrap();
+        let destination = station(&mut id_generator, 0.into());
+        let order = CargoTransfer {
+            stack: source.id,
+            destination: destination.id,
+            amount: TransferAmount::Exactly(CargoList::new(0, 10, 0, 0)),
+        };
+        let (source_id, destination_id) = (source.id, destination.id);
+        state.stacks.insert(source.id, source);
+        state.stacks.insert(destination.id, destination);
+
+        let original = state.snapshot();
+        let mut preview = state.snapshot();
+        order.resolve(0.into(), &mut preview).unwrap();
+        assert_eq!(preview.stacks[&destination_id].cargo().ore, 10);
+        assert_eq!(state.stacks[&source_id].cargo().ore, 10);
+        assert_eq!(state.stacks[&destination_id].cargo().ore, 0);
+
+        order.resolve(0.into(), &mut state).unwrap();
+        state.restore(original);
+        assert_eq!(state.stacks[&source_id].cargo().ore, 10);
+    }
+
+    #[test]
+    fn test_conversion_ratio() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = station(&mut id_generator, 0.into());
+        stack.deposit_cargo(CargoList::new(0, 10, 0, 0)).unwrap();
+        let order = Conversion {
+            stack: stack.id,
+            factory: *stack.factories().keys().next().unwrap(),
+            kind: ConversionKind::OreToMaterials,
+            amount: 3,
+        };
+        state.stacks.insert(stack.id, stack);
+
+        let before = state.conserved_quantities();
+        order.resolve(0.into(), &mut state).unwrap();
+        let after = state.conserved_quantities();
+        assert_eq!(after.ore, before.ore - 3 * state.rules.ore_per_material);
+        assert_eq!(after.materials, before.materials + 3);
+        assert_eq!(after.ice, before.ice);
+        assert_eq!(after.fuel, before.fuel);
+        assert_eq!(after.warheads, before.warheads);
+        assert_eq!(after.component_mass, before.component_mass);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.ore_per_material = 3;
+        let mut stack = station(&mut id_generator, 0.into());
+        stack.deposit_cargo(CargoList::new(0, 10, 0, 0)).unwrap();
+        let order = Conversion {
+            stack: stack.id,
+            factory: *stack.factories().keys().next().unwrap(),
+            kind: ConversionKind::OreToMaterials,
+            amount: 3,
+        };
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        order.resolve(0.into(), &mut state).unwrap();
+        assert_eq!(state.stacks[&stack_id].cargo(), CargoList::new(0, 1, 3, 0));
+    }
 }
@@ -0,0 +1,100 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Turn reports
+//!
+//! What the server sends back once a phase has been resolved
+
+use std::collections::HashMap;
+
+#[cfg(feature = "client")]
+use serde::Deserialize;
+#[cfg(feature = "server")]
+use serde::Serialize;
+
+use crate::{
+    order::{HitRoll, OrderError, RollRecord},
+    stack::HitOutcome,
+    EntityId, GameOutcome, Phase, PlayerId,
+};
+
+/// The result of one order, keyed by its index in the turn it was given in
+pub type OrderResult = (usize, Result<(), OrderError>);
+
+/// Everything that happened when a phase was resolved
+///
+/// The counterpart of [`crate::order::PlayerTurn`]: players send turns, and
+/// get a report back
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnReport {
+    /// The round that was resolved
+    pub round: u64,
+    /// The phase that was resolved
+    pub phase: Phase,
+    /// The round now
+    pub next_round: u64,
+    /// The phase now
+    pub next_phase: Phase,
+    /// For each turn, in the order given, who submitted it and the result of
+    /// each of its orders
+    pub orders: Vec<(PlayerId, Vec<OrderResult>)>,
+    /// Each shot, collision, or warhead hit, with the stack it was aimed at,
+    /// in the order they happened
+    ///
+    /// Applying each hit's [`HitOutcome::draw`] in order with
+    /// [`crate::stack::Stack::apply_drawn_hit`] replays the damage done
+    pub hits: Vec<(EntityId, HitOutcome)>,
+    /// Each shot fired in the combat phase, in the order they were fired
+    pub combat_log: CombatLog,
+    /// Every hit roll made in the combat phase, in order
+    pub rolls: Vec<RollRecord>,
+    /// For each player, the stack created for each key they used with
+    /// [`crate::order::StackTransferTarget::New`]
+    pub new_stacks: HashMap<PlayerId, HashMap<u64, EntityId>>,
+    /// Stacks and warheads that came into being, in id order
+    pub created: Vec<EntityId>,
+    /// Stacks and warheads that were destroyed or otherwise removed, in id
+    /// order
+    pub destroyed: Vec<EntityId>,
+    /// How the game ended, if it's over
+    pub outcome: Option<GameOutcome>,
+}
+
+/// A shot fired in the combat phase, for replaying combat
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShotEvent {
+    /// The stack that fired
+    pub shooter: EntityId,
+    pub gun: EntityId,
+    pub target: EntityId,
+    /// How far apart the shooter and target were when the shot was fired
+    pub distance: u64,
+    /// The roll that decided whether the shot hit
+    pub roll: HitRoll,
+    pub hit: bool,
+    /// The component hit, if any
+    pub damaged_component: Option<EntityId>,
+}
+
+/// Every shot fired in a combat phase, in order
+pub type CombatLog = Vec<ShotEvent>;
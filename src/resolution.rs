@@ -0,0 +1,2396 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Phase resolution
+//!
+//! Applies all players' orders for a phase to the game state
+
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+use crate::{
+    order::{Burn, EconomicOrder, Launch, OrderError, PlayerTurn, RollRecord, Shoot},
+    position_partway,
+    report::{CombatLog, ShotEvent, TurnReport},
+    sorted_by_id,
+    stack::{HitOutcome, Stack},
+    vec2, EntityId, EntityIdGenerator, GameOutcome, GameState, Phase, PlayerId,
+};
+
+impl GameState {
+    /// Resolve the current phase from every player's submitted orders, then
+    /// advance to the next phase
+    ///
+    /// Orders are resolved in the order the turns were given, and within each
+    /// turn in the order they were submitted. A turn that fails
+    /// [`PlayerTurn::validate_phase`] has all of its orders rejected
+    ///
+    /// Once only one player is left, they win; if the last players are all
+    /// eliminated at once, the game is a draw. After that, no more orders are
+    /// accepted
+    ///
+    /// Returns a report of the result of each order and everything that
+    /// happened
+    pub fn resolve_turn(
+        &mut self,
+        turns: &[(PlayerId, PlayerTurn)],
+        id_generator: &mut EntityIdGenerator,
+        rng: &mut impl Rng,
+    ) -> TurnReport {
+        let mut report = TurnReport {
+            round: self.round,
+            phase: self.phase,
+            next_round: self.round,
+            next_phase: self.phase,
+            orders: Vec::new(),
+            hits: Vec::new(),
+            combat_log: Vec::new(),
+            rolls: Vec::new(),
+            new_stacks: HashMap::new(),
+            created: Vec::new(),
+            destroyed: Vec::new(),
+            outcome: self.outcome,
+        };
+        if self.is_over() {
+            report.orders = turns
+                .iter()
+                .map(|(player, turn)| {
+                    (
+                        *player,
+                        (0..turn.len())
+                            .map(|index| (index, Err(OrderError::GameOver)))
+                            .collect(),
+                    )
+                })
+                .collect();
+            return report;
+        }
+        let before = self.stack_and_warhead_ids();
+
+        let verdicts = turns
+            .iter()
+            .map(|(_, turn)| turn.validate_phase(self.phase, &self.rules))
+            .collect::<Vec<_>>();
+        let mut reports = turns
+            .iter()
+            .zip(&verdicts)
+            .map(|((_, turn), verdict)| match verdict {
+                Ok(()) => Vec::new(),
+                Err(error) => (0..turn.len()).map(|index| (index, Err(*error))).collect(),
+            })
+            .collect::<Vec<_>>();
+        let submitted = turns
+            .iter()
+            .zip(&verdicts)
+            .enumerate()
+            .filter(|(_, (_, verdict))| verdict.is_ok())
+            .flat_map(|(turn_index, ((_, turn), _))| {
+                (0..turn.len()).map(move |index| (turn_index, index))
+            })
+            .collect::<Vec<_>>();
+        let accepted = turns
+            .iter()
+            .zip(&verdicts)
+            .filter(|(_, verdict)| verdict.is_ok())
+            .map(|(turn, _)| turn)
+            .collect::<Vec<_>>();
+
+        let results = match self.phase {
+            Phase::Economic => {
+                let orders = flatten(&accepted, |turn| match turn {
+                    PlayerTurn::Economic(orders) => Some(orders),
+                    _ => None,
+                });
+                let results = self.resolve_economic(&orders, id_generator);
+                report.new_stacks = results.new_stacks;
+                results.orders
+            }
+            Phase::Ordnance => {
+                let launches = flatten(&accepted, |turn| match turn {
+                    PlayerTurn::Ordnance(launches) => Some(launches),
+                    _ => None,
+                });
+                self.resolve_ordnance(&launches, id_generator)
+            }
+            Phase::Combat => {
+                let shots = flatten(&accepted, |turn| match turn {
+                    PlayerTurn::Combat(shots) => Some(shots),
+                    _ => None,
+                });
+                let CombatResults {
+                    shots: results,
+                    rolls,
+                    log,
+                } = self.resolve_combat(&shots, rng);
+                report.combat_log = log;
+                report.rolls = rolls;
+                report.hits = shots
+                    .iter()
+                    .zip(&results)
+                    .filter_map(|((_, shot), result)| Some((shot.target, (*result).ok()?)))
+                    .collect();
+                results
+                    .into_iter()
+                    .map(|result| result.map(|_| ()))
+                    .collect()
+            }
+            Phase::Movement => {
+                let burns = flatten(&accepted, |turn| match turn {
+                    PlayerTurn::Movement(burns) => Some(burns),
+                    _ => None,
+                });
+                let results = self.resolve_movement(&burns, rng);
+                report.hits = results.collisions;
+                report.hits.extend(results.detonations);
+                results.burns
+            }
+        };
+        for ((turn_index, index), result) in submitted.into_iter().zip(results) {
+            reports[turn_index].push((index, result));
+        }
+
+        let survivors = self.surviving_players();
+        self.outcome = match survivors[..] {
+            [] => Some(GameOutcome::Draw),
+            [winner] => Some(GameOutcome::Win(winner)),
+            _ => None,
+        };
+
+        self.phase = match self.phase {
+            Phase::Economic => Phase::Ordnance,
+            Phase::Ordnance => Phase::Combat,
+            Phase::Combat => Phase::Movement,
+            Phase::Movement => {
+                self.round += 1;
+                Phase::Economic
+            }
+        };
+        let after = self.stack_and_warhead_ids();
+        report.created = after.difference(&before).copied().collect();
+        report.created.sort();
+        report.destroyed = before.difference(&after).copied().collect();
+        report.destroyed.sort();
+        report.next_round = self.round;
+        report.next_phase = self.phase;
+        report.outcome = self.outcome;
+        report.orders = turns
+            .iter()
+            .map(|(player, _)| *player)
+            .zip(reports)
+            .collect();
+        report
+    }
+
+    /// The ids of every stack and warhead
+    fn stack_and_warhead_ids(&self) -> HashSet<EntityId> {
+        self.stacks
+            .keys()
+            .chain(self.warheads.keys())
+            .copied()
+            .collect()
+    }
+
+    /// Resolve the economic phase
+    ///
+    /// First, every stack landed on a minor body mines it. Then orders are
+    /// resolved one at a time, in order. Each factory may act once, and each
+    /// habitat may make as many repairs as the rules allow. Afterwards, every
+    /// stack's cargo is normalized
+    ///
+    /// A player may only give orders to stacks they controlled at the start of
+    /// the phase, so capturing a stack with a habitat doesn't let the captor
+    /// use it until next phase. A stack that loses control partway through
+    /// the phase can't be given any more orders either
+    ///
+    /// Under [`crate::rules::RuleSet::transactional_economy`], each run of
+    /// consecutive orders from one player is a batch: the state is
+    /// snapshotted before the batch, and if any of its orders fail, the
+    /// snapshot is restored and the orders that succeeded are reported as
+    /// rolled back
+    ///
+    /// Returns the result of each order, in order, and the stacks created for
+    /// each player's [`crate::order::StackTransferTarget::New`] keys
+    pub fn resolve_economic(
+        &mut self,
+        orders: &[(PlayerId, EconomicOrder)],
+        id_generator: &mut EntityIdGenerator,
+    ) -> EconomicResults {
+        self.resolve_mining();
+        let controllers = self
+            .stacks
+            .iter()
+            .filter_map(|(id, stack)| Some((*id, stack.controller()?)))
+            .collect::<HashMap<_, _>>();
+        let mut acted = HashMap::new();
+        let mut new_stacks = HashMap::new();
+        let mut results = Vec::with_capacity(orders.len());
+        for batch in orders.chunk_by(|(first, _), (second, _)| first == second) {
+            let snapshot = self
+                .rules
+                .transactional_economy
+                .then(|| (self.snapshot(), acted.clone(), new_stacks.clone()));
+            let batch_results = batch
+                .iter()
+                .map(|(player, order)| {
+                    self.resolve_economic_order(
+                        *player,
+                        order,
+                        id_generator,
+                        &controllers,
+                        &mut acted,
+                        &mut new_stacks,
+                    )
+                })
+                .collect::<Vec<_>>();
+            match snapshot {
+                Some((state, old_acted, old_new_stacks))
+                    if batch_results.iter().any(Result::is_err) =>
+                {
+                    self.restore(state);
+                    acted = old_acted;
+                    new_stacks = old_new_stacks;
+                    results.extend(
+                        batch_results
+                            .into_iter()
+                            .map(|result| result.and(Err(OrderError::RolledBack))),
+                    );
+                }
+                _ => results.extend(batch_results),
+            }
+        }
+        for stack in self.stacks.values_mut() {
+            stack.normalize_cargo();
+        }
+        EconomicResults {
+            orders: results,
+            new_stacks,
+        }
+    }
+
+    /// Add each landed stack's mining yield to its cargo, as much as fits
+    ///
+    /// Yields are worked out before any are added, so every stack sees the
+    /// same state
+    fn resolve_mining(&mut self) {
+        let yields = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter_map(|(id, stack)| {
+                let body = self.body_at(stack.position)?;
+                Some((id, self.mining_yield(id, body.id)))
+            })
+            .collect::<Vec<_>>();
+        for (id, cargo) in yields {
+            let stack = self.stacks.get_mut(&id).unwrap();
+            let room = stack.cargo_capacity().saturating_sub(stack.cargo().total());
+            // capped to the room left, so it always fits
+            let _ = stack.deposit_cargo(cargo.capped(room));
+        }
+    }
+
+    /// Resolve one economic order, noting which factories and habitats have
+    /// acted and which new stacks have been created
+    fn resolve_economic_order(
+        &mut self,
+        player: PlayerId,
+        order: &EconomicOrder,
+        id_generator: &mut EntityIdGenerator,
+        controllers: &HashMap<EntityId, PlayerId>,
+        acted: &mut HashMap<EntityId, u64>,
+        new_stacks: &mut HashMap<PlayerId, HashMap<u64, EntityId>>,
+    ) -> Result<(), OrderError> {
+        if controllers.get(&order.stack()) != Some(&player) {
+            return Err(if self.stacks.contains_key(&order.stack()) {
+                OrderError::NotControlled
+            } else {
+                OrderError::NoSuchStack
+            });
+        }
+
+        // the component that acts, and how many times it may act
+        let actor = match order {
+            EconomicOrder::Production(order) => Some((order.factory, 1)),
+            EconomicOrder::FactoryRepair(order) => Some((order.factory, 1)),
+            EconomicOrder::HabitatRepair(order) => {
+                Some((order.habitat, self.rules.habitat_repairs_per_phase))
+            }
+            EconomicOrder::Conversion(order) => Some((order.factory, 1)),
+            EconomicOrder::CargoTransfer(_)
+            | EconomicOrder::FuelTransfer(_)
+            | EconomicOrder::Reload(_)
+            | EconomicOrder::StackTransfer(_)
+            | EconomicOrder::Jettison(_)
+            | EconomicOrder::Unload(_) => None,
+        };
+        if actor
+            .is_some_and(|(actor, limit)| acted.get(&actor).is_some_and(|count| *count >= limit))
+        {
+            return Err(OrderError::AlreadyActed);
+        }
+
+        match order {
+            EconomicOrder::Production(order) => order.resolve(player, self, id_generator),
+            EconomicOrder::CargoTransfer(order) => order.resolve(player, self),
+            EconomicOrder::FuelTransfer(order) => order.resolve(player, self),
+            EconomicOrder::Reload(order) => order.resolve(player, self),
+            EconomicOrder::FactoryRepair(order) => order.resolve(player, self),
+            EconomicOrder::HabitatRepair(order) => order.resolve(player, self),
+            EconomicOrder::StackTransfer(order) => order.resolve(
+                player,
+                self,
+                id_generator,
+                new_stacks.entry(player).or_default(),
+            ),
+            EconomicOrder::Conversion(order) => order.resolve(player, self),
+            EconomicOrder::Jettison(order) => order.resolve(player, self),
+            EconomicOrder::Unload(order) => order.resolve(player, self),
+        }?;
+        if let Some((actor, _)) = actor {
+            *acted.entry(actor).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    /// Resolve the ordnance phase
+    ///
+    /// Returns the result of each launch, in order
+    pub fn resolve_ordnance(
+        &mut self,
+        launches: &[(PlayerId, Launch)],
+        id_generator: &mut EntityIdGenerator,
+    ) -> Vec<Result<(), OrderError>> {
+        launches
+            .iter()
+            .map(|(player, launch)| launch.resolve(*player, self, id_generator))
+            .collect()
+    }
+
+    /// Resolve the combat phase
+    ///
+    /// Shots are resolved one at a time, in order; each gun may fire once.
+    /// Stacks are destroyed as soon as they lose their last component, so later
+    /// shots at them do nothing. Guns fired past a stack's limit for the phase
+    /// overheat and are less accurate
+    ///
+    /// Returns the result of each shot, in order, with what it did to its
+    /// target, a record of every hit roll, and a log of every shot fired
+    pub fn resolve_combat(
+        &mut self,
+        shots: &[(PlayerId, Shoot)],
+        rng: &mut impl Rng,
+    ) -> CombatResults {
+        let mut fired = HashSet::new();
+        let mut guns_fired = HashMap::new();
+        let mut rolls = Vec::new();
+        let mut log = Vec::new();
+        let results = shots
+            .iter()
+            .enumerate()
+            .map(|(index, (player, shot))| {
+                if fired.contains(&shot.gun) {
+                    return Err(OrderError::AlreadyActed);
+                }
+                let stack_fired = guns_fired.entry(shot.stack).or_insert(0);
+                let distance = self
+                    .stacks
+                    .get(&shot.stack)
+                    .zip(self.stacks.get(&shot.target))
+                    .map(|(shooter, target)| shooter.position.distance(target.position));
+                let (outcome, roll) = shot.resolve(*player, self, *stack_fired, rng)?;
+                log.push(ShotEvent {
+                    shooter: shot.stack,
+                    gun: shot.gun,
+                    target: shot.target,
+                    distance: distance.unwrap(),
+                    roll,
+                    hit: outcome.hit,
+                    damaged_component: outcome.component,
+                });
+                rolls.push(RollRecord {
+                    round: self.round,
+                    phase: self.phase,
+                    order: index,
+                    roll,
+                    hit: roll.hit(),
+                });
+                fired.insert(shot.gun);
+                *stack_fired += 1;
+                Ok(outcome)
+            })
+            .collect();
+        CombatResults {
+            shots: results,
+            rolls,
+            log,
+        }
+    }
+
+    /// Resolve the movement phase
+    ///
+    /// In order:
+    /// 1. burns change the velocities of stacks
+    /// 2. gravity changes the velocities of stacks and warheads
+    /// 3. stacks and warheads move by their velocities
+    /// 4. anything ending up in a major body's hex or off the map is
+    ///    destroyed, and stacks sharing a hex with different velocities
+    ///    collide
+    /// 5. warheads that passed through the hex of a stack not controlled by
+    ///    the warhead's owner detonate there
+    /// 6. contested stacks and derelicts get captors
+    ///
+    /// Each step is its own stage - [`GameState::apply_burns`],
+    /// [`GameState::apply_gravity`], [`GameState::integrate`],
+    /// [`GameState::resolve_collisions`], [`GameState::resolve_warheads`], and
+    /// [`GameState::resolve_control`] - which can be called on its own to
+    /// inspect the state partway through
+    ///
+    /// Returns the result of each burn, in order, and each hit from a
+    /// collision or warhead detonation
+    pub fn resolve_movement(
+        &mut self,
+        burns: &[(PlayerId, Burn)],
+        rng: &mut impl Rng,
+    ) -> MovementResults {
+        let results = self.apply_burns(burns);
+        self.apply_gravity();
+        self.integrate();
+        let collisions = self.resolve_collisions(rng);
+        let detonations = self.resolve_warheads(rng);
+        self.resolve_control();
+        MovementResults {
+            burns: results,
+            collisions,
+            detonations,
+        }
+    }
+
+    /// Change the velocities of stacks by their burns
+    ///
+    /// Multiple burns ordered by a player for the same stack are summed into
+    /// a single burn, which must be within the stack's capabilities as a
+    /// whole; if it isn't, none of them happen
+    ///
+    /// Returns the result of each burn, in order
+    pub fn apply_burns(&mut self, burns: &[(PlayerId, Burn)]) -> Vec<Result<(), OrderError>> {
+        // a sum that overflows is kept as `None`, and fails
+        let mut combined: Vec<(PlayerId, EntityId, Option<Burn>, Vec<usize>)> = Vec::new();
+        for (index, (player, burn)) in burns.iter().enumerate() {
+            match combined.iter_mut().find(|(other_player, stack, _, _)| {
+                *other_player == *player && *stack == burn.stack
+            }) {
+                Some((_, _, total, indices)) => {
+                    *total = total.take().and_then(|total| {
+                        Some(Burn {
+                            delta: total.delta.checked_add(burn.delta)?,
+                            ..total
+                        })
+                    });
+                    indices.push(index);
+                }
+                None => combined.push((*player, burn.stack, Some(burn.clone()), vec![index])),
+            }
+        }
+        let mut results = vec![Ok(()); burns.len()];
+        for (player, _, burn, indices) in combined {
+            let result = burn.map_or(Err(OrderError::Overflow), |burn| burn.resolve(player, self));
+            for index in indices {
+                results[index] = result;
+            }
+        }
+        results
+    }
+
+    /// Change the velocities of stacks and warheads by the gravity where they
+    /// are
+    pub fn apply_gravity(&mut self) {
+        let stack_gravity = sorted_by_id(&self.stacks)
+            .into_iter()
+            .map(|(id, stack)| (id, self.gravity_at(stack.position)))
+            .collect::<Vec<_>>();
+        for (id, gravity) in stack_gravity {
+            self.stacks.get_mut(&id).unwrap().velocity += gravity;
+        }
+
+        let warhead_gravity = sorted_by_id(&self.warheads)
+            .into_iter()
+            .map(|(id, warhead)| (id, self.gravity_at(warhead.position)))
+            .collect::<Vec<_>>();
+        for (id, gravity) in warhead_gravity {
+            self.warheads.get_mut(&id).unwrap().velocity += gravity;
+        }
+    }
+
+    /// Move stacks and warheads by their velocities
+    pub fn integrate(&mut self) {
+        for stack in self.stacks.values_mut() {
+            stack.position += stack.velocity;
+        }
+        for warhead in self.warheads.values_mut() {
+            warhead.position += warhead.velocity;
+        }
+    }
+
+    /// Destroy anything in a major body's hex or off the map, then damage
+    /// colliding stacks
+    ///
+    /// Each pair of stacks in the same hex takes one hit each per hex/turn of
+    /// relative velocity; rendezvoused stacks don't collide
+    ///
+    /// Returns each hit from a collision, with the stack hit
+    pub fn resolve_collisions(&mut self, rng: &mut impl Rng) -> Vec<(EntityId, HitOutcome)> {
+        let body_positions = self
+            .major_bodies
+            .values()
+            .map(|body| body.position)
+            .collect::<Vec<_>>();
+        let lost = |position: &vec2::Position| {
+            body_positions.contains(position) || !self.in_bounds(*position)
+        };
+        let stacks = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, stack)| lost(&stack.position))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        let warheads = sorted_by_id(&self.warheads)
+            .into_iter()
+            .filter(|(_, warhead)| lost(&warhead.position))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in stacks {
+            self.stacks.remove(&id);
+        }
+        for id in warheads {
+            self.warheads.remove(&id);
+        }
+
+        let stack_ids = sorted_by_id(&self.stacks)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        let index = self.spatial_index();
+        let mut hits = HashMap::<EntityId, u64>::new();
+        for first in &stack_ids {
+            let first = &self.stacks[first];
+            for second in index.stacks_at(first.position) {
+                if *second <= first.id {
+                    continue;
+                }
+                let second = &self.stacks[second];
+                let speed = (first.velocity - second.velocity).norm();
+                *hits.entry(first.id).or_default() += speed;
+                *hits.entry(second.id).or_default() += speed;
+            }
+        }
+        let mut outcomes = Vec::new();
+        for id in stack_ids {
+            let stack = self.stacks.get_mut(&id).unwrap();
+            for _ in 0..hits.get(&id).copied().unwrap_or(0) {
+                outcomes.push((id, stack.apply_hit(rng)));
+            }
+        }
+        self.remove_destroyed_stacks();
+        outcomes
+    }
+
+    /// Detonate each warhead whose path this turn crossed an enemy stack's,
+    /// where it met the first such stack by id
+    ///
+    /// Each enemy stack is hit according to how far it was from the
+    /// detonation at that point in the move, not where it ended up
+    pub fn resolve_warheads(&mut self, rng: &mut impl Rng) -> Vec<(EntityId, HitOutcome)> {
+        let mut outcomes = Vec::new();
+        let warhead_ids = sorted_by_id(&self.warheads)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for warhead_id in warhead_ids {
+            let owner = self.warheads[&warhead_id].owner;
+            let trigger = sorted_by_id(&self.stacks)
+                .into_iter()
+                .filter(|(_, stack)| stack.controller() != Some(owner))
+                .find_map(|(id, _)| match self.closest_point(warhead_id, id) {
+                    Some((0, position, moment)) => Some((position, moment)),
+                    _ => None,
+                });
+            let Some((position, moment)) = trigger else {
+                continue;
+            };
+            let warhead = self.warheads.get_mut(&warhead_id).unwrap();
+            warhead.position = position;
+            let warhead = &self.warheads[&warhead_id];
+
+            let targets = sorted_by_id(&self.stacks)
+                .into_iter()
+                .map(|(_, stack)| stack)
+                .filter(|stack| stack.controller() != Some(warhead.owner))
+                .map(|stack| {
+                    let path = (stack.position - stack.velocity).line_to(stack.position);
+                    let distance = position_partway(&path, moment).distance(warhead.position);
+                    (stack.id, distance)
+                })
+                .filter(|(_, distance)| *distance <= warhead.blast_radius)
+                .map(|(id, distance)| (id, warhead.hits_at(distance)))
+                .collect::<Vec<(EntityId, u64)>>();
+            for (target, hits) in targets {
+                let stack = self.stacks.get_mut(&target).unwrap();
+                for _ in 0..hits {
+                    outcomes.push((target, stack.apply_hit(rng)));
+                }
+            }
+            self.warheads.remove(&warhead_id);
+        }
+        self.remove_destroyed_stacks();
+        outcomes
+    }
+
+    /// Decide who controls the stacks whose habitats don't settle it
+    ///
+    /// A contested stack goes to the player with the lowest id among its
+    /// habitats' owners. A derelict rendezvoused with stacks whose habitats all
+    /// belong to one player goes to the lowest id among those players; one
+    /// rendezvoused with nobody keeps its captor, if it has one. Either way,
+    /// the captor becomes the stack's owner
+    ///
+    /// Stacks whose habitats all belong to one player are theirs, and have no
+    /// captor
+    pub fn resolve_control(&mut self) {
+        let claimed = |stack: &Stack| !stack.is_derelict() && !stack.is_contested();
+        let index = self.spatial_index();
+        let captors = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter_map(|(id, stack)| {
+                if stack.is_contested() {
+                    Some((
+                        id,
+                        stack.habitats().values().map(|habitat| habitat.owner).min(),
+                    ))
+                } else if stack.is_derelict() {
+                    let captor = index
+                        .stacks_at(stack.position)
+                        .iter()
+                        .map(|id| &self.stacks[id])
+                        .filter(|other| claimed(other) && other.rendezvoused_with(stack))
+                        .filter_map(Stack::controller)
+                        .min()?;
+                    Some((id, Some(captor)))
+                } else {
+                    Some((id, None))
+                }
+            })
+            .collect::<Vec<_>>();
+        for (id, captor) in captors {
+            let stack = self.stacks.get_mut(&id).unwrap();
+            stack.captor = captor;
+            if let Some(captor) = captor {
+                stack.owner = captor;
+            }
+        }
+    }
+
+    /// Remove stacks with no components left
+    fn remove_destroyed_stacks(&mut self) {
+        self.stacks
+            .retain(|_, stack| !stack.component_ids().is_empty());
+    }
+}
+
+/// What happened in the economic phase
+#[derive(Debug, Clone)]
+pub struct EconomicResults {
+    /// The result of each order, in order
+    pub orders: Vec<Result<(), OrderError>>,
+    /// For each player, the stack created for each key they used with
+    /// [`crate::order::StackTransferTarget::New`], so clients can match up
+    /// the stacks they predicted
+    pub new_stacks: HashMap<PlayerId, HashMap<u64, EntityId>>,
+}
+
+/// What happened in the combat phase
+#[derive(Debug, Clone)]
+pub struct CombatResults {
+    /// The result of each shot, in order
+    pub shots: Vec<Result<HitOutcome, OrderError>>,
+    /// Every hit roll made, in order
+    pub rolls: Vec<RollRecord>,
+    /// Every shot fired, in order
+    pub log: CombatLog,
+}
+
+/// What happened in the movement phase
+#[derive(Debug, Clone)]
+pub struct MovementResults {
+    /// The result of each burn, in order
+    pub burns: Vec<Result<(), OrderError>>,
+    /// Each hit from a collision, with the stack hit
+    pub collisions: Vec<(EntityId, HitOutcome)>,
+    /// Each hit from a warhead detonation, with the stack hit
+    pub detonations: Vec<(EntityId, HitOutcome)>,
+}
+
+/// Collect the orders from the turns for one phase, with the player who
+/// submitted each
+fn flatten<T: Clone>(
+    turns: &[&(PlayerId, PlayerTurn)],
+    orders: impl Fn(&PlayerTurn) -> Option<&Vec<T>>,
+) -> Vec<(PlayerId, T)> {
+    turns
+        .iter()
+        .filter_map(|(player, turn)| Some((*player, orders(turn)?)))
+        .flat_map(|(player, orders)| orders.iter().map(move |order| (player, order.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::{
+        astronomical::MajorBody,
+        order::{
+            Conversion, ConversionKind, FactoryRepair, FuelTransfer, HabitatRepair, PhaseOrder,
+            Production, Reload, StackComponent, StackTransfer, StackTransferTarget, TransferAmount,
+        },
+        stack::{
+            ArmourPlate, CargoHold, CargoList, Engine, Factory, FuelTank, Gun, Habitat, Stack,
+            Warhead, WarheadMount,
+        },
+        vec2::{Displacement, Position},
+        EntityIdGenerator,
+    };
+
+    fn ship(
+        id_generator: &mut EntityIdGenerator,
+        position: Position,
+        velocity: Displacement,
+        player: PlayerId,
+    ) -> Stack {
+        let mut stack = Stack::new("Ship", id_generator, position, velocity, player);
+        let habitat = Habitat::new(id_generator, player);
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let engine = Engine::new(id_generator);
+        stack.engines_mut().insert(engine.id, engine);
+        let mut tank = FuelTank::new(id_generator);
+        tank.fuel = 10;
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+        stack
+    }
+
+    #[test]
+    fn test_turn_reports_order_index() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let mut state = GameState::empty();
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let factory = Factory::new(&mut id_generator);
+        let factory_id = factory.id;
+        stack.factories_mut().insert(factory.id, factory);
+        let hold = CargoHold::new(&mut id_generator);
+        stack.cargo_holds_mut().insert(hold.id, hold);
+        stack.deposit_cargo(CargoList::new(0, 0, 10, 0)).unwrap();
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let turn = PlayerTurn::Economic(vec![
+            EconomicOrder::Production(Production {
+                stack: stack_id,
+                factory: factory_id,
+                component: StackComponent::Gun,
+            }),
+            EconomicOrder::Conversion(Conversion {
+                stack: stack_id,
+                factory: factory_id,
+                kind: ConversionKind::MaterialsToWarheads,
+                amount: 1,
+            }),
+            EconomicOrder::FuelTransfer(FuelTransfer {
+                stack: stack_id,
+                destination: stack_id,
+                amount: TransferAmount::Exactly(1),
+            }),
+        ]);
+        let late = PlayerTurn::Movement(vec![Burn {
+            stack: stack_id,
+            delta: Displacement::new(1, 0),
+        }]);
+        let report = state.resolve_turn(
+            &[(0.into(), turn), (0.into(), late)],
+            &mut id_generator,
+            &mut rng,
+        );
+
+        assert_eq!(
+            report.orders[0].1,
+            vec![(0, Ok(())), (1, Err(OrderError::AlreadyActed)), (2, Ok(()))]
+        );
+        assert_eq!(report.orders[1].1, vec![(0, Err(OrderError::WrongPhase))]);
+        assert_eq!(state.stacks[&stack_id].guns().len(), 1);
+        assert_eq!(state.stacks[&stack_id].cargo(), CargoList::new(0, 0, 5, 0));
+        assert_eq!(state.phase, Phase::Ordnance);
+    }
+
+    #[test]
+    fn test_game_outcome() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let mut state = GameState::empty();
+        state.phase = Phase::Movement;
+        for (player, position, velocity) in [
+            (0, Position::new(0, 0), Displacement::new(3, 0)),
+            (1, Position::new(6, 0), Displacement::new(-3, 0)),
+        ] {
+            let stack = ship(&mut id_generator, position, velocity, player.into());
+            state.stacks.insert(stack.id, stack);
+        }
+        let mut sole_survivor = state.clone();
+
+        state.resolve_turn(&[], &mut id_generator, &mut rng);
+        assert!(state.stacks.is_empty());
+        assert_eq!(state.outcome, Some(GameOutcome::Draw));
+        assert_eq!(
+            state
+                .resolve_turn(
+                    &[
+                        (0.into(), PlayerTurn::Economic(Vec::new())),
+                        (
+                            1.into(),
+                            PlayerTurn::Economic(vec![EconomicOrder::Reload(Reload {
+                                stack: 0.into(),
+                                mount: 0.into(),
+                            })])
+                        )
+                    ],
+                    &mut id_generator,
+                    &mut rng
+                )
+                .orders[1]
+                .1,
+            vec![(0, Err(OrderError::GameOver))]
+        );
+
+        sole_survivor
+            .stacks
+            .retain(|_, stack| stack.owner == 0.into());
+        sole_survivor.resolve_turn(&[], &mut id_generator, &mut rng);
+        assert!(sole_survivor.is_over());
+        assert_eq!(sole_survivor.outcome, Some(GameOutcome::Win(0.into())));
+    }
+
+    #[test]
+    fn test_shoot_destroyed_target() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let mut guns = Vec::new();
+        for _ in 0..2 {
+            let gun = Gun::new(&mut id_generator);
+            guns.push(gun.id);
+            shooter.guns_mut().insert(gun.id, gun);
+        }
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let mut target = Stack::new(
+            "Wreck",
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        let mut tank = FuelTank::new(&mut id_generator);
+        tank.damaged = true;
+        let tank_id = tank.id;
+        target.fuel_tanks_mut().insert(tank.id, tank);
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+
+        // guaranteed to hit at range 0, and destroys the only component
+        let results = state
+            .resolve_combat(
+                &[
+                    (
+                        0.into(),
+                        Shoot {
+                            stack: shooter_id,
+                            gun: guns[0],
+                            target: target_id,
+                        },
+                    ),
+                    (
+                        0.into(),
+                        Shoot {
+                            stack: shooter_id,
+                            gun: guns[1],
+                            target: target_id,
+                        },
+                    ),
+                ],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .shots;
+        assert_eq!(
+            results,
+            vec![
+                Ok(HitOutcome {
+                    hit: true,
+                    component: Some(tank_id),
+                    destroyed: true,
+                    draw: Some(0),
+                }),
+                Err(OrderError::TargetGone)
+            ]
+        );
+        assert!(!state.stacks.contains_key(&target_id));
+    }
+
+    #[test]
+    fn test_combat_log() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let mut guns = Vec::new();
+        for _ in 0..3 {
+            let gun = Gun::new(&mut id_generator);
+            guns.push(gun.id);
+            shooter.guns_mut().insert(gun.id, gun);
+        }
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let mut targets = Vec::new();
+        for position in [Position::new(0, 0), Position::new(3, 0)] {
+            let mut target = Stack::new(
+                "Bunker",
+                &mut id_generator,
+                position,
+                Displacement::new(0, 0),
+                1.into(),
+            );
+            for _ in 0..5 {
+                let plate = ArmourPlate::new(&mut id_generator);
+                target.armour_plates_mut().insert(plate.id, plate);
+            }
+            targets.push(target.id);
+            state.stacks.insert(target.id, target);
+        }
+
+        // point blank always hits; at the edge of effective range, this roll
+        // misses
+        let shots = [targets[0], targets[1], targets[0]]
+            .into_iter()
+            .zip(&guns)
+            .map(|(target, gun)| {
+                (
+                    0.into(),
+                    Shoot {
+                        stack: shooter_id,
+                        gun: *gun,
+                        target,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let results = state.resolve_combat(&shots, &mut ChaCha20Rng::seed_from_u64(0));
+        assert_eq!(results.log.len(), 3);
+        for (((event, (_, shot)), distance), hit) in results
+            .log
+            .iter()
+            .zip(&shots)
+            .zip([0, Gun::EFFECTIVE_RANGE, 0])
+            .zip([true, false, true])
+        {
+            assert_eq!(event.shooter, shooter_id);
+            assert_eq!(event.gun, shot.gun);
+            assert_eq!(event.target, shot.target);
+            assert_eq!(event.distance, distance);
+            assert_eq!(event.hit, hit);
+            assert_eq!(event.damaged_component.is_some(), hit);
+        }
+    }
+
+    #[test]
+    fn test_overheated_gun_less_accurate() {
+        let shots = Gun::GUNS_PER_TURN + 1;
+        let mut id_generator = EntityIdGenerator::new();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let mut guns = Vec::new();
+        for _ in 0..shots {
+            let gun = Gun::new(&mut id_generator);
+            guns.push(gun.id);
+            shooter.guns_mut().insert(gun.id, gun);
+        }
+        let mut target = Stack::new(
+            "Target",
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        for _ in 0..shots {
+            let plate = ArmourPlate::new(&mut id_generator);
+            target.armour_plates_mut().insert(plate.id, plate);
+        }
+        let orders = guns
+            .iter()
+            .map(|gun| {
+                (
+                    0.into(),
+                    Shoot {
+                        stack: shooter.id,
+                        gun: *gun,
+                        target: target.id,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // at range 0, the guns within the limit always hit, and the last gun
+        // only hits some of the time
+        let trials = 1000;
+        let mut last_hits = 0;
+        for seed in 0..trials {
+            let mut state = GameState::empty();
+            state.stacks.insert(shooter.id, shooter.clone());
+            state.stacks.insert(target.id, target.clone());
+            state.resolve_combat(&orders, &mut ChaCha20Rng::seed_from_u64(seed));
+            let damaged = state.stacks[&target.id]
+                .armour_plates()
+                .values()
+                .filter(|plate| plate.damaged)
+                .count() as u64;
+            assert!(damaged >= Gun::GUNS_PER_TURN);
+            last_hits += damaged - Gun::GUNS_PER_TURN;
+        }
+        let expected = trials as f64 * Gun::OVERHEAT_PENALTY;
+        assert!((last_hits as f64 - expected).abs() < expected * 0.15);
+    }
+
+    #[test]
+    fn test_burn_then_gravity_then_integrate() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let body = MajorBody::new(
+            "Planet",
+            &mut id_generator,
+            Position::new(0, 0),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(body.id, body);
+        let stack = ship(
+            &mut id_generator,
+            Position::new(1, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        // burning away from the planet exactly cancels its gravity
+        let results = state
+            .resolve_movement(
+                &[(
+                    0.into(),
+                    Burn {
+                        stack: id,
+                        delta: Displacement::new(1, 0),
+                    },
+                )],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .burns;
+        assert_eq!(results, vec![Ok(())]);
+        let stack = &state.stacks[&id];
+        assert_eq!(stack.velocity, Displacement::new(0, 0));
+        assert_eq!(stack.position, Position::new(1, 0));
+        assert_eq!(stack.fuel(), 9);
+    }
+
+    #[test]
+    fn test_gravity_varies_by_body() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::new(&[0; 32], 2, &mut id_generator);
+        let outwards = Displacement::new(0, 1);
+        // a stack beside each body, coasting away from it
+        let mut beside = |name: &str| {
+            let body = state
+                .major_bodies
+                .values()
+                .find(|body| body.name == name)
+                .unwrap();
+            let stack = ship(
+                &mut id_generator,
+                body.position + outwards,
+                outwards * 4,
+                0.into(),
+            );
+            let id = stack.id;
+            state.stacks.insert(id, stack);
+            id
+        };
+        let (sol, mercury) = (beside("Sol"), beside("Mercury"));
+
+        // Sol pulls three times as hard as Mercury
+        state.resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0));
+        assert_eq!(state.stacks[&sol].velocity, outwards);
+        assert_eq!(state.stacks[&mercury].velocity, outwards * 3);
+    }
+
+    #[test]
+    fn test_burns_combine() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let stack = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        // one engine can change the velocity by one hex; these sum to one hex
+        let results = state
+            .resolve_movement(
+                &[
+                    (
+                        0.into(),
+                        Burn {
+                            stack: id,
+                            delta: Displacement::new(1, 0),
+                        },
+                    ),
+                    (
+                        0.into(),
+                        Burn {
+                            stack: id,
+                            delta: Displacement::new(-1, 1),
+                        },
+                    ),
+                ],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .burns;
+        assert_eq!(results, vec![Ok(()), Ok(())]);
+        assert_eq!(state.stacks[&id].velocity, Displacement::new(0, 1));
+        assert_eq!(state.stacks[&id].fuel(), 9);
+
+        // these sum to two hexes, which is too much, even though each is fine
+        let results = state
+            .resolve_movement(
+                &[
+                    (
+                        0.into(),
+                        Burn {
+                            stack: id,
+                            delta: Displacement::new(1, 0),
+                        },
+                    ),
+                    (
+                        0.into(),
+                        Burn {
+                            stack: id,
+                            delta: Displacement::new(1, 0),
+                        },
+                    ),
+                ],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .burns;
+        assert_eq!(
+            results,
+            vec![
+                Err(OrderError::InsufficientThrust),
+                Err(OrderError::InsufficientThrust)
+            ]
+        );
+        assert_eq!(state.stacks[&id].velocity, Displacement::new(0, 1));
+        assert_eq!(state.stacks[&id].fuel(), 9);
+    }
+
+    #[test]
+    fn test_warhead_blast_radius() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let centre = Position::new(5, 5);
+        let mut targets = Vec::new();
+        for position in centre.hexes_within(2) {
+            let mut stack = Stack::new(
+                "Target",
+                &mut id_generator,
+                position,
+                Displacement::new(0, 0),
+                1.into(),
+            );
+            for _ in 0..10 {
+                let plate = ArmourPlate::new(&mut id_generator);
+                stack.armour_plates_mut().insert(plate.id, plate);
+            }
+            targets.push((stack.id, position.distance(centre)));
+            state.stacks.insert(stack.id, stack);
+        }
+        let mut warhead =
+            Warhead::new(&mut id_generator, centre, Displacement::new(0, 0), 0.into());
+        warhead.blast_radius = 1;
+        state.warheads.insert(warhead.id, warhead);
+
+        let detonations = state
+            .resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0))
+            .detonations;
+        assert!(state.warheads.is_empty());
+        for (id, distance) in targets {
+            // each hit damages one armour plate
+            let damaged = state.stacks[&id]
+                .armour_plates()
+                .values()
+                .filter(|plate| plate.damaged)
+                .count();
+            let expected = match distance {
+                0 => 5,
+                1 => 2,
+                _ => 0,
+            };
+            assert_eq!(damaged, expected);
+            assert_eq!(
+                detonations
+                    .iter()
+                    .filter(|(target, outcome)| *target == id && outcome.hit)
+                    .count(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_burn_cost_matches_resolution() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(5, 5),
+            Displacement::ZERO,
+            0.into(),
+        );
+        for _ in 0..2 {
+            let engine = Engine::new(&mut id_generator);
+            stack.engines_mut().insert(engine.id, engine);
+        }
+        let delta = Displacement::new(2, 0);
+        let cost = stack.burn_cost(delta).unwrap();
+        assert_eq!(stack.burn_cost(Displacement::new(9, 0)), None);
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        state.resolve_movement(
+            &[(
+                0.into(),
+                Burn {
+                    stack: stack_id,
+                    delta,
+                },
+            )],
+            &mut ChaCha20Rng::seed_from_u64(0),
+        );
+        assert_eq!(state.stacks[&stack_id].fuel(), 10 - cost);
+    }
+
+    #[test]
+    fn test_zero_burn_costs_nothing() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(5, 5),
+            Displacement::ZERO,
+            0.into(),
+        );
+        stack.engines_mut().clear();
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let results = state
+            .resolve_movement(
+                &[(
+                    0.into(),
+                    Burn {
+                        stack: stack_id,
+                        delta: Displacement::ZERO,
+                    },
+                )],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .burns;
+        assert_eq!(results, vec![Ok(())]);
+        assert_eq!(state.stacks[&stack_id].fuel(), 10);
+    }
+
+    #[test]
+    fn test_leaving_map() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.map_radius = Some(10);
+        let mut ids = Vec::new();
+        for position in [Position::new(8, 0), Position::new(-8, 0)] {
+            let stack = ship(&mut id_generator, position, Displacement::ZERO, 0.into());
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let burns = [
+            (
+                0.into(),
+                Burn {
+                    stack: ids[0],
+                    delta: Displacement::new(1, 0),
+                },
+            ),
+            (
+                0.into(),
+                Burn {
+                    stack: ids[1],
+                    delta: Displacement::new(-1, 0),
+                },
+            ),
+        ];
+        state.resolve_movement(&burns, &mut ChaCha20Rng::seed_from_u64(0));
+        state.resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0));
+        assert_eq!(state.stacks[&ids[0]].position, Position::new(10, 0));
+        assert_eq!(state.stacks[&ids[1]].position, Position::new(-10, 0));
+
+        state.resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0));
+        assert!(state.stacks.is_empty());
+    }
+
+    #[test]
+    fn test_movement_stages() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let planet = MajorBody::new(
+            "Venus",
+            &mut id_generator,
+            Position::new(0, 3),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(planet.id, planet);
+        let mut burner = None;
+        for (position, velocity, player) in [
+            (Position::new(0, 0), Displacement::new(1, 0), 0),
+            (Position::new(2, 0), Displacement::new(-1, 0), 1),
+            (Position::new(1, 2), Displacement::new(0, 0), 1),
+            (Position::new(5, 5), Displacement::new(0, 1), 0),
+        ] {
+            let stack = ship(&mut id_generator, position, velocity, player.into());
+            burner.get_or_insert(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(3, 0),
+            Displacement::new(-2, 0),
+            0.into(),
+        );
+        state.warheads.insert(warhead.id, warhead);
+        let burns = [(
+            PlayerId::from(0),
+            Burn {
+                stack: burner.unwrap(),
+                delta: Displacement::new(0, 1),
+            },
+        )];
+
+        let mut whole = state.clone();
+        let results = whole.resolve_movement(&burns, &mut ChaCha20Rng::seed_from_u64(0));
+
+        let mut staged = state;
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let burn_results = staged.apply_burns(&burns);
+        staged.apply_gravity();
+        staged.integrate();
+        staged.resolve_collisions(&mut rng);
+        let detonations = staged.resolve_warheads(&mut rng);
+
+        assert_eq!(burn_results, results.burns);
+        assert_eq!(detonations, results.detonations);
+        assert!(!detonations.is_empty());
+        assert_eq!(staged.checksum(), whole.checksum());
+    }
+
+    #[test]
+    fn test_collisions() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut ids = Vec::new();
+        for (position, velocity) in [
+            (Position::new(0, 0), Displacement::new(1, 0)),
+            (Position::new(2, 0), Displacement::new(-1, 0)),
+            (Position::new(5, 5), Displacement::new(1, 1)),
+            (Position::new(5, 5), Displacement::new(1, 1)),
+        ] {
+            let mut stack = ship(&mut id_generator, position, velocity, 0.into());
+            for _ in 0..3 {
+                let plate = ArmourPlate::new(&mut id_generator);
+                stack.armour_plates_mut().insert(plate.id, plate);
+            }
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+
+        state.resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0));
+
+        let damaged = |state: &GameState, id: EntityId| {
+            state.stacks[&id]
+                .component_ids()
+                .into_iter()
+                .filter(|component| {
+                    state.stacks[&id]
+                        .find_component(*component)
+                        .unwrap()
+                        .damaged()
+                })
+                .count()
+        };
+        assert_eq!(state.stacks[&ids[0]].position, Position::new(1, 0));
+        assert_eq!(damaged(&state, ids[0]), 2);
+        assert_eq!(damaged(&state, ids[1]), 2);
+        assert_eq!(damaged(&state, ids[2]), 0);
+        assert_eq!(damaged(&state, ids[3]), 0);
+    }
+
+    #[test]
+    fn test_movement_sequence() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let body = MajorBody::new(
+            "Planet",
+            &mut id_generator,
+            Position::new(0, 0),
+            0.5,
+            "#ffffff",
+        );
+        state.major_bodies.insert(body.id, body);
+
+        // falls into the planet
+        let doomed = ship(
+            &mut id_generator,
+            Position::new(0, 2),
+            Displacement::new(0, -2),
+            0.into(),
+        );
+        let doomed_id = doomed.id;
+        state.stacks.insert(doomed.id, doomed);
+
+        // coasts through deep space into a warhead
+        let target = ship(
+            &mut id_generator,
+            Position::new(10, 0),
+            Displacement::new(1, 0),
+            1.into(),
+        );
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+        let warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(11, 2),
+            Displacement::new(0, -2),
+            0.into(),
+        );
+        let warhead_id = warhead.id;
+        state.warheads.insert(warhead.id, warhead);
+
+        // a burn ordered by someone who doesn't control the stack is rejected
+        let results = state
+            .resolve_movement(
+                &[(
+                    0.into(),
+                    Burn {
+                        stack: target_id,
+                        delta: Displacement::new(0, 1),
+                    },
+                )],
+                &mut ChaCha20Rng::seed_from_u64(0),
+            )
+            .burns;
+        assert_eq!(results, vec![Err(OrderError::NotControlled)]);
+
+        assert!(!state.stacks.contains_key(&doomed_id));
+        assert!(!state.warheads.contains_key(&warhead_id));
+        // with this seed, the warhead leaves only a damaged fuel tank
+        let target = &state.stacks[&target_id];
+        let (&tank, fuel_tank) = target.fuel_tanks().iter().next().unwrap();
+        assert_eq!(target.position, Position::new(11, 0));
+        assert_eq!(target.component_ids(), vec![tank]);
+        assert!(fuel_tank.damaged);
+    }
+
+    #[test]
+    fn test_legal_orders_resolve() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        let mut state = GameState::empty();
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(1, 0),
+            0.into(),
+        );
+        let factory = Factory::new(&mut id_generator);
+        stack.factories_mut().insert(factory.id, factory);
+        let hold = CargoHold::new(&mut id_generator);
+        stack.cargo_holds_mut().insert(hold.id, hold);
+        let gun = Gun::new(&mut id_generator);
+        stack.guns_mut().insert(gun.id, gun);
+        let mount = WarheadMount::new(&mut id_generator);
+        stack.launch_clamps_mut().insert(mount.id, mount);
+        let mut mount = WarheadMount::new(&mut id_generator);
+        mount.loaded = true;
+        stack.launch_clamps_mut().insert(mount.id, mount);
+        let mut plate = ArmourPlate::new(&mut id_generator);
+        plate.damaged = true;
+        stack.armour_plates_mut().insert(plate.id, plate);
+        for _ in 0..3 {
+            let engine = Engine::new(&mut id_generator);
+            stack.engines_mut().insert(engine.id, engine);
+        }
+        stack.deposit_cargo(CargoList::new(2, 2, 5, 1)).unwrap();
+        stack.remove_fuel(5).unwrap();
+        state.stacks.insert(stack.id, stack);
+        let enemy = ship(
+            &mut id_generator,
+            Position::new(3, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        state.stacks.insert(enemy.id, enemy);
+
+        for phase in [
+            Phase::Economic,
+            Phase::Ordnance,
+            Phase::Combat,
+            Phase::Movement,
+        ] {
+            state.phase = phase;
+            let orders = state.legal_orders(0.into());
+            assert!(!orders.is_empty());
+            for order in orders {
+                assert_eq!(order.phase(), phase);
+                let turn = match order {
+                    PhaseOrder::Economic(order) => PlayerTurn::Economic(vec![order]),
+                    PhaseOrder::Ordnance(order) => PlayerTurn::Ordnance(vec![order]),
+                    PhaseOrder::Combat(order) => PlayerTurn::Combat(vec![order]),
+                    PhaseOrder::Movement(order) => PlayerTurn::Movement(vec![order]),
+                };
+                let mut trial = state.clone();
+                let report = trial.resolve_turn(&[(0.into(), turn)], &mut id_generator, &mut rng);
+                assert_eq!(report.orders, vec![(0.into(), vec![(0, Ok(()))])]);
+            }
+        }
+        assert!(state.legal_orders(2.into()).is_empty());
+    }
+
+    #[test]
+    fn test_resolution_deterministic() {
+        let play_round = || {
+            let mut id_generator = EntityIdGenerator::new();
+            let mut state = GameState::new(&[5; 32], 3, &mut id_generator);
+            let mut rng = ChaCha20Rng::seed_from_u64(0);
+            for _ in 0..4 {
+                let turns = state
+                    .players()
+                    .map(|player| {
+                        let orders = state.legal_orders(player);
+                        let turn = match state.phase {
+                            Phase::Economic => PlayerTurn::Economic(Vec::new()),
+                            Phase::Ordnance => PlayerTurn::Ordnance(Vec::new()),
+                            Phase::Combat => PlayerTurn::Combat(Vec::new()),
+                            Phase::Movement => PlayerTurn::Movement(Vec::new()),
+                        };
+                        let turn =
+                            orders
+                                .into_iter()
+                                .fold(turn, |turn, order| match (turn, order) {
+                                    (
+                                        PlayerTurn::Economic(mut orders),
+                                        PhaseOrder::Economic(order),
+                                    ) => {
+                                        orders.push(order);
+                                        PlayerTurn::Economic(orders)
+                                    }
+                                    (
+                                        PlayerTurn::Ordnance(mut orders),
+                                        PhaseOrder::Ordnance(order),
+                                    ) => {
+                                        orders.push(order);
+                                        PlayerTurn::Ordnance(orders)
+                                    }
+                                    (PlayerTurn::Combat(mut orders), PhaseOrder::Combat(order)) => {
+                                        orders.push(order);
+                                        PlayerTurn::Combat(orders)
+                                    }
+                                    (
+                                        PlayerTurn::Movement(mut orders),
+                                        PhaseOrder::Movement(order),
+                                    ) => {
+                                        orders.push(order);
+                                        PlayerTurn::Movement(orders)
+                                    }
+                                    _ => unreachable!("legal orders are for the current phase"),
+                                });
+                        (player, turn)
+                    })
+                    .collect::<Vec<_>>();
+                state.resolve_turn(&turns, &mut id_generator, &mut rng);
+            }
+            assert_eq!(state.round, 2);
+            state
+        };
+
+        let first = play_round();
+        let second = play_round();
+        assert_eq!(first.checksum(), second.checksum());
+        assert_eq!(first.entity_ids(), second.entity_ids());
+    }
+
+    #[test]
+    fn test_warhead_passes_through_target() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut target = Stack::new(
+            "Target",
+            &mut id_generator,
+            Position::new(2, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        let plate = ArmourPlate::new(&mut id_generator);
+        let plate_id = plate.id;
+        target.armour_plates_mut().insert(plate.id, plate);
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+        let near_miss = Warhead::new(
+            &mut id_generator,
+            Position::new(0, 1),
+            Displacement::new(4, 0),
+            0.into(),
+        );
+        let near_miss_id = near_miss.id;
+        state.warheads.insert(near_miss.id, near_miss);
+        let warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(4, 0),
+            0.into(),
+        );
+        let warhead_id = warhead.id;
+        state.warheads.insert(warhead.id, warhead);
+
+        let mut moved = state.clone();
+        moved.integrate();
+        assert_eq!(moved.closest_approach(warhead_id, target_id), Some(0));
+        assert_eq!(moved.closest_approach(near_miss_id, target_id), Some(1));
+
+        // the warhead ends up at (4, 0), past the target
+        let detonations = state
+            .resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0))
+            .detonations;
+        assert_eq!(detonations.len(), Warhead::DAMAGE as usize);
+        assert!(detonations.iter().all(|(id, _)| *id == target_id));
+        assert_eq!(detonations[0].1.component, Some(plate_id));
+        assert!(!state.stacks.contains_key(&target_id));
+        assert_eq!(state.warheads.keys().collect::<Vec<_>>(), [&near_miss_id]);
+    }
+
+    #[test]
+    fn test_warhead_hits_moving_target() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        // the target and the warhead both pass through (2, 0) halfway through
+        // the move, and end up well apart
+        let mut target = Stack::new(
+            "Target",
+            &mut id_generator,
+            Position::new(2, -2),
+            Displacement::new(0, 4),
+            1.into(),
+        );
+        for _ in 0..10 {
+            let plate = ArmourPlate::new(&mut id_generator);
+            target.armour_plates_mut().insert(plate.id, plate);
+        }
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+        let warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(4, 0),
+            0.into(),
+        );
+        state.warheads.insert(warhead.id, warhead);
+
+        let detonations = state
+            .resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0))
+            .detonations;
+        assert!(state.warheads.is_empty());
+        assert_eq!(state.stacks[&target_id].position, Position::new(2, 2));
+        assert_eq!(detonations.len(), Warhead::DAMAGE as usize);
+        assert!(detonations.iter().all(|(id, _)| *id == target_id));
+    }
+
+    #[test]
+    fn test_warhead_damage() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut target = Stack::new(
+            "Target",
+            &mut id_generator,
+            Position::new(2, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        for _ in 0..5 {
+            let plate = ArmourPlate::new(&mut id_generator);
+            target.armour_plates_mut().insert(plate.id, plate);
+        }
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+        let mut warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(2, 0),
+            0.into(),
+        );
+        warhead.damage = 3;
+        state.warheads.insert(warhead.id, warhead);
+
+        let detonations = state
+            .resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0))
+            .detonations;
+        assert_eq!(detonations.len(), 3);
+        assert!(detonations
+            .iter()
+            .all(|(id, outcome)| *id == target_id && outcome.hit));
+        let plates = &state.stacks[&target_id].armour_plates();
+        assert_eq!(
+            5 - plates.len() + plates.values().filter(|plate| plate.damaged).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_simultaneous_capture() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut derelict = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            2.into(),
+        );
+        derelict.habitats_mut().clear();
+        let derelict_id = derelict.id;
+        state.stacks.insert(derelict.id, derelict);
+        let captors = [0, 1]
+            .map(|player| {
+                ship(
+                    &mut id_generator,
+                    Position::new(0, 0),
+                    Displacement::new(0, 0),
+                    player.into(),
+                )
+            })
+            .map(|captor| {
+                let habitat = *captor.habitats().keys().next().unwrap();
+                let id = captor.id;
+                state.stacks.insert(captor.id, captor);
+                (id, habitat)
+            });
+
+        // whichever order the transfers are resolved in, the stack is
+        // contested until movement, then goes to the lowest player id
+        for order in [[1, 0], [0, 1]] {
+            let mut state = state.clone();
+            let results = state.resolve_economic(
+                &order.map(|player| {
+                    let (stack, habitat) = captors[player];
+                    (
+                        PlayerId::from(player as u8),
+                        EconomicOrder::StackTransfer(StackTransfer {
+                            stack,
+                            destination: StackTransferTarget::Existing(derelict_id),
+                            components: vec![habitat],
+                        }),
+                    )
+                }),
+                &mut id_generator,
+            );
+            assert_eq!(results.orders, vec![Ok(()), Ok(())]);
+            let contested = &state.stacks[&derelict_id];
+            assert!(contested.is_contested());
+            assert_eq!(contested.controller(), None);
+            assert_eq!(contested.owner, PlayerId::from(order[0] as u8));
+
+            state.resolve_movement(&[], &mut ChaCha20Rng::seed_from_u64(0));
+            let contested = &state.stacks[&derelict_id];
+            assert!(contested.is_contested());
+            assert_eq!(contested.controller(), Some(0.into()));
+            assert_eq!(contested.owner, 0.into());
+        }
+    }
+
+    #[test]
+    fn test_capture_by_rendezvous() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut derelicts = Vec::new();
+        for position in [Position::new(0, 0), Position::new(5, 0)] {
+            let mut derelict = ship(
+                &mut id_generator,
+                position,
+                Displacement::new(1, 0),
+                2.into(),
+            );
+            derelict.habitats_mut().clear();
+            derelicts.push(derelict.id);
+            state.stacks.insert(derelict.id, derelict);
+        }
+        // player 1 is already rendezvoused with the first derelict, and player
+        // 0 burns to match it; only player 1 passes by the second
+        let mut captors = Vec::new();
+        for (player, position, velocity) in [
+            (1, Position::new(0, 0), Displacement::new(1, 0)),
+            (0, Position::new(0, 0), Displacement::new(0, 0)),
+            (1, Position::new(6, 0), Displacement::new(0, 0)),
+        ] {
+            let captor = ship(&mut id_generator, position, velocity, player.into());
+            captors.push(captor.id);
+            state.stacks.insert(captor.id, captor);
+        }
+        assert_eq!(state.stacks[&derelicts[0]].controller(), None);
+
+        let results = state.resolve_movement(
+            &[(
+                0.into(),
+                Burn {
+                    stack: captors[1],
+                    delta: Displacement::new(1, 0),
+                },
+            )],
+            &mut ChaCha20Rng::seed_from_u64(0),
+        );
+        assert_eq!(results.burns, vec![Ok(())]);
+        let captured = &state.stacks[&derelicts[0]];
+        assert!(captured.is_derelict());
+        assert_eq!(captured.controller(), Some(0.into()));
+        assert_eq!(captured.owner, 0.into());
+        let passed = &state.stacks[&derelicts[1]];
+        assert_eq!(passed.controller(), None);
+        assert_eq!(passed.owner, 2.into());
+    }
+
+    #[test]
+    fn test_economic_phase_mines() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let body = crate::astronomical::MinorBody::new(
+            "Vesta",
+            &mut id_generator,
+            Position::new(2, 2),
+            0.2,
+            3,
+            25,
+        );
+        state.minor_bodies.insert(body.id, body);
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(2, 2),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let miner = crate::stack::Miner::new(&mut id_generator);
+        stack.miners_mut().insert(miner.id, miner);
+        let hold = CargoHold::new(&mut id_generator);
+        stack.cargo_holds_mut().insert(hold.id, hold);
+        let stack_id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        state.resolve_economic(&[], &mut id_generator);
+        assert_eq!(
+            state.stacks[&stack_id].cargo(),
+            CargoList::new(3, CargoHold::CAPACITY - 3, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_capture_takes_effect_next_phase() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut derelict = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        derelict.habitats_mut().clear();
+        let captor = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = *captor.habitats().keys().next().unwrap();
+        let (derelict_id, captor_id) = (derelict.id, captor.id);
+        state.stacks.insert(derelict.id, derelict);
+        state.stacks.insert(captor.id, captor);
+        let siphon = EconomicOrder::FuelTransfer(FuelTransfer {
+            stack: derelict_id,
+            destination: captor_id,
+            amount: TransferAmount::Exactly(5),
+        });
+
+        let results = state.resolve_economic(
+            &[
+                (
+                    0.into(),
+                    EconomicOrder::StackTransfer(StackTransfer {
+                        stack: captor_id,
+                        destination: StackTransferTarget::Existing(derelict_id),
+                        components: vec![habitat],
+                    }),
+                ),
+                (0.into(), siphon.clone()),
+            ],
+            &mut id_generator,
+        );
+        assert_eq!(results.orders, vec![Ok(()), Err(OrderError::NotControlled)]);
+        assert_eq!(state.stacks[&derelict_id].controller(), Some(0.into()));
+        assert_eq!(state.stacks[&derelict_id].fuel(), 10);
+
+        let results = state.resolve_economic(&[(0.into(), siphon)], &mut id_generator);
+        assert_eq!(results.orders, vec![Ok(())]);
+        assert_eq!(state.stacks[&derelict_id].fuel(), 5);
+    }
+
+    #[test]
+    fn test_transactional_economy() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.transactional_economy = true;
+        let mut stack = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let factory = Factory::new(&mut id_generator);
+        let factory_id = factory.id;
+        stack.factories_mut().insert(factory.id, factory);
+        let hold = CargoHold::new(&mut id_generator);
+        stack.cargo_holds_mut().insert(hold.id, hold);
+        stack.deposit_cargo(CargoList::new(0, 0, 1, 0)).unwrap();
+        let damaged = [
+            stack.fuel_tanks().keys().next(),
+            stack.engines().keys().next(),
+        ]
+        .map(|id| *id.unwrap());
+        for id in damaged {
+            stack.find_component_mut(id).unwrap().set_damaged(true);
+        }
+        let habitat = *stack.habitats().keys().next().unwrap();
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+        let before = serde_json::to_value(&state.stacks[&id]).unwrap();
+
+        // the first repair uses up the only material, so the second fails
+        let orders = [
+            EconomicOrder::FactoryRepair(FactoryRepair {
+                stack: id,
+                factory: factory_id,
+                components: vec![damaged[0]],
+            }),
+            EconomicOrder::HabitatRepair(HabitatRepair {
+                stack: id,
+                habitat,
+                component: damaged[1],
+            }),
+        ]
+        .map(|order| (PlayerId::from(0), order));
+        let results = state.resolve_economic(&orders, &mut id_generator);
+        assert_eq!(
+            results.orders,
+            vec![
+                Err(OrderError::RolledBack),
+                Err(OrderError::InsufficientCargo)
+            ]
+        );
+        assert_eq!(serde_json::to_value(&state.stacks[&id]).unwrap(), before);
+
+        // without transactions, the first repair sticks
+        state.rules.transactional_economy = false;
+        let results = state.resolve_economic(&orders, &mut id_generator);
+        assert_eq!(
+            results.orders,
+            vec![Ok(()), Err(OrderError::InsufficientCargo)]
+        );
+        assert_eq!(state.stacks[&id].damaged_components(), vec![damaged[1]]);
+    }
+
+    #[test]
+    fn test_new_stack_keys_reported() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let source = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let engine = *source.engines().keys().next().unwrap();
+        let source_id = source.id;
+        state.stacks.insert(source.id, source);
+
+        let results = state.resolve_economic(
+            &[(
+                0.into(),
+                EconomicOrder::StackTransfer(StackTransfer {
+                    stack: source_id,
+                    destination: StackTransferTarget::New(42),
+                    components: vec![engine],
+                }),
+            )],
+            &mut id_generator,
+        );
+        assert_eq!(results.orders, vec![Ok(())]);
+        let created = results.new_stacks[&0.into()][&42];
+        assert!(state.stacks[&created].engines().contains_key(&engine));
+        assert_eq!(results.new_stacks.len(), 1);
+    }
+
+    #[test]
+    fn test_turn_report() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let mut state = GameState::empty();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let gun = Gun::new(&mut id_generator);
+        let gun_id = gun.id;
+        shooter.guns_mut().insert(gun.id, gun);
+        let engine = *shooter.engines().keys().next().unwrap();
+        let factory = Factory::new(&mut id_generator);
+        let factory_id = factory.id;
+        shooter.factories_mut().insert(factory.id, factory);
+        let hold = CargoHold::new(&mut id_generator);
+        shooter.cargo_holds_mut().insert(hold.id, hold);
+        let cost = StackComponent::Gun.mass().unwrap() * state.rules.materials_per_mass;
+        shooter
+            .deposit_cargo(CargoList::new(0, 0, cost, 0))
+            .unwrap();
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let other = ship(
+            &mut id_generator,
+            Position::new(5, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        state.stacks.insert(other.id, other);
+        let mut target = Stack::new(
+            "Wreck",
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        let mut tank = FuelTank::new(&mut id_generator);
+        tank.damaged = true;
+        let tank_id = tank.id;
+        target.fuel_tanks_mut().insert(tank.id, tank);
+        let target_id = target.id;
+        state.stacks.insert(target.id, target);
+
+        let round = state.round;
+        let report = state.resolve_turn(
+            &[(
+                0.into(),
+                PlayerTurn::Economic(vec![
+                    EconomicOrder::Production(Production {
+                        stack: shooter_id,
+                        factory: factory_id,
+                        component: StackComponent::Gun,
+                    }),
+                    EconomicOrder::StackTransfer(StackTransfer {
+                        stack: shooter_id,
+                        destination: StackTransferTarget::New(7),
+                        components: vec![engine],
+                    }),
+                ]),
+            )],
+            &mut id_generator,
+            &mut rng,
+        );
+        let created = report.new_stacks[&0.into()][&7];
+        assert_eq!(report.round, round);
+        assert_eq!(report.next_round, round);
+        assert_eq!(report.phase, Phase::Economic);
+        assert_eq!(report.next_phase, Phase::Ordnance);
+        assert_eq!(
+            report.orders,
+            vec![(0.into(), vec![(0, Ok(())), (1, Ok(()))])]
+        );
+        assert_eq!(state.stacks[&shooter_id].guns().len(), 2);
+        assert_eq!(state.stacks[&shooter_id].cargo().materials, 0);
+        assert_eq!(report.created, vec![created]);
+        assert!(report.destroyed.is_empty());
+        assert!(report.hits.is_empty());
+
+        state.phase = Phase::Combat;
+        let report = state.resolve_turn(
+            &[(
+                0.into(),
+                PlayerTurn::Combat(vec![Shoot {
+                    stack: shooter_id,
+                    gun: gun_id,
+                    target: target_id,
+                }]),
+            )],
+            &mut id_generator,
+            &mut rng,
+        );
+        assert_eq!(
+            report.hits,
+            vec![(
+                target_id,
+                HitOutcome {
+                    hit: true,
+                    component: Some(tank_id),
+                    destroyed: true,
+                    draw: Some(0),
+                }
+            )]
+        );
+        assert_eq!(report.combat_log.len(), 1);
+        assert_eq!(report.combat_log[0].damaged_component, Some(tank_id));
+        assert_eq!(report.destroyed, vec![target_id]);
+        assert!(report.created.is_empty());
+        assert!(report.new_stacks.is_empty());
+        assert_eq!(report.outcome, None);
+    }
+
+    #[test]
+    fn test_rolls_reproduce_on_replay() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let guns = (0..3)
+            .map(|_| {
+                let gun = Gun::new(&mut id_generator);
+                let id = gun.id;
+                shooter.guns_mut().insert(gun.id, gun);
+                id
+            })
+            .collect::<Vec<_>>();
+        let mut target = ship(
+            &mut id_generator,
+            Position::new(2, 0),
+            Displacement::new(0, 0),
+            1.into(),
+        );
+        for _ in 0..3 {
+            let plate = ArmourPlate::new(&mut id_generator);
+            target.armour_plates_mut().insert(plate.id, plate);
+        }
+        let shots = guns
+            .iter()
+            .map(|gun| {
+                (
+                    0.into(),
+                    Shoot {
+                        stack: shooter.id,
+                        gun: *gun,
+                        target: target.id,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        state.stacks.insert(shooter.id, shooter);
+        state.stacks.insert(target.id, target);
+        state.phase = Phase::Combat;
+
+        let logged = state
+            .clone()
+            .resolve_combat(&shots, &mut ChaCha20Rng::seed_from_u64(7))
+            .rolls;
+        let replayed = state
+            .clone()
+            .resolve_combat(&shots, &mut ChaCha20Rng::seed_from_u64(7))
+            .rolls;
+        assert_eq!(logged, replayed);
+        assert_eq!(logged.len(), shots.len());
+        for (index, record) in logged.iter().enumerate() {
+            assert_eq!((record.round, record.phase), (1, Phase::Combat));
+            assert_eq!(record.order, index);
+            assert_eq!(record.hit, record.roll.roll < record.roll.threshold);
+        }
+
+        // the first roll is the first number drawn from the seed
+        let first = ChaCha20Rng::seed_from_u64(7).gen::<f64>();
+        assert_eq!(logged[0].roll.roll, first);
+    }
+
+    #[test]
+    fn test_report_replays_hits() {
+        // redo the hits in a report without the RNG, then compare the damage
+        fn replay(state: &mut GameState, hits: &[(EntityId, HitOutcome)]) {
+            for (target, outcome) in hits {
+                let stack = state.stacks.get_mut(target).unwrap();
+                match outcome.draw {
+                    Some(draw) => assert_eq!(stack.apply_drawn_hit(draw), *outcome),
+                    None => assert!(!outcome.hit),
+                }
+            }
+            state
+                .stacks
+                .retain(|_, stack| !stack.component_ids().is_empty());
+        }
+        fn damage(state: &GameState) -> Vec<(EntityId, Vec<EntityId>, Vec<EntityId>)> {
+            sorted_by_id(&state.stacks)
+                .into_iter()
+                .map(|(id, stack)| {
+                    let damaged = stack
+                        .components_with_ids()
+                        .filter(|(_, component)| component.damaged())
+                        .map(|(id, _)| id)
+                        .collect();
+                    (id, stack.component_ids(), damaged)
+                })
+                .collect()
+        }
+
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut shooter = ship(
+            &mut id_generator,
+            Position::new(0, 0),
+            Displacement::new(0, 0),
+            0.into(),
+        );
+        let guns = (0..6)
+            .map(|_| {
+                let gun = Gun::new(&mut id_generator);
+                let id = gun.id;
+                shooter.guns_mut().insert(gun.id, gun);
+                id
+            })
+            .collect::<Vec<_>>();
+        let shooter_id = shooter.id;
+        state.stacks.insert(shooter.id, shooter);
+        let mut targets = Vec::new();
+        for (position, velocity) in [
+            (Position::new(1, 0), Displacement::new(0, 0)),
+            (Position::new(3, 0), Displacement::new(-2, 0)),
+        ] {
+            let mut target = ship(&mut id_generator, position, velocity, 1.into());
+            let plate = ArmourPlate::new(&mut id_generator);
+            target.armour_plates_mut().insert(plate.id, plate);
+            targets.push(target.id);
+            state.stacks.insert(target.id, target);
+        }
+        let mut rng = ChaCha20Rng::seed_from_u64(3);
+
+        state.phase = Phase::Combat;
+        let before = state.clone();
+        let report = state.resolve_turn(
+            &[(
+                0.into(),
+                PlayerTurn::Combat(
+                    guns.iter()
+                        .map(|gun| Shoot {
+                            stack: shooter_id,
+                            gun: *gun,
+                            target: targets[0],
+                        })
+                        .collect(),
+                ),
+            )],
+            &mut id_generator,
+            &mut rng,
+        );
+        assert_eq!(report.combat_log.len(), guns.len());
+        assert_eq!(report.rolls.len(), guns.len());
+        for (event, record) in report.combat_log.iter().zip(&report.rolls) {
+            assert_eq!(event.roll, record.roll);
+            assert_eq!(event.hit, event.roll.hit());
+        }
+        assert!(report.hits.iter().any(|(_, outcome)| outcome.hit));
+        let mut replayed = before;
+        replay(&mut replayed, &report.hits);
+        assert_eq!(damage(&replayed), damage(&state));
+
+        // the second target runs into the first, right where a warhead is
+        // passing through
+        let warhead = Warhead::new(
+            &mut id_generator,
+            Position::new(1, -1),
+            Displacement::new(0, 2),
+            0.into(),
+        );
+        state.warheads.insert(warhead.id, warhead);
+        let before = state.clone();
+        let report = state.resolve_turn(
+            &[(0.into(), PlayerTurn::Movement(Vec::new()))],
+            &mut id_generator,
+            &mut rng,
+        );
+        assert!(targets
+            .iter()
+            .all(|target| report.hits.iter().any(|(id, _)| id == target)));
+        let mut replayed = before;
+        replayed.apply_gravity();
+        replayed.integrate();
+        replay(&mut replayed, &report.hits);
+        assert_eq!(damage(&replayed), damage(&state));
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Game rules
+//!
+//! Balance values that can be changed per game without touching resolution
+
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::{Deserialize, Serialize};
+
+/// The tunable rules of a game
+///
+/// The default is the standard rules; any rule left out when deserializing
+/// takes its standard value
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize),
+    serde(default)
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    /// Points of materials needed to produce one point of component mass
+    pub materials_per_mass: u64,
+    /// Points of ore converted into one point of materials
+    pub ore_per_material: u64,
+    /// Points of ice converted into one point of fuel
+    pub ice_per_fuel: u64,
+    /// Points of materials converted into one warhead
+    pub materials_per_warhead: u64,
+    /// How far from the sun the map extends, if it's bounded
+    ///
+    /// Anything that moves off the map is lost
+    pub map_radius: Option<u64>,
+    /// The most orders a player may submit in one phase
+    pub max_orders_per_phase: usize,
+    /// How many hexes away a player's stacks can see, if sight is limited
+    pub sensor_range: Option<u64>,
+    /// Whether each player's economic orders succeed or fail together
+    ///
+    /// If any order in a player's run of orders fails, the whole run is
+    /// undone
+    pub transactional_economy: bool,
+    /// How fast stacks may move relative to each other and still transfer
+    ///
+    /// Zero requires a rendezvous; anything more also allows transfers
+    /// between adjacent hexes
+    pub transfer_max_relative_speed: u64,
+    /// How many hexes away a landed stack's miners can draw from other minor
+    /// bodies
+    pub mining_range: u64,
+    /// Points of materials needed to repair one component
+    pub repair_material_cost: u64,
+    /// How many components each habitat may repair in one economic phase
+    pub habitat_repairs_per_phase: u64,
+}
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            materials_per_mass: 1,
+            ore_per_material: 2,
+            ice_per_fuel: 2,
+            materials_per_warhead: 5,
+            map_radius: None,
+            max_orders_per_phase: 1000,
+            sensor_range: None,
+            transactional_economy: false,
+            transfer_max_relative_speed: 0,
+            mining_range: 0,
+            repair_material_cost: 1,
+            habitat_repairs_per_phase: 1,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_rules_default() {
+        let rules: RuleSet = serde_json::from_str(r#"{"sensor_range": 4}"#).unwrap();
+        assert_eq!(
+            rules,
+            RuleSet {
+                sensor_range: Some(4),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Saved games
+//!
+//! Game states are saved tagged with the save format version, so a save from
+//! an incompatible build is rejected instead of being misread
+
+use std::fmt;
+
+use serde::Deserialize;
+#[cfg(feature = "server")]
+use serde::Serialize;
+
+use crate::GameState;
+
+/// The save format version
+///
+/// Version 1 is the format as first released: the rule set (including sensor
+/// range, the transactional economy switch and the repair rules), major body
+/// gravity and parents, stack captors, and the game outcome. Changes made
+/// while it was still unreleased didn't bump it. From now on, bump this
+/// whenever a change to the game state changes how it's serialized
+pub const SAVE_VERSION: u32 = 1;
+
+/// Why a saved game could not be loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The save was made with a different save format version
+    VersionMismatch { found: u32, expected: u32 },
+    /// The save isn't a saved game at all
+    Malformed(String),
+}
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::VersionMismatch { found, expected } => write!(
+                f,
+                "save format version {found} is not supported (expected version {expected})"
+            ),
+            LoadError::Malformed(reason) => write!(f, "not a valid saved game: {reason}"),
+        }
+    }
+}
+impl std::error::Error for LoadError {}
+
+/// Just the version tag, read before anything else
+#[derive(Deserialize)]
+struct VersionTag {
+    version: u32,
+}
+
+#[cfg(feature = "server")]
+#[derive(Serialize)]
+struct SavedRef<'a> {
+    version: u32,
+    state: &'a GameState,
+}
+
+#[derive(Deserialize)]
+struct Saved {
+    state: GameState,
+}
+
+impl GameState {
+    /// Serialize the game state, tagged with the save format version
+    #[cfg(feature = "server")]
+    pub fn to_versioned_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&SavedRef {
+            version: SAVE_VERSION,
+            state: self,
+        })
+        .expect("game state should always serialize")
+    }
+
+    /// Load a game state saved by [`GameState::to_versioned_bytes`]
+    ///
+    /// The version tag is checked before the state itself is read, so a save
+    /// from an incompatible build reports the mismatch rather than whatever
+    /// part of the state failed to parse
+    pub fn from_versioned_bytes(bytes: &[u8]) -> Result<GameState, LoadError> {
+        let tag: VersionTag = serde_json::from_slice(bytes)
+            .map_err(|error| LoadError::Malformed(error.to_string()))?;
+        if tag.version != SAVE_VERSION {
+            return Err(LoadError::VersionMismatch {
+                found: tag.version,
+                expected: SAVE_VERSION,
+            });
+        }
+        let saved: Saved = serde_json::from_slice(bytes)
+            .map_err(|error| LoadError::Malformed(error.to_string()))?;
+        Ok(saved.state)
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::EntityIdGenerator;
+
+    #[test]
+    fn test_versioned_round_trip() {
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let bytes = state.to_versioned_bytes();
+        let loaded = GameState::from_versioned_bytes(&bytes).unwrap();
+        assert_eq!(loaded.checksum(), state.checksum());
+
+        let mut saved: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        saved["version"] = (SAVE_VERSION + 1).into();
+        saved["state"] = serde_json::Value::Null;
+        let error =
+            GameState::from_versioned_bytes(&serde_json::to_vec(&saved).unwrap()).unwrap_err();
+        assert_eq!(
+            error,
+            LoadError::VersionMismatch {
+                found: SAVE_VERSION + 1,
+                expected: SAVE_VERSION
+            }
+        );
+        assert!(error.to_string().contains(&format!("{}", SAVE_VERSION + 1)));
+
+        assert!(matches!(
+            GameState::from_versioned_bytes(b"{\"state\": {}}"),
+            Err(LoadError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,592 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Scenario specifications
+//!
+//! A scenario fixes parts of the solar system that would otherwise be randomly
+//! generated, for reproducible testing and custom game setups
+
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+
+#[cfg(feature = "server")]
+use crate::{
+    astronomical::{MajorBody, MinorBody},
+    sorted_by_id,
+    stack::Stack,
+    EntityId, EntityIdGenerator, GameState, Phase, PlayerId,
+};
+use crate::{
+    order::StackComponent,
+    rules::RuleSet,
+    stack::{CargoHold, CargoList, FuelTank},
+    vec2,
+};
+
+/// A scenario
+///
+/// Any part left as `None` is randomly generated
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone, Default)]
+pub struct ScenarioSpec {
+    /// The exact set of major bodies to place, replacing the randomly placed
+    /// sun, planets, and moons
+    #[cfg_attr(any(feature = "client", feature = "server"), serde(default))]
+    pub major_bodies: Option<Vec<MajorBodySpec>>,
+    /// The exact set of minor bodies to place, replacing the moons of Mars and
+    /// the randomly generated asteroids
+    pub minor_bodies: Option<Vec<MinorBodySpec>>,
+    /// The rules to play by
+    #[cfg_attr(any(feature = "client", feature = "server"), serde(default))]
+    pub rules: RuleSet,
+    /// The stacks each player starts with
+    #[cfg_attr(any(feature = "client", feature = "server"), serde(default))]
+    pub loadout: StartingLoadout,
+}
+
+/// The stacks each player starts the game with
+///
+/// The default is a single station
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone)]
+pub struct StartingLoadout {
+    pub stacks: Vec<StartingStackSpec>,
+}
+impl StartingLoadout {
+    /// Check that the player gets a habitat to control their stacks, that
+    /// every stack has components, and that its fuel and cargo fit in its
+    /// tanks and holds
+    pub fn is_valid(&self) -> bool {
+        self.stacks
+            .iter()
+            .any(|stack| stack.components.contains(&StackComponent::Habitat))
+            && self.stacks.iter().all(StartingStackSpec::is_valid)
+    }
+}
+impl Default for StartingLoadout {
+    fn default() -> Self {
+        Self {
+            stacks: vec![StartingStackSpec {
+                name: None,
+                offset: vec2::Displacement::ZERO,
+                components: vec![
+                    StackComponent::Factory,
+                    StackComponent::Habitat,
+                    StackComponent::FuelTank,
+                    StackComponent::FuelTank,
+                    StackComponent::CargoHold,
+                    StackComponent::CargoHold,
+                    StackComponent::CargoHold,
+                ],
+                fuel: 40,
+                cargo: CargoList::new(0, 0, 60, 0),
+            }],
+        }
+    }
+}
+
+/// One of the stacks in a [`StartingLoadout`]
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone)]
+pub struct StartingStackSpec {
+    /// The stack's name, or `None` to use the player's station name
+    pub name: Option<String>,
+    /// Where the stack starts relative to the player's starting position
+    ///
+    /// This is the offset for the first player; it's rotated along with each
+    /// other player's starting orbit, so every player gets the same layout
+    pub offset: vec2::Displacement,
+    /// The stack's components; habitats belong to the player
+    pub components: Vec<StackComponent>,
+    /// Fuel, spread over the fuel tanks
+    pub fuel: u64,
+    /// Cargo, spread over the cargo holds
+    pub cargo: CargoList,
+}
+impl StartingStackSpec {
+    fn is_valid(&self) -> bool {
+        let count = |kind: StackComponent| {
+            self.components
+                .iter()
+                .filter(|component| **component == kind)
+                .count() as u64
+        };
+        !self.components.is_empty()
+            && self
+                .components
+                .iter()
+                .all(|component| component.mass().is_some())
+            && self.fuel <= count(StackComponent::FuelTank) * FuelTank::CAPACITY
+            && self.cargo.total() <= count(StackComponent::CargoHold) * CargoHold::CAPACITY
+    }
+
+    /// Where the stack starts for a player
+    ///
+    /// `position` is the player's starting position, and `sixths` how far
+    /// their starting orbit is rotated from the first player's
+    #[cfg(feature = "server")]
+    pub(crate) fn position(&self, position: vec2::Position, sixths: usize) -> vec2::Position {
+        position + self.offset.rotate_clockwise(sixths)
+    }
+
+    /// Create the described stack for a player
+    ///
+    /// `position` and `velocity` are the player's starting orbit, and
+    /// `sixths` how far it's rotated from the first player's
+    ///
+    /// # Panics
+    ///
+    /// If the spec isn't valid
+    #[cfg(feature = "server")]
+    pub(crate) fn build(
+        &self,
+        id_generator: &mut EntityIdGenerator,
+        player: PlayerId,
+        default_name: &str,
+        (position, velocity): (vec2::Position, vec2::Displacement),
+        sixths: usize,
+    ) -> Stack {
+        let mut stack = Stack::new(
+            self.name.as_deref().unwrap_or(default_name),
+            id_generator,
+            self.position(position, sixths),
+            velocity,
+            player,
+        );
+        stack.created = Some((1, Phase::Economic));
+        for component in &self.components {
+            stack.insert_component(
+                component
+                    .build(id_generator, player)
+                    .expect("starting components should be producible"),
+            );
+        }
+        stack
+            .add_fuel(self.fuel)
+            .expect("starting fuel should fit in the tanks");
+        stack
+            .deposit_cargo(self.cargo.clone())
+            .expect("starting cargo should fit in the holds");
+        stack
+    }
+}
+
+/// A minor body to place exactly as described
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone)]
+pub struct MinorBodySpec {
+    pub name: String,
+    pub position: vec2::Position,
+    pub radius: f64,
+    pub ice_abundance: u64,
+    pub ore_abundance: u64,
+}
+impl MinorBodySpec {
+    pub fn new(
+        name: &str,
+        position: vec2::Position,
+        radius: f64,
+        ice_abundance: u64,
+        ore_abundance: u64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            position,
+            radius,
+            ice_abundance,
+            ore_abundance,
+        }
+    }
+
+    /// Create the described body
+    #[cfg(feature = "server")]
+    pub fn build(&self, id_generator: &mut EntityIdGenerator) -> MinorBody {
+        MinorBody::new(
+            &self.name,
+            id_generator,
+            self.position,
+            self.radius,
+            self.ice_abundance,
+            self.ore_abundance,
+        )
+    }
+}
+
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone)]
+pub struct MajorBodySpec {
+    pub name: String,
+    pub position: vec2::Position,
+    pub radius: f64,
+    pub colour: String,
+    /// The name of the body this one orbits, for moons
+    pub parent: Option<String>,
+    pub gravity: u64,
+}
+impl MajorBodySpec {
+    /// Create the described bodies, linking moons to their parents by name
+    #[cfg(feature = "server")]
+    pub fn build_all(
+        specs: &[MajorBodySpec],
+        id_generator: &mut EntityIdGenerator,
+    ) -> HashMap<EntityId, MajorBody> {
+        let mut bodies = specs
+            .iter()
+            .map(|spec| {
+                let mut body = MajorBody::new(
+                    &spec.name,
+                    id_generator,
+                    spec.position,
+                    spec.radius,
+                    &spec.colour,
+                );
+                body.gravity = spec.gravity;
+                body
+            })
+            .collect::<Vec<_>>();
+        for (index, spec) in specs.iter().enumerate() {
+            bodies[index].parent = spec.parent.as_ref().and_then(|parent| {
+                bodies
+                    .iter()
+                    .find(|body| body.name == *parent)
+                    .map(|body| body.id)
+            });
+        }
+        bodies.into_iter().map(|body| (body.id, body)).collect()
+    }
+}
+
+#[cfg(feature = "server")]
+impl GameState {
+    /// Capture the solar system as a scenario that recreates it regardless of
+    /// the seed
+    ///
+    /// The first player's stacks become the starting loadout every player
+    /// gets; damage, loaded mounts, and anything belonging to other players
+    /// isn't kept. If the first player controls nothing, the default loadout
+    /// is used
+    pub fn to_scenario(&self) -> ScenarioSpec {
+        let major_bodies = sorted_by_id(&self.major_bodies)
+            .into_iter()
+            .map(|(_, body)| MajorBodySpec {
+                name: body.name.clone(),
+                position: body.position,
+                radius: body.radius,
+                colour: body.colour.clone(),
+                parent: body
+                    .parent
+                    .and_then(|parent| self.major_bodies.get(&parent))
+                    .map(|parent| parent.name.clone()),
+                gravity: body.gravity,
+            })
+            .collect();
+        let minor_bodies = sorted_by_id(&self.minor_bodies)
+            .into_iter()
+            .map(|(_, body)| {
+                MinorBodySpec::new(
+                    &body.name,
+                    body.position,
+                    body.radius,
+                    body.ice_abundance,
+                    body.ore_abundance,
+                )
+            })
+            .collect();
+
+        let terra = self
+            .major_bodies
+            .values()
+            .find(|body| body.name == "Terra")
+            .map_or((16.0, 0.0).into(), |terra| terra.position);
+        let start = terra + crate::starting_orbit(0, self.num_players).0;
+        let stacks = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, stack)| stack.controller() == Some(0.into()))
+            .map(|(_, stack)| {
+                let components = [
+                    (StackComponent::FuelTank, stack.fuel_tanks().len()),
+                    (StackComponent::CargoHold, stack.cargo_holds().len()),
+                    (StackComponent::Engine, stack.engines().len()),
+                    (StackComponent::Gun, stack.guns().len()),
+                    (StackComponent::LaunchClamp, stack.launch_clamps().len()),
+                    (StackComponent::Habitat, stack.habitats().len()),
+                    (StackComponent::Miner, stack.miners().len()),
+                    (StackComponent::Factory, stack.factories().len()),
+                    (StackComponent::ArmourPlate, stack.armour_plates().len()),
+                ]
+                .into_iter()
+                .flat_map(|(kind, count)| std::iter::repeat_n(kind, count))
+                .collect();
+                StartingStackSpec {
+                    name: (stack.name != crate::STARTING_STATION_NAMES[0])
+                        .then(|| stack.name.clone()),
+                    offset: stack.position - start,
+                    components,
+                    fuel: stack.fuel(),
+                    cargo: stack.cargo(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let loadout = if stacks.is_empty() {
+            StartingLoadout::default()
+        } else {
+            StartingLoadout { stacks }
+        };
+
+        ScenarioSpec {
+            major_bodies: Some(major_bodies),
+            minor_bodies: Some(minor_bodies),
+            rules: self.rules.clone(),
+            loadout,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::{lobby::GameCreationError, GameState};
+
+    #[test]
+    fn test_fixed_minor_bodies() {
+        let scenario = ScenarioSpec {
+            minor_bodies: Some(vec![
+                MinorBodySpec::new("Ceres", vec2::Position::new(30, 0), 0.2, 3, 1),
+                MinorBodySpec::new("Vesta", vec2::Position::new(-31, 2), 0.2, 0, 5),
+            ]),
+            ..Default::default()
+        };
+        let state = GameState::from_scenario(&[0; 32], 2, &scenario, &mut EntityIdGenerator::new())
+            .unwrap();
+
+        assert_eq!(state.minor_bodies.len(), 2);
+        let ceres = state
+            .minor_bodies
+            .values()
+            .find(|body| body.name == "Ceres")
+            .unwrap();
+        assert_eq!(ceres.position, vec2::Position::new(30, 0));
+        assert_eq!(ceres.ice_abundance, 3);
+        assert_eq!(ceres.ore_abundance, 1);
+        let vesta = state
+            .minor_bodies
+            .values()
+            .find(|body| body.name == "Vesta")
+            .unwrap();
+        assert_eq!(vesta.position, vec2::Position::new(-31, 2));
+        assert_eq!(vesta.ice_abundance, 0);
+        assert_eq!(vesta.ore_abundance, 5);
+    }
+
+    #[test]
+    fn test_random_minor_bodies() {
+        let state = GameState::from_scenario(
+            &[0; 32],
+            2,
+            &ScenarioSpec::default(),
+            &mut EntityIdGenerator::new(),
+        )
+        .unwrap();
+        assert!(state.minor_bodies.len() > 2);
+    }
+
+    #[test]
+    fn test_two_stack_loadout() {
+        let mut loadout = StartingLoadout::default();
+        assert!(loadout.is_valid());
+        loadout.stacks.push(StartingStackSpec {
+            name: Some("Scout".into()),
+            offset: vec2::Displacement::new(1, 0),
+            components: vec![StackComponent::Habitat, StackComponent::Engine],
+            fuel: 0,
+            cargo: CargoList::default(),
+        });
+        assert!(loadout.is_valid());
+        let scenario = ScenarioSpec {
+            loadout,
+            ..Default::default()
+        };
+        let state = GameState::from_scenario(&[0; 32], 3, &scenario, &mut EntityIdGenerator::new())
+            .unwrap();
+
+        let mut layouts = Vec::new();
+        for player in state.players() {
+            let stacks = state
+                .stacks
+                .values()
+                .filter(|stack| stack.owner == player)
+                .collect::<Vec<_>>();
+            assert_eq!(stacks.len(), 2);
+            let station = stacks.iter().find(|stack| stack.name != "Scout").unwrap();
+            let scout = stacks.iter().find(|stack| stack.name == "Scout").unwrap();
+            assert_eq!(station.factories().len(), 1);
+            assert_eq!(station.cargo().materials, 60);
+            assert_eq!(scout.engines().len(), 1);
+            assert_eq!(scout.controller(), Some(player));
+            assert_eq!(scout.velocity, station.velocity);
+            layouts.push(scout.position - station.position);
+        }
+        assert_eq!(
+            layouts,
+            [0, 2, 4].map(|sixths| vec2::Displacement::new(1, 0).rotate_clockwise(sixths))
+        );
+
+        let mut loadout = StartingLoadout::default();
+        loadout.stacks[0].fuel = 41;
+        assert!(!loadout.is_valid());
+        loadout.stacks[0].fuel = 40;
+        loadout.stacks[0]
+            .components
+            .retain(|component| *component != StackComponent::Habitat);
+        assert!(!loadout.is_valid());
+    }
+
+    #[test]
+    fn test_starting_stacks_placed_clear() {
+        let build = |offsets: &[vec2::Displacement]| {
+            let mut loadout = StartingLoadout::default();
+            for offset in offsets {
+                loadout.stacks.push(StartingStackSpec {
+                    name: Some("Scout".into()),
+                    offset: *offset,
+                    components: vec![StackComponent::Engine],
+                    fuel: 0,
+                    cargo: CargoList::default(),
+                });
+            }
+            let scenario = ScenarioSpec {
+                loadout,
+                ..Default::default()
+            };
+            GameState::from_scenario(&[0; 32], 2, &scenario, &mut EntityIdGenerator::new())
+                .map(|_| ())
+        };
+
+        assert_eq!(build(&[vec2::Displacement::new(1, 0)]), Ok(()));
+        // the first player starts just beside Terra
+        assert_eq!(
+            build(&[vec2::Displacement::new(0, 1)]),
+            Err(GameCreationError::StartingStackInMajorBody)
+        );
+        assert_eq!(
+            build(&[vec2::Displacement::ZERO]),
+            Err(GameCreationError::StartingStacksOverlap)
+        );
+        assert_eq!(
+            build(&[vec2::Displacement::new(1, 0), vec2::Displacement::new(1, 0)]),
+            Err(GameCreationError::StartingStacksOverlap)
+        );
+
+        let mut scenario = ScenarioSpec::default();
+        scenario.loadout.stacks[0].fuel = 41;
+        assert_eq!(
+            GameState::from_scenario(&[0; 32], 2, &scenario, &mut EntityIdGenerator::new())
+                .unwrap_err(),
+            GameCreationError::InvalidLoadout
+        );
+    }
+
+    #[test]
+    fn test_to_scenario_round_trip() {
+        let original = GameState::new(&[4; 32], 3, &mut EntityIdGenerator::new());
+        let scenario = original.to_scenario();
+        let copy = GameState::from_scenario(&[9; 32], 3, &scenario, &mut EntityIdGenerator::new())
+            .unwrap();
+
+        fn sorted<T: Ord>(mut items: Vec<T>) -> Vec<T> {
+            items.sort();
+            items
+        }
+        let major = |state: &GameState| {
+            sorted(
+                state
+                    .major_bodies
+                    .values()
+                    .map(|body| {
+                        let parent = body.parent.map(|parent| &state.major_bodies[&parent].name);
+                        (
+                            body.name.clone(),
+                            body.position.to_tuple(),
+                            body.gravity,
+                            parent.cloned(),
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        let minor = |state: &GameState| {
+            sorted(
+                state
+                    .minor_bodies
+                    .values()
+                    .map(|body| {
+                        (
+                            body.position.to_tuple(),
+                            body.ice_abundance,
+                            body.ore_abundance,
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        let stacks = |state: &GameState| {
+            sorted(
+                state
+                    .stacks
+                    .values()
+                    .map(|stack| {
+                        (
+                            stack.name.clone(),
+                            stack.position.to_tuple(),
+                            stack.velocity.to_tuple(),
+                            stack.fuel(),
+                            stack.component_ids().len(),
+                        )
+                    })
+                    .collect(),
+            )
+        };
+        assert_eq!(copy.major_bodies.len(), original.major_bodies.len());
+        assert_eq!(copy.minor_bodies.len(), original.minor_bodies.len());
+        assert_eq!(major(&copy), major(&original));
+        assert_eq!(minor(&copy), minor(&original));
+        assert_eq!(stacks(&copy), stacks(&original));
+
+        let elsewhere = GameState::new(&[9; 32], 3, &mut EntityIdGenerator::new());
+        assert_ne!(minor(&elsewhere), minor(&original));
+    }
+}
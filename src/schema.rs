@@ -0,0 +1,121 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! JSON Schemas for the wire formats
+//!
+//! For clients written in other languages: the orders they send, and the
+//! views of the game they receive
+
+use std::collections::BTreeMap;
+
+use schemars::{schema::RootSchema, schema_for};
+
+use crate::{
+    order::{EconomicOrder, PlayerTurn},
+    view::PlayerView,
+};
+
+/// Schemas for the wire format types, by type name
+pub fn schemas() -> BTreeMap<&'static str, RootSchema> {
+    BTreeMap::from([
+        ("PlayerTurn", schema_for!(PlayerTurn)),
+        ("EconomicOrder", schema_for!(EconomicOrder)),
+        ("PlayerView", schema_for!(PlayerView)),
+    ])
+}
+
+/// All the schemas as one pretty-printed JSON object, keyed by type name
+pub fn dump_schemas() -> String {
+    serde_json::to_string_pretty(&schemas()).expect("schemas should always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "client", feature = "server"))]
+    use jsonschema::JSONSchema;
+    use serde_json::Value;
+
+    use super::*;
+
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn validator(name: &str) -> JSONSchema {
+        let schema = serde_json::to_value(&schemas()[name]).unwrap();
+        JSONSchema::compile(&schema).unwrap()
+    }
+
+    #[test]
+    fn test_dump_schemas() {
+        let dumped: Value = serde_json::from_str(&dump_schemas()).unwrap();
+        for name in ["PlayerTurn", "EconomicOrder", "PlayerView"] {
+            assert!(dumped[name].is_object());
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn test_order_matches_schema() {
+        use crate::{
+            order::{Burn, FuelTransfer, Production, StackComponent, TransferAmount},
+            vec2,
+        };
+
+        let turn = PlayerTurn::Economic(vec![EconomicOrder::Production(Production {
+            stack: 1.into(),
+            factory: 2.into(),
+            component: StackComponent::Engine,
+        })]);
+        let payload = serde_json::to_value(&turn).unwrap();
+        assert!(validator("PlayerTurn").is_valid(&payload));
+        let order = serde_json::to_value(match &turn {
+            PlayerTurn::Economic(orders) => &orders[0],
+            _ => unreachable!(),
+        })
+        .unwrap();
+        assert!(validator("EconomicOrder").is_valid(&order));
+
+        let turn = PlayerTurn::Movement(vec![Burn {
+            stack: 1.into(),
+            delta: vec2::Displacement::new(1, -1),
+        }]);
+        assert!(validator("PlayerTurn").is_valid(&serde_json::to_value(&turn).unwrap()));
+
+        for amount in [TransferAmount::Exactly(5), TransferAmount::All] {
+            let order = serde_json::to_value(EconomicOrder::FuelTransfer(FuelTransfer {
+                stack: 1.into(),
+                destination: 2.into(),
+                amount,
+            }))
+            .unwrap();
+            assert!(validator("EconomicOrder").is_valid(&order));
+        }
+
+        let bad = serde_json::json!({ "Economic": [{ "Production": { "stack": 1 } }] });
+        assert!(!validator("PlayerTurn").is_valid(&bad));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_view_matches_schema() {
+        use crate::{EntityIdGenerator, GameState};
+
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let view = serde_json::to_value(state.view_for(0.into())).unwrap();
+        assert!(validator("PlayerView").is_valid(&view));
+    }
+}
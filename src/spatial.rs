@@ -0,0 +1,125 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Spatial index
+//!
+//! Stacks grouped by hex, for answering many area queries against one state
+
+use std::collections::HashMap;
+
+use crate::{sorted_by_id, vec2, EntityId, GameState};
+
+/// The stacks in each occupied hex
+///
+/// This is a snapshot of where the stacks were when it was built; rebuild it
+/// once stacks have moved or been created or destroyed
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+    /// The stacks in each occupied hex, in id order
+    hexes: HashMap<vec2::Position, Vec<EntityId>>,
+}
+impl SpatialIndex {
+    /// Index the state's stacks
+    pub fn new(state: &GameState) -> Self {
+        let mut hexes = HashMap::<_, Vec<_>>::new();
+        for (id, stack) in sorted_by_id(&state.stacks) {
+            hexes.entry(stack.position).or_default().push(id);
+        }
+        Self { hexes }
+    }
+
+    /// The stacks in the hex, in id order
+    pub fn stacks_at(&self, position: vec2::Position) -> &[EntityId] {
+        self.hexes.get(&position).map_or(&[], Vec::as_slice)
+    }
+
+    /// The stacks within `radius` hexes of `center`, in id order
+    ///
+    /// Looks at whichever there are fewer of: the hexes within the radius, or
+    /// the occupied hexes
+    pub fn stacks_within(&self, center: vec2::Position, radius: u64) -> Vec<EntityId> {
+        let hexes_within = radius
+            .checked_add(1)
+            .and_then(|next| radius.checked_mul(next))
+            .and_then(|pairs| pairs.checked_mul(3))
+            .and_then(|ring_hexes| ring_hexes.checked_add(1));
+        let mut ids = match hexes_within {
+            Some(count) if count < self.hexes.len() as u64 => center
+                .hexes_within(radius)
+                .into_iter()
+                .flat_map(|position| self.stacks_at(position))
+                .copied()
+                .collect::<Vec<_>>(),
+            _ => self
+                .hexes
+                .iter()
+                .filter(|(position, _)| position.distance(center) <= radius)
+                .flat_map(|(_, ids)| ids)
+                .copied()
+                .collect(),
+        };
+        ids.sort();
+        ids
+    }
+}
+
+impl GameState {
+    /// Index the stacks by hex, for answering many area queries at once
+    pub fn spatial_index(&self) -> SpatialIndex {
+        SpatialIndex::new(self)
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::{stack::Stack, EntityIdGenerator};
+
+    #[test]
+    fn test_spatial_index() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        for (q, r) in [(0, 0), (0, 0), (1, 0), (2, -1), (5, 5), (-40, 7)] {
+            let stack = Stack::new(
+                "Probe",
+                &mut id_generator,
+                vec2::Position::new(q, r),
+                vec2::Displacement::new(0, 0),
+                0.into(),
+            );
+            state.stacks.insert(stack.id, stack);
+        }
+        let index = state.spatial_index();
+
+        let origin = vec2::Position::new(0, 0);
+        assert_eq!(index.stacks_at(origin), &state.stacks_within(origin, 0)[..]);
+        assert_eq!(index.stacks_at(origin).len(), 2);
+        assert!(index.stacks_at(vec2::Position::new(9, 9)).is_empty());
+        // small radii walk the hexes in range, and large ones the occupied
+        // hexes; both agree with a scan of every stack
+        for radius in [0, 1, 2, 10, 100, u64::MAX] {
+            for center in [origin, vec2::Position::new(4, 4)] {
+                assert_eq!(
+                    index.stacks_within(center, radius),
+                    state.stacks_within(center, radius)
+                );
+            }
+        }
+    }
+}
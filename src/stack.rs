@@ -17,37 +17,88 @@
 //
 // SPDX-License-Identifier: AGPL-3.0-or-later
 
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, ops::Add, sync::OnceLock};
 
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use rand::Rng;
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::Deserialize;
+#[cfg(any(feature = "client", feature = "server"))]
+use serde::Serialize;
 
 #[cfg(feature = "server")]
 use crate::EntityIdGenerator;
-use crate::{vec2, EntityId, PlayerId};
+use crate::{order::OrderError, sorted_by_id, vec2, EntityId, Phase, PlayerId};
 
 /// A stack
 ///
 /// Anything that's not an astronomical body or a warhead
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
 pub struct Stack {
     pub name: String,
     pub id: EntityId,
     pub position: vec2::Position,
     pub velocity: vec2::Displacement,
     pub owner: PlayerId,
+    /// Who controls the stack when its habitats don't settle it, as decided
+    /// after movement by [`crate::GameState::resolve_control`]
+    pub captor: Option<PlayerId>,
+    /// The round and phase the stack came into being in, if known
+    pub created: Option<(u64, Phase)>,
 
-    pub fuel_tanks: HashMap<EntityId, FuelTank>,
-    pub cargo_holds: HashMap<EntityId, CargoHold>,
-    pub engines: HashMap<EntityId, Engine>,
-    pub guns: HashMap<EntityId, Gun>,
-    pub launch_clamps: HashMap<EntityId, WarheadMount>,
-    pub habitats: HashMap<EntityId, Habitat>,
-    pub miners: HashMap<EntityId, Miner>,
-    pub factories: HashMap<EntityId, Factory>,
-    pub armour_plates: HashMap<EntityId, ArmourPlate>,
+    fuel_tanks: HashMap<EntityId, FuelTank>,
+    cargo_holds: HashMap<EntityId, CargoHold>,
+    engines: HashMap<EntityId, Engine>,
+    guns: HashMap<EntityId, Gun>,
+    launch_clamps: HashMap<EntityId, WarheadMount>,
+    habitats: HashMap<EntityId, Habitat>,
+    miners: HashMap<EntityId, Miner>,
+    factories: HashMap<EntityId, Factory>,
+    armour_plates: HashMap<EntityId, ArmourPlate>,
+
+    /// [`Stack::mass`], once worked out; cleared by anything that can change
+    /// the components or their contents
+    #[cfg_attr(any(feature = "client", feature = "server"), serde(skip))]
+    #[cfg_attr(feature = "schema", schemars(skip))]
+    mass_cache: OnceLock<u64>,
 }
+
+/// Read and write access to each kind of component in a stack
+///
+/// The component maps are only writable through these, so that the cached
+/// mass is cleared whenever they might change
+macro_rules! component_maps {
+    ( $( $name:ident, $name_mut:ident: $component:ty; )* ) => {
+        $(
+            pub fn $name(&self) -> &HashMap<EntityId, $component> {
+                &self.$name
+            }
+
+            pub fn $name_mut(&mut self) -> &mut HashMap<EntityId, $component> {
+                self.mass_cache.take();
+                &mut self.$name
+            }
+        )*
+    };
+}
+
+impl Stack {
+    component_maps! {
+        fuel_tanks, fuel_tanks_mut: FuelTank;
+        cargo_holds, cargo_holds_mut: CargoHold;
+        engines, engines_mut: Engine;
+        guns, guns_mut: Gun;
+        launch_clamps, launch_clamps_mut: WarheadMount;
+        habitats, habitats_mut: Habitat;
+        miners, miners_mut: Miner;
+        factories, factories_mut: Factory;
+        armour_plates, armour_plates_mut: ArmourPlate;
+    }
+}
+
 impl Stack {
     #[cfg(feature = "server")]
     pub fn new(
@@ -63,6 +114,8 @@ impl Stack {
             position,
             velocity,
             owner,
+            captor: None,
+            created: None,
 
             fuel_tanks: HashMap::new(),
             cargo_holds: HashMap::new(),
@@ -73,27 +126,868 @@ impl Stack {
             miners: HashMap::new(),
             factories: HashMap::new(),
             armour_plates: HashMap::new(),
+
+            mass_cache: OnceLock::new(),
+        }
+    }
+
+    /// The player controlling this stack, if any
+    ///
+    /// Control comes from habitats; a stack with no habitats, or with habitats
+    /// belonging to different players, is controlled by its captor, if it has
+    /// one
+    pub fn controller(&self) -> Option<PlayerId> {
+        let mut owners = self.habitats.values().map(|habitat| habitat.owner);
+        match owners.next() {
+            Some(first) if owners.all(|owner| owner == first) => Some(first),
+            _ => self.captor,
+        }
+    }
+
+    /// Whether the stack has no habitats left
+    ///
+    /// Derelicts keep their `owner`, but nobody controls them until they're
+    /// captured. Anyone can capture one by transferring a habitat into it, or
+    /// by being rendezvoused with it after movement, which makes them its
+    /// owner
+    pub fn is_derelict(&self) -> bool {
+        self.habitats.is_empty()
+    }
+
+    /// Whether the stack has habitats belonging to more than one player
+    ///
+    /// Transfers resolve one at a time, so if two players move habitats into
+    /// the same derelict in one phase, the first to be resolved captures it
+    /// and the second makes it contested. Nobody controls a contested stack
+    /// until after the next movement, when the player with the lowest id
+    /// among its habitats' owners becomes its captor and owner
+    pub fn is_contested(&self) -> bool {
+        let mut owners = self.habitats.values().map(|habitat| habitat.owner);
+        owners
+            .next()
+            .is_some_and(|first| owners.any(|owner| owner != first))
+    }
+
+    /// Damage a component in the stack
+    ///
+    /// Undamaged armour plates are always hit first; otherwise, a random
+    /// component is hit, becoming damaged if it was intact and being destroyed
+    /// if it was already damaged. Misses only if there are no components
+    ///
+    /// Candidates are considered in order of id so that a seeded RNG always
+    /// hits the same component
+    #[cfg(feature = "server")]
+    pub fn apply_hit(&mut self, rng: &mut impl Rng) -> HitOutcome {
+        let candidates = self.hit_candidates().len();
+        if candidates == 0 {
+            return HitOutcome::MISS;
+        }
+        self.apply_drawn_hit(rng.gen_range(0..candidates))
+    }
+
+    /// Damage the component picked by a hit's [`HitOutcome::draw`], as
+    /// [`Stack::apply_hit`] would have, so a replay can redo the hit without
+    /// the RNG
+    pub fn apply_drawn_hit(&mut self, draw: usize) -> HitOutcome {
+        self.mass_cache.take();
+        let Some(id) = self.hit_candidates().get(draw).copied() else {
+            return HitOutcome::MISS;
+        };
+        let mut component = self.find_component_mut(id).unwrap();
+        let outcome = HitOutcome {
+            hit: true,
+            component: Some(id),
+            destroyed: component.damaged(),
+            draw: Some(draw),
+        };
+        if outcome.destroyed {
+            self.remove_component(id);
+        } else {
+            component.set_damaged(true);
+        }
+        outcome
+    }
+
+    /// The components a hit could land on, in id order: the undamaged armour
+    /// plates if there are any, and every component otherwise
+    fn hit_candidates(&self) -> Vec<EntityId> {
+        let armour = sorted_by_id(&self.armour_plates)
+            .into_iter()
+            .filter(|(_, plate)| !plate.damaged)
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        if armour.is_empty() {
+            self.component_ids()
+        } else {
+            armour
+        }
+    }
+
+    /// Ids of every component in the stack, in order
+    pub fn component_ids(&self) -> Vec<EntityId> {
+        let mut ids = self
+            .fuel_tanks
+            .keys()
+            .chain(self.cargo_holds.keys())
+            .chain(self.engines.keys())
+            .chain(self.guns.keys())
+            .chain(self.launch_clamps.keys())
+            .chain(self.habitats.keys())
+            .chain(self.miners.keys())
+            .chain(self.factories.keys())
+            .chain(self.armour_plates.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    /// Every component in the stack with its id, in id order
+    pub fn components_with_ids(&self) -> impl Iterator<Item = (EntityId, &dyn Component)> {
+        fn with_ids<T: Component>(
+            components: &HashMap<EntityId, T>,
+        ) -> impl Iterator<Item = (EntityId, &dyn Component)> {
+            components
+                .iter()
+                .map(|(id, component)| (*id, component as &dyn Component))
+        }
+
+        let mut components = with_ids(&self.fuel_tanks)
+            .chain(with_ids(&self.cargo_holds))
+            .chain(with_ids(&self.engines))
+            .chain(with_ids(&self.guns))
+            .chain(with_ids(&self.launch_clamps))
+            .chain(with_ids(&self.habitats))
+            .chain(with_ids(&self.miners))
+            .chain(with_ids(&self.factories))
+            .chain(with_ids(&self.armour_plates))
+            .collect::<Vec<_>>();
+        components.sort_by_key(|(id, _)| *id);
+        components.into_iter()
+    }
+
+    /// Ids of the damaged components in the stack, in id order
+    pub fn damaged_components(&self) -> Vec<EntityId> {
+        self.components_with_ids()
+            .filter(|(_, component)| component.damaged())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Could the stack repair its own damage - does it have an undamaged
+    /// factory or habitat?
+    pub fn can_self_repair(&self) -> bool {
+        self.factories.values().any(|factory| !factory.damaged)
+            || self.habitats.values().any(|habitat| !habitat.damaged)
+    }
+
+    /// Find a component in the stack by id
+    pub fn find_component(&self, id: EntityId) -> Option<ComponentRef<'_>> {
+        if let Some(component) = self.fuel_tanks.get(&id) {
+            Some(ComponentRef::FuelTank(component))
+        } else if let Some(component) = self.cargo_holds.get(&id) {
+            Some(ComponentRef::CargoHold(component))
+        } else if let Some(component) = self.engines.get(&id) {
+            Some(ComponentRef::Engine(component))
+        } else if let Some(component) = self.guns.get(&id) {
+            Some(ComponentRef::Gun(component))
+        } else if let Some(component) = self.launch_clamps.get(&id) {
+            Some(ComponentRef::WarheadMount(component))
+        } else if let Some(component) = self.habitats.get(&id) {
+            Some(ComponentRef::Habitat(component))
+        } else if let Some(component) = self.miners.get(&id) {
+            Some(ComponentRef::Miner(component))
+        } else if let Some(component) = self.factories.get(&id) {
+            Some(ComponentRef::Factory(component))
+        } else {
+            self.armour_plates.get(&id).map(ComponentRef::ArmourPlate)
+        }
+    }
+
+    /// Find a component in the stack by id, for modification
+    pub fn find_component_mut(&mut self, id: EntityId) -> Option<ComponentMut<'_>> {
+        self.mass_cache.take();
+        if let Some(component) = self.fuel_tanks.get_mut(&id) {
+            Some(ComponentMut::FuelTank(component))
+        } else if let Some(component) = self.cargo_holds.get_mut(&id) {
+            Some(ComponentMut::CargoHold(component))
+        } else if let Some(component) = self.engines.get_mut(&id) {
+            Some(ComponentMut::Engine(component))
+        } else if let Some(component) = self.guns.get_mut(&id) {
+            Some(ComponentMut::Gun(component))
+        } else if let Some(component) = self.launch_clamps.get_mut(&id) {
+            Some(ComponentMut::WarheadMount(component))
+        } else if let Some(component) = self.habitats.get_mut(&id) {
+            Some(ComponentMut::Habitat(component))
+        } else if let Some(component) = self.miners.get_mut(&id) {
+            Some(ComponentMut::Miner(component))
+        } else if let Some(component) = self.factories.get_mut(&id) {
+            Some(ComponentMut::Factory(component))
+        } else {
+            self.armour_plates
+                .get_mut(&id)
+                .map(ComponentMut::ArmourPlate)
+        }
+    }
+
+    /// Remove a component from the stack by id, returning it if it was there
+    pub fn remove_component(&mut self, id: EntityId) -> Option<AnyComponent> {
+        self.mass_cache.take();
+        if let Some(component) = self.fuel_tanks.remove(&id) {
+            Some(AnyComponent::FuelTank(component))
+        } else if let Some(component) = self.cargo_holds.remove(&id) {
+            Some(AnyComponent::CargoHold(component))
+        } else if let Some(component) = self.engines.remove(&id) {
+            Some(AnyComponent::Engine(component))
+        } else if let Some(component) = self.guns.remove(&id) {
+            Some(AnyComponent::Gun(component))
+        } else if let Some(component) = self.launch_clamps.remove(&id) {
+            Some(AnyComponent::WarheadMount(component))
+        } else if let Some(component) = self.habitats.remove(&id) {
+            Some(AnyComponent::Habitat(component))
+        } else if let Some(component) = self.miners.remove(&id) {
+            Some(AnyComponent::Miner(component))
+        } else if let Some(component) = self.factories.remove(&id) {
+            Some(AnyComponent::Factory(component))
+        } else {
+            self.armour_plates
+                .remove(&id)
+                .map(AnyComponent::ArmourPlate)
+        }
+    }
+
+    /// Add a component to the stack
+    pub fn insert_component(&mut self, component: AnyComponent) {
+        self.mass_cache.take();
+        match component {
+            AnyComponent::FuelTank(component) => {
+                self.fuel_tanks.insert(component.id, component);
+            }
+            AnyComponent::CargoHold(component) => {
+                self.cargo_holds.insert(component.id, component);
+            }
+            AnyComponent::Engine(component) => {
+                self.engines.insert(component.id, component);
+            }
+            AnyComponent::Gun(component) => {
+                self.guns.insert(component.id, component);
+            }
+            AnyComponent::WarheadMount(component) => {
+                self.launch_clamps.insert(component.id, component);
+            }
+            AnyComponent::Habitat(component) => {
+                self.habitats.insert(component.id, component);
+            }
+            AnyComponent::Miner(component) => {
+                self.miners.insert(component.id, component);
+            }
+            AnyComponent::Factory(component) => {
+                self.factories.insert(component.id, component);
+            }
+            AnyComponent::ArmourPlate(component) => {
+                self.armour_plates.insert(component.id, component);
+            }
+        }
+    }
+
+    /// What the stack looks like from the outside
+    pub fn silhouette(&self) -> StackSilhouette {
+        StackSilhouette {
+            name: self.name.clone(),
+            id: self.id,
+            position: self.position,
+            velocity: self.velocity,
+            owner: self.owner,
+
+            fuel_tanks: self.fuel_tanks.len() as u64,
+            cargo_holds: self.cargo_holds.len() as u64,
+            engines: self.engines.len() as u64,
+            guns: self.guns.len() as u64,
+            launch_clamps: self.launch_clamps.len() as u64,
+            habitats: self.habitats.len() as u64,
+            miners: self.miners.len() as u64,
+            factories: self.factories.len() as u64,
+            armour_plates: self.armour_plates.len() as u64,
+        }
+    }
+
+    /// Are the two stacks in the same hex with the same velocity?
+    pub fn rendezvoused_with(&self, other: &Stack) -> bool {
+        self.position == other.position && self.velocity == other.velocity
+    }
+
+    /// Are the two stacks close enough to transfer between?
+    ///
+    /// With no tolerance, the stacks must be rendezvoused; otherwise they may
+    /// also be in adjacent hexes, as long as their relative speed is within
+    /// the tolerance
+    pub fn within_transfer_range(&self, other: &Stack, max_relative_speed: u64) -> bool {
+        let max_distance = max_relative_speed.min(1);
+        self.position.distance(other.position) <= max_distance
+            && (self.velocity - other.velocity).norm() <= max_relative_speed
+    }
+
+    /// Total mass of the components in the stack, not including contents
+    pub fn component_mass(&self) -> u64 {
+        self.fuel_tanks.len() as u64 * FuelTank::MASS
+            + self.cargo_holds.len() as u64 * CargoHold::MASS
+            + self.engines.len() as u64 * Engine::MASS
+            + self.guns.len() as u64 * Gun::MASS
+            + self.launch_clamps.len() as u64 * WarheadMount::MASS
+            + self.habitats.len() as u64 * Habitat::MASS
+            + self.miners.len() as u64 * Miner::MASS
+            + self.factories.len() as u64 * Factory::MASS
+            + self.armour_plates.len() as u64 * ArmourPlate::MASS
+    }
+
+    /// Total mass of the stack
+    ///
+    /// Each point of fuel, each point of cargo, and each loaded warhead has a
+    /// mass of 1
+    ///
+    /// Cached until the components or their contents next change
+    pub fn mass(&self) -> u64 {
+        let mass = *self.mass_cache.get_or_init(|| self.computed_mass());
+        debug_assert_eq!(mass, self.computed_mass(), "cached mass is stale");
+        mass
+    }
+
+    /// Total mass of the stack, worked out from scratch
+    fn computed_mass(&self) -> u64 {
+        let cargo = self.cargo();
+        self.component_mass() + self.fuel() + cargo.total() - cargo.warheads + self.total_warheads()
+    }
+
+    /// Warheads carried as cargo plus those loaded into mounts
+    ///
+    /// A warhead is in exactly one of the two places, so this is every
+    /// warhead aboard, counted once
+    pub fn total_warheads(&self) -> u64 {
+        self.cargo().warheads
+            + self
+                .launch_clamps
+                .values()
+                .filter(|mount| mount.loaded)
+                .count() as u64
+    }
+
+    /// Roughly how big to draw the stack
+    ///
+    /// Grows with the cube root of the mass, as if the stack were a ball of
+    /// constant density
+    pub fn visual_radius(&self) -> f64 {
+        (self.mass() as f64).cbrt()
+    }
+
+    /// Number of undamaged engines
+    pub fn working_engines(&self) -> u64 {
+        self.engines
+            .values()
+            .filter(|engine| !engine.damaged)
+            .count() as u64
+    }
+
+    /// Mass the working engines have to push - everything except themselves
+    fn pushed_mass(&self) -> u64 {
+        self.pushed_mass_of(self.mass())
+    }
+
+    /// Mass the working engines would have to push if the whole stack had the
+    /// given mass
+    fn pushed_mass_of(&self, mass: u64) -> u64 {
+        mass.saturating_sub(self.working_engines() * Engine::MASS)
+            .max(1)
+    }
+
+    /// Fuel needed to change velocity by a displacement with the given norm,
+    /// or `None` if the engines can't provide that much thrust
+    ///
+    /// One point of fuel is burned per engine needed to push the stack's mass
+    pub(crate) fn thrust_fuel(&self, norm: u64) -> Option<u64> {
+        self.thrust_fuel_with_mass(norm, self.mass())
+    }
+
+    fn thrust_fuel_with_mass(&self, norm: u64, mass: u64) -> Option<u64> {
+        let needed = norm
+            .checked_mul(self.pushed_mass_of(mass))?
+            .div_ceil(Engine::THRUST);
+        if needed <= self.working_engines() {
+            Some(needed)
+        } else {
+            None
+        }
+    }
+
+    /// Fuel a burn by this delta would use, or `None` if the stack doesn't
+    /// have the thrust or the fuel for it
+    pub fn burn_cost(&self, delta: vec2::Displacement) -> Option<u64> {
+        self.burn_cost_with_mass(delta, self.mass())
+    }
+
+    /// Fuel a burn by this delta would use if the stack had the given total
+    /// mass, for previewing burns after loading or unloading cargo
+    ///
+    /// Uses the stack's current engines and fuel
+    pub fn burn_cost_with_mass(
+        &self,
+        delta: vec2::Displacement,
+        mass_override: u64,
+    ) -> Option<u64> {
+        self.thrust_fuel_with_mass(delta.checked_norm()?, mass_override)
+            .filter(|fuel| *fuel <= self.fuel())
+    }
+
+    /// Largest velocity change the stack can make this turn, given its
+    /// engines and fuel
+    pub fn max_delta(&self) -> u64 {
+        self.working_engines().min(self.fuel()) * Engine::THRUST / self.pushed_mass()
+    }
+
+    /// Whether the stack can move under its own power, and if not, why not
+    pub fn mobility_status(&self) -> MobilityStatus {
+        if self.working_engines() == 0 {
+            MobilityStatus::NoEngines
+        } else if self.fuel() == 0 {
+            MobilityStatus::OutOfFuel
+        } else {
+            match self.max_delta() {
+                0 => MobilityStatus::Overweight,
+                max_delta => MobilityStatus::Mobile { max_delta },
+            }
+        }
+    }
+
+    /// Total fuel across all fuel tanks
+    pub fn fuel(&self) -> u64 {
+        self.fuel_tanks.values().map(|tank| tank.fuel).sum()
+    }
+
+    /// Total fuel capacity across all fuel tanks
+    pub fn fuel_capacity(&self) -> u64 {
+        self.fuel_tanks.len() as u64 * FuelTank::CAPACITY
+    }
+
+    /// Total cargo across all cargo holds
+    pub fn cargo(&self) -> CargoList {
+        self.cargo_holds
+            .values()
+            .fold(CargoList::default(), |total, hold| total + &hold.inventory)
+    }
+
+    /// Total cargo capacity across all cargo holds
+    pub fn cargo_capacity(&self) -> u64 {
+        self.cargo_holds.len() as u64 * CargoHold::CAPACITY
+    }
+
+    /// Add fuel to the stack's tanks, filling tanks in order of id
+    pub(crate) fn add_fuel(&mut self, amount: u64) -> Result<(), OrderError> {
+        self.mass_cache.take();
+        let total = self
+            .fuel()
+            .checked_add(amount)
+            .ok_or(OrderError::Overflow)?;
+        if total > self.fuel_capacity() {
+            return Err(OrderError::CapacityExceeded);
+        }
+
+        let mut remaining = amount;
+        let ids = sorted_by_id(&self.fuel_tanks)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let tank = self.fuel_tanks.get_mut(&id).unwrap();
+            let added = remaining.min(FuelTank::CAPACITY - tank.fuel);
+            tank.fuel += added;
+            remaining -= added;
+        }
+        Ok(())
+    }
+
+    /// Remove fuel from the stack's tanks, draining tanks in order of id
+    pub(crate) fn remove_fuel(&mut self, amount: u64) -> Result<(), OrderError> {
+        self.mass_cache.take();
+        if self.fuel() < amount {
+            return Err(OrderError::InsufficientFuel);
+        }
+
+        let mut remaining = amount;
+        let ids = sorted_by_id(&self.fuel_tanks)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let tank = self.fuel_tanks.get_mut(&id).unwrap();
+            let removed = remaining.min(tank.fuel);
+            tank.fuel -= removed;
+            remaining -= removed;
+        }
+        Ok(())
+    }
+
+    /// Add cargo to the stack's holds, filling holds in order of id
+    ///
+    /// Nothing is added if the cargo doesn't all fit
+    pub fn deposit_cargo(&mut self, cargo: CargoList) -> Result<(), OrderError> {
+        self.mass_cache.take();
+        let total = cargo
+            .checked_total()
+            .and_then(|incoming| self.cargo().total().checked_add(incoming))
+            .ok_or(OrderError::Overflow)?;
+        if total > self.cargo_capacity() {
+            return Err(OrderError::CapacityExceeded);
         }
+
+        self.fill_holds(cargo);
+        Ok(())
+    }
+
+    /// Put as much of the cargo as fits into the stack's holds, filling holds
+    /// in order of id, and return what didn't fit
+    fn fill_holds(&mut self, cargo: CargoList) -> CargoList {
+        let mut remaining = cargo;
+        let ids = sorted_by_id(&self.cargo_holds)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let inventory = &mut self.cargo_holds.get_mut(&id).unwrap().inventory;
+            let mut space = CargoHold::CAPACITY.saturating_sub(inventory.total());
+            for (held, incoming) in [
+                (&mut inventory.ice, &mut remaining.ice),
+                (&mut inventory.ore, &mut remaining.ore),
+                (&mut inventory.materials, &mut remaining.materials),
+                (&mut inventory.warheads, &mut remaining.warheads),
+            ] {
+                let added = space.min(*incoming);
+                *held += added;
+                *incoming -= added;
+                space -= added;
+            }
+        }
+        remaining
+    }
+
+    /// Remove cargo from the stack's holds, emptying holds in order of id
+    ///
+    /// Nothing is removed if the stack doesn't hold all of it
+    pub fn withdraw_cargo(&mut self, cargo: CargoList) -> Result<(), OrderError> {
+        self.mass_cache.take();
+        if !self.cargo().contains(&cargo) {
+            return Err(OrderError::InsufficientCargo);
+        }
+
+        let mut remaining = cargo;
+        let ids = sorted_by_id(&self.cargo_holds)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in ids {
+            let inventory = &mut self.cargo_holds.get_mut(&id).unwrap().inventory;
+            for (held, outgoing) in [
+                (&mut inventory.ice, &mut remaining.ice),
+                (&mut inventory.ore, &mut remaining.ore),
+                (&mut inventory.materials, &mut remaining.materials),
+                (&mut inventory.warheads, &mut remaining.warheads),
+            ] {
+                let removed = (*held).min(*outgoing);
+                *held -= removed;
+                *outgoing -= removed;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pack the stack's cargo into the fewest holds
+    ///
+    /// Holds are filled in order of id, and each hold takes ice, then ore,
+    /// then materials, then warheads, so the same cargo always ends up in the
+    /// same layout
+    ///
+    /// If the holds were overfilled, the cargo that no longer fits is
+    /// dropped, and returned
+    pub fn normalize_cargo(&mut self) -> CargoList {
+        self.mass_cache.take();
+        let cargo = self.cargo();
+        for hold in self.cargo_holds.values_mut() {
+            hold.inventory = CargoList::default();
+        }
+        self.fill_holds(cargo)
     }
 }
 
+/// What happened to a stack when it was shot at or caught in a blast
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitOutcome {
+    pub hit: bool,
+    /// The component hit, if any
+    pub component: Option<EntityId>,
+    /// Whether the component hit was destroyed rather than just damaged
+    pub destroyed: bool,
+    /// Which of the components that could be hit was, for replaying the hit
+    /// with [`Stack::apply_drawn_hit`]
+    pub draw: Option<usize>,
+}
+impl HitOutcome {
+    pub const MISS: HitOutcome = HitOutcome {
+        hit: false,
+        component: None,
+        destroyed: false,
+        draw: None,
+    };
+}
+
+/// Whether a stack can move under its own power
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MobilityStatus {
+    Mobile {
+        max_delta: u64,
+    },
+    OutOfFuel,
+    NoEngines,
+    /// The stack is too heavy for its engines to move it even one hex/turn
+    Overweight,
+}
+
+/// The outside of a stack
+///
+/// How many of each component it has, but not their contents or condition
+#[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackSilhouette {
+    pub name: String,
+    pub id: EntityId,
+    pub position: vec2::Position,
+    pub velocity: vec2::Displacement,
+    pub owner: PlayerId,
+
+    pub fuel_tanks: u64,
+    pub cargo_holds: u64,
+    pub engines: u64,
+    pub guns: u64,
+    pub launch_clamps: u64,
+    pub habitats: u64,
+    pub miners: u64,
+    pub factories: u64,
+    pub armour_plates: u64,
+}
+
+/// A component of any type, removed from its stack
+#[derive(Debug, Clone)]
+pub enum AnyComponent {
+    FuelTank(FuelTank),
+    CargoHold(CargoHold),
+    Engine(Engine),
+    Gun(Gun),
+    WarheadMount(WarheadMount),
+    Habitat(Habitat),
+    Miner(Miner),
+    Factory(Factory),
+    ArmourPlate(ArmourPlate),
+}
+impl AnyComponent {
+    pub fn id(&self) -> EntityId {
+        match self {
+            AnyComponent::FuelTank(component) => component.id,
+            AnyComponent::CargoHold(component) => component.id,
+            AnyComponent::Engine(component) => component.id,
+            AnyComponent::Gun(component) => component.id,
+            AnyComponent::WarheadMount(component) => component.id,
+            AnyComponent::Habitat(component) => component.id,
+            AnyComponent::Miner(component) => component.id,
+            AnyComponent::Factory(component) => component.id,
+            AnyComponent::ArmourPlate(component) => component.id,
+        }
+    }
+
+    pub fn damaged(&self) -> bool {
+        match self {
+            AnyComponent::FuelTank(component) => component.damaged,
+            AnyComponent::CargoHold(component) => component.damaged,
+            AnyComponent::Engine(component) => component.damaged,
+            AnyComponent::Gun(component) => component.damaged,
+            AnyComponent::WarheadMount(component) => component.damaged,
+            AnyComponent::Habitat(component) => component.damaged,
+            AnyComponent::Miner(component) => component.damaged,
+            AnyComponent::Factory(component) => component.damaged,
+            AnyComponent::ArmourPlate(component) => component.damaged,
+        }
+    }
+}
+
+/// A reference to a component of any type
+#[derive(Debug, Clone, Copy)]
+pub enum ComponentRef<'a> {
+    FuelTank(&'a FuelTank),
+    CargoHold(&'a CargoHold),
+    Engine(&'a Engine),
+    Gun(&'a Gun),
+    WarheadMount(&'a WarheadMount),
+    Habitat(&'a Habitat),
+    Miner(&'a Miner),
+    Factory(&'a Factory),
+    ArmourPlate(&'a ArmourPlate),
+}
+impl ComponentRef<'_> {
+    pub fn id(&self) -> EntityId {
+        match self {
+            ComponentRef::FuelTank(component) => component.id,
+            ComponentRef::CargoHold(component) => component.id,
+            ComponentRef::Engine(component) => component.id,
+            ComponentRef::Gun(component) => component.id,
+            ComponentRef::WarheadMount(component) => component.id,
+            ComponentRef::Habitat(component) => component.id,
+            ComponentRef::Miner(component) => component.id,
+            ComponentRef::Factory(component) => component.id,
+            ComponentRef::ArmourPlate(component) => component.id,
+        }
+    }
+
+    pub fn damaged(&self) -> bool {
+        match self {
+            ComponentRef::FuelTank(component) => component.damaged,
+            ComponentRef::CargoHold(component) => component.damaged,
+            ComponentRef::Engine(component) => component.damaged,
+            ComponentRef::Gun(component) => component.damaged,
+            ComponentRef::WarheadMount(component) => component.damaged,
+            ComponentRef::Habitat(component) => component.damaged,
+            ComponentRef::Miner(component) => component.damaged,
+            ComponentRef::Factory(component) => component.damaged,
+            ComponentRef::ArmourPlate(component) => component.damaged,
+        }
+    }
+
+    /// Mass of the component itself, not including contents
+    pub fn mass(&self) -> u64 {
+        match self {
+            ComponentRef::FuelTank(_) => FuelTank::MASS,
+            ComponentRef::CargoHold(_) => CargoHold::MASS,
+            ComponentRef::Engine(_) => Engine::MASS,
+            ComponentRef::Gun(_) => Gun::MASS,
+            ComponentRef::WarheadMount(_) => WarheadMount::MASS,
+            ComponentRef::Habitat(_) => Habitat::MASS,
+            ComponentRef::Miner(_) => Miner::MASS,
+            ComponentRef::Factory(_) => Factory::MASS,
+            ComponentRef::ArmourPlate(_) => ArmourPlate::MASS,
+        }
+    }
+}
+
+/// A mutable reference to a component of any type
+#[derive(Debug)]
+pub enum ComponentMut<'a> {
+    FuelTank(&'a mut FuelTank),
+    CargoHold(&'a mut CargoHold),
+    Engine(&'a mut Engine),
+    Gun(&'a mut Gun),
+    WarheadMount(&'a mut WarheadMount),
+    Habitat(&'a mut Habitat),
+    Miner(&'a mut Miner),
+    Factory(&'a mut Factory),
+    ArmourPlate(&'a mut ArmourPlate),
+}
+impl ComponentMut<'_> {
+    pub fn id(&self) -> EntityId {
+        match self {
+            ComponentMut::FuelTank(component) => component.id,
+            ComponentMut::CargoHold(component) => component.id,
+            ComponentMut::Engine(component) => component.id,
+            ComponentMut::Gun(component) => component.id,
+            ComponentMut::WarheadMount(component) => component.id,
+            ComponentMut::Habitat(component) => component.id,
+            ComponentMut::Miner(component) => component.id,
+            ComponentMut::Factory(component) => component.id,
+            ComponentMut::ArmourPlate(component) => component.id,
+        }
+    }
+
+    pub fn damaged(&self) -> bool {
+        match self {
+            ComponentMut::FuelTank(component) => component.damaged,
+            ComponentMut::CargoHold(component) => component.damaged,
+            ComponentMut::Engine(component) => component.damaged,
+            ComponentMut::Gun(component) => component.damaged,
+            ComponentMut::WarheadMount(component) => component.damaged,
+            ComponentMut::Habitat(component) => component.damaged,
+            ComponentMut::Miner(component) => component.damaged,
+            ComponentMut::Factory(component) => component.damaged,
+            ComponentMut::ArmourPlate(component) => component.damaged,
+        }
+    }
+
+    pub fn set_damaged(&mut self, damaged: bool) {
+        match self {
+            ComponentMut::FuelTank(component) => component.damaged = damaged,
+            ComponentMut::CargoHold(component) => component.damaged = damaged,
+            ComponentMut::Engine(component) => component.damaged = damaged,
+            ComponentMut::Gun(component) => component.damaged = damaged,
+            ComponentMut::WarheadMount(component) => component.damaged = damaged,
+            ComponentMut::Habitat(component) => component.damaged = damaged,
+            ComponentMut::Miner(component) => component.damaged = damaged,
+            ComponentMut::Factory(component) => component.damaged = damaged,
+            ComponentMut::ArmourPlate(component) => component.damaged = damaged,
+        }
+    }
+}
+
+/// Something every component type can do
+pub trait Component: fmt::Debug + fmt::Display {
+    fn id(&self) -> EntityId;
+    fn damaged(&self) -> bool;
+    /// Mass of the component itself, not including contents
+    fn mass(&self) -> u64;
+    fn name(&self) -> &'static str;
+}
+
 /// Create a component type
 ///
-/// `name` = component name
+/// `name` = component name, also used as its display name
 /// `fields` = extra fields
 macro_rules! component {
     ( $(#[$attributes:meta])* $name:ident<mass = $mass:literal> { $($fields:tt)* } ) => {
         $(#[$attributes])*
         #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
         #[cfg_attr(feature = "server", derive(Serialize))]
-        #[derive(Debug)]
+        #[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+        #[derive(Debug, Clone)]
         pub struct $name {
             pub id: EntityId,
             pub damaged: bool,
             $($fields)*
         }
         impl $name {
-            const MASS: u64 = $mass;
+            pub const MASS: u64 = $mass;
+            pub const NAME: &'static str = stringify!($name);
+        }
+        impl Component for $name {
+            fn id(&self) -> EntityId {
+                self.id
+            }
+            fn damaged(&self) -> bool {
+                self.damaged
+            }
+            fn mass(&self) -> u64 {
+                Self::MASS
+            }
+            fn name(&self) -> &'static str {
+                Self::NAME
+            }
+        }
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if self.damaged {
+                    write!(f, "{} (damaged)", Self::NAME)
+                } else {
+                    write!(f, "{}", Self::NAME)
+                }
+            }
         }
     };
 }
@@ -107,6 +1001,8 @@ component! {
     }
 }
 impl FuelTank {
+    pub const CAPACITY: u64 = 20;
+
     #[cfg(feature = "server")]
     pub fn new(id_generator: &mut EntityIdGenerator) -> Self {
         Self {
@@ -126,25 +1022,34 @@ component! {
     }
 }
 impl CargoHold {
+    pub const CAPACITY: u64 = 20;
+
     #[cfg(feature = "server")]
     pub fn new(id_generator: &mut EntityIdGenerator) -> Self {
         Self {
             id: id_generator.next().unwrap(),
             damaged: false,
-            inventory: CargoList::new(0, 0, 0, 0),
+            inventory: CargoList::default(),
         }
     }
 }
 /// A collection of items held in a cargo hold
 ///
 /// More-or-less an inventory, but also used in transfer orders
-#[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
-#[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug, Clone)]
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct CargoList {
     pub ice: u64,
     pub ore: u64,
     pub materials: u64,
+    /// Warheads carried as cargo, taking up cargo space
+    ///
+    /// Reloading a warhead mount takes the warhead out of cargo, so loaded
+    /// warheads are never also counted here
     pub warheads: u64,
 }
 impl CargoList {
@@ -156,6 +1061,56 @@ impl CargoList {
             warheads,
         }
     }
+
+    /// Total number of points of cargo
+    pub fn total(&self) -> u64 {
+        self.ice + self.ore + self.materials + self.warheads
+    }
+
+    /// Total number of points of cargo, or `None` if it overflows
+    pub fn checked_total(&self) -> Option<u64> {
+        self.ice
+            .checked_add(self.ore)?
+            .checked_add(self.materials)?
+            .checked_add(self.warheads)
+    }
+
+    /// Is there at least as much of everything in this list as in `other`?
+    pub fn contains(&self, other: &CargoList) -> bool {
+        self.ice >= other.ice
+            && self.ore >= other.ore
+            && self.materials >= other.materials
+            && self.warheads >= other.warheads
+    }
+
+    /// As much of this list as fits in `limit` points of cargo, taking ice,
+    /// then ore, then materials, then warheads
+    pub fn capped(&self, limit: u64) -> CargoList {
+        let mut left = limit;
+        let mut take = |amount: u64| {
+            let taken = amount.min(left);
+            left -= taken;
+            taken
+        };
+        CargoList::new(
+            take(self.ice),
+            take(self.ore),
+            take(self.materials),
+            take(self.warheads),
+        )
+    }
+}
+impl Add<&CargoList> for CargoList {
+    type Output = CargoList;
+
+    fn add(self, rhs: &CargoList) -> Self::Output {
+        Self::Output {
+            ice: self.ice + rhs.ice,
+            ore: self.ore + rhs.ore,
+            materials: self.materials + rhs.materials,
+            warheads: self.warheads + rhs.warheads,
+        }
+    }
 }
 
 component! {
@@ -170,6 +1125,9 @@ component! {
     }
 }
 impl Engine {
+    /// Points of other mass one engine can push
+    pub const THRUST: u64 = 25;
+
     #[cfg(feature = "server")]
     pub fn new(id_generator: &mut EntityIdGenerator) -> Self {
         Self {
@@ -187,10 +1145,34 @@ component! {
     /// hex away, guaranteed to hit at 0 hexes away; hit chance follows the
     /// inverse-fourth-power relationship (2 hexes = (2/3)^4 = ~0.20 hit
     /// chance)
+    ///
+    /// Each gun a stack fires past the first [`Gun::GUNS_PER_TURN`] in a
+    /// combat phase overheats, its hit chance multiplied by
+    /// [`Gun::OVERHEAT_PENALTY`] once more than the gun before it
     Gun<mass = 5> {
     }
 }
 impl Gun {
+    /// Number of guns a stack can fire in one combat phase before they start
+    /// to overheat
+    pub const GUNS_PER_TURN: u64 = 4;
+    /// Factor applied to the hit chance for each gun fired past the limit
+    pub const OVERHEAT_PENALTY: f64 = 0.5;
+    /// Farthest a gun is worth firing; beyond this, the hit chance is under 1%
+    pub const EFFECTIVE_RANGE: u64 = 3;
+
+    /// Chance to hit a target the given number of hexes away
+    pub fn hit_chance(distance: u64) -> f64 {
+        (2.0_f64 / 3.0).powf((distance * distance) as f64)
+    }
+
+    /// Factor applied to the hit chance of a gun fired after the stack has
+    /// already fired `guns_fired` guns this phase
+    pub fn overheat_factor(guns_fired: u64) -> f64 {
+        let excess = (guns_fired + 1).saturating_sub(Self::GUNS_PER_TURN);
+        Self::OVERHEAT_PENALTY.powf(excess as f64)
+    }
+
     #[cfg(feature = "server")]
     pub fn new(id_generator: &mut EntityIdGenerator) -> Self {
         Self {
@@ -225,7 +1207,7 @@ component! {
     /// A habitat for holding humans
     ///
     /// During the economic phase, can repair up to one item per habitat at a
-    /// cost of 1 point of materials
+    /// cost of 1 point of materials, under the default rules
     ///
     /// Additionally serves as a source of control; you gain control of
     /// anything in the same stack as one of your habitats
@@ -272,7 +1254,7 @@ component! {
     ///  - convert any amount of ore into materials at 2:1
     ///  - convert any amount of ice into fuel at 2:1
     ///  - repair any number of components in a single stack (costs 1 point of
-    ///    materials per component repaired)
+    ///    materials per component repaired, under the default rules)
     Factory<mass = 50> {
     }
 }
@@ -304,21 +1286,555 @@ impl ArmourPlate {
 
 /// A warhead
 ///
-/// Deals 5 points of damage
+/// Deals its damage as hits on detonation; warheads with a blast radius also
+/// damage stacks in the surrounding hexes, with damage falling off with
+/// distance
 #[cfg_attr(any(feature = "client", feature = "server"), derive(Deserialize))]
 #[cfg_attr(feature = "server", derive(Serialize))]
-#[derive(Debug, Clone)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Warhead {
     pub id: EntityId,
     pub position: vec2::Position,
     pub velocity: vec2::Displacement,
     pub owner: PlayerId,
+    pub blast_radius: u64,
+    /// Number of hits dealt to a stack at the detonation
+    pub damage: u8,
+    /// The round and phase the warhead was launched in, if known
+    pub created: Option<(u64, Phase)>,
 }
+impl Warhead {
+    /// Number of hits a standard warhead deals on detonation
+    pub const DAMAGE: u8 = 5;
 
-#[cfg(test)]
+    #[cfg(feature = "server")]
+    pub fn new(
+        id_generator: &mut EntityIdGenerator,
+        position: vec2::Position,
+        velocity: vec2::Displacement,
+        owner: PlayerId,
+    ) -> Self {
+        Self {
+            id: id_generator.next().unwrap(),
+            position,
+            velocity,
+            owner,
+            blast_radius: 0,
+            damage: Self::DAMAGE,
+            created: None,
+        }
+    }
+
+    /// Number of hits dealt to a stack the given number of hexes away from
+    /// the detonation
+    pub fn hits_at(&self, distance: u64) -> u64 {
+        if distance > self.blast_radius {
+            0
+        } else {
+            u64::from(self.damage) / (distance + 1)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
 mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
     use super::*;
 
+    fn warship(id_generator: &mut EntityIdGenerator) -> Stack {
+        let mut stack = Stack::new(
+            "Warship",
+            id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        for _ in 0..4 {
+            let gun = Gun::new(id_generator);
+            stack.guns_mut().insert(gun.id, gun);
+            let engine = Engine::new(id_generator);
+            stack.engines_mut().insert(engine.id, engine);
+            let tank = FuelTank::new(id_generator);
+            stack.fuel_tanks_mut().insert(tank.id, tank);
+        }
+        stack
+    }
+
     #[test]
-    fn test() {}
+    fn test_can_self_repair() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let gun = *stack.guns.keys().next().unwrap();
+        stack.guns_mut().get_mut(&gun).unwrap().damaged = true;
+        assert!(!stack.can_self_repair());
+
+        let mut habitat = Habitat::new(&mut id_generator, 0.into());
+        habitat.damaged = true;
+        let habitat_id = habitat.id;
+        stack.habitats_mut().insert(habitat.id, habitat);
+        assert!(!stack.can_self_repair());
+        stack.habitats_mut().get_mut(&habitat_id).unwrap().damaged = false;
+        assert!(stack.can_self_repair());
+
+        let mut stack = warship(&mut id_generator);
+        let factory = Factory::new(&mut id_generator);
+        stack.factories_mut().insert(factory.id, factory);
+        assert!(stack.can_self_repair());
+    }
+
+    #[test]
+    fn test_find_component() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let gun_id = *stack.guns.keys().next().unwrap();
+
+        let found = stack.find_component(gun_id).unwrap();
+        assert!(matches!(found, ComponentRef::Gun(_)));
+        assert_eq!(found.id(), gun_id);
+        assert_eq!(found.mass(), Gun::MASS);
+        assert!(stack.find_component(stack.id).is_none());
+        assert!(stack.find_component(id_generator.next().unwrap()).is_none());
+
+        stack.find_component_mut(gun_id).unwrap().set_damaged(true);
+        assert!(stack.guns[&gun_id].damaged);
+    }
+
+    #[test]
+    fn test_remove_component() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let gun_id = *stack.guns.keys().next().unwrap();
+        let count = stack.component_ids().len();
+
+        let removed = stack.remove_component(gun_id).unwrap();
+        assert!(matches!(removed, AnyComponent::Gun(_)));
+        assert_eq!(removed.id(), gun_id);
+        assert_eq!(stack.guns.len(), 3);
+        assert_eq!(stack.engines.len(), 4);
+        assert_eq!(stack.fuel_tanks.len(), 4);
+        assert_eq!(stack.component_ids().len(), count - 1);
+        assert!(stack.remove_component(gun_id).is_none());
+    }
+
+    #[test]
+    fn test_component_variants() {
+        // adding a component type breaks these matches until it's listed here;
+        // the owned, borrowed, and mutable views must all cover it
+        let mut id_generator = EntityIdGenerator::new();
+        let all = [
+            AnyComponent::FuelTank(FuelTank::new(&mut id_generator)),
+            AnyComponent::CargoHold(CargoHold::new(&mut id_generator)),
+            AnyComponent::Engine(Engine::new(&mut id_generator)),
+            AnyComponent::Gun(Gun::new(&mut id_generator)),
+            AnyComponent::WarheadMount(WarheadMount::new(&mut id_generator)),
+            AnyComponent::Habitat(Habitat::new(&mut id_generator, 0.into())),
+            AnyComponent::Miner(Miner::new(&mut id_generator)),
+            AnyComponent::Factory(Factory::new(&mut id_generator)),
+            AnyComponent::ArmourPlate(ArmourPlate::new(&mut id_generator)),
+        ];
+        let indices = all
+            .iter()
+            .map(|component| match component {
+                AnyComponent::FuelTank(_) => 0,
+                AnyComponent::CargoHold(_) => 1,
+                AnyComponent::Engine(_) => 2,
+                AnyComponent::Gun(_) => 3,
+                AnyComponent::WarheadMount(_) => 4,
+                AnyComponent::Habitat(_) => 5,
+                AnyComponent::Miner(_) => 6,
+                AnyComponent::Factory(_) => 7,
+                AnyComponent::ArmourPlate(_) => 8,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (0..all.len()).collect::<Vec<_>>());
+
+        let mut stack = warship(&mut id_generator);
+        for component in all {
+            let id = component.id();
+            stack.insert_component(component.clone());
+            let matching = match (&component, stack.find_component(id).unwrap()) {
+                (AnyComponent::FuelTank(_), ComponentRef::FuelTank(_))
+                | (AnyComponent::CargoHold(_), ComponentRef::CargoHold(_))
+                | (AnyComponent::Engine(_), ComponentRef::Engine(_))
+                | (AnyComponent::Gun(_), ComponentRef::Gun(_))
+                | (AnyComponent::WarheadMount(_), ComponentRef::WarheadMount(_))
+                | (AnyComponent::Habitat(_), ComponentRef::Habitat(_))
+                | (AnyComponent::Miner(_), ComponentRef::Miner(_))
+                | (AnyComponent::Factory(_), ComponentRef::Factory(_))
+                | (AnyComponent::ArmourPlate(_), ComponentRef::ArmourPlate(_)) => true,
+                (
+                    AnyComponent::FuelTank(_)
+                    | AnyComponent::CargoHold(_)
+                    | AnyComponent::Engine(_)
+                    | AnyComponent::Gun(_)
+                    | AnyComponent::WarheadMount(_)
+                    | AnyComponent::Habitat(_)
+                    | AnyComponent::Miner(_)
+                    | AnyComponent::Factory(_)
+                    | AnyComponent::ArmourPlate(_),
+                    _,
+                ) => false,
+            };
+            assert!(matching);
+            assert_eq!(stack.find_component_mut(id).unwrap().id(), id);
+            assert_eq!(stack.remove_component(id).unwrap().id(), id);
+        }
+    }
+
+    #[test]
+    fn test_move_component() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut from = warship(&mut id_generator);
+        let mut to = warship(&mut id_generator);
+        let engine_id = *from.engines.keys().next().unwrap();
+        from.find_component_mut(engine_id)
+            .unwrap()
+            .set_damaged(true);
+
+        let engine = from.remove_component(engine_id).unwrap();
+        to.insert_component(engine);
+        assert!(from.find_component(engine_id).is_none());
+        let moved = to.find_component(engine_id).unwrap();
+        assert!(matches!(moved, ComponentRef::Engine(_)));
+        assert_eq!(moved.id(), engine_id);
+        assert!(moved.damaged());
+        assert_eq!(to.engines.len(), 5);
+    }
+
+    #[test]
+    fn test_mass_tracks_mutations() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let base = 4 * (Gun::MASS + Engine::MASS + FuelTank::MASS);
+        assert_eq!(stack.mass(), base);
+        assert_eq!(stack.mass_cache.get(), Some(&base));
+
+        let mutations: [fn(&mut Stack, &mut EntityIdGenerator); 6] = [
+            |stack, _| stack.add_fuel(7).unwrap(),
+            |stack, id_generator| {
+                stack.insert_component(AnyComponent::CargoHold(CargoHold::new(id_generator)))
+            },
+            |stack, _| stack.deposit_cargo(CargoList::new(1, 2, 3, 0)).unwrap(),
+            |stack, _| {
+                let gun_id = *stack.guns().keys().next().unwrap();
+                stack.remove_component(gun_id);
+            },
+            |stack, id_generator| {
+                let mut mount = WarheadMount::new(id_generator);
+                mount.loaded = true;
+                stack.launch_clamps_mut().insert(mount.id, mount);
+            },
+            |stack, _| {
+                for mount in stack.launch_clamps_mut().values_mut() {
+                    mount.loaded = false;
+                }
+            },
+        ];
+        for mutate in mutations {
+            mutate(&mut stack, &mut id_generator);
+            assert_eq!(stack.mass_cache.get(), None);
+            assert_eq!(stack.mass(), stack.computed_mass());
+        }
+        assert_eq!(
+            stack.mass(),
+            base + 7 + CargoHold::MASS + 6 - Gun::MASS + WarheadMount::MASS
+        );
+    }
+
+    #[test]
+    fn test_overfilled_hold() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = Stack::new(
+            "Freighter",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let mut overfilled = CargoHold::new(&mut id_generator);
+        overfilled.inventory = CargoList::new(15, 10, 0, 2);
+        let overfilled_id = overfilled.id;
+        stack.cargo_holds_mut().insert(overfilled.id, overfilled);
+        let empty = CargoHold::new(&mut id_generator);
+        let empty_id = empty.id;
+        stack.cargo_holds_mut().insert(empty.id, empty);
+
+        // deposits skip the overfilled hold rather than underflowing
+        stack.deposit_cargo(CargoList::new(0, 0, 5, 0)).unwrap();
+        assert_eq!(
+            stack.cargo_holds[&empty_id].inventory,
+            CargoList::new(0, 0, 5, 0)
+        );
+
+        // what no longer fits is dropped rather than panicking
+        stack.remove_component(empty_id);
+        assert_eq!(stack.normalize_cargo(), CargoList::new(0, 5, 0, 2));
+        assert_eq!(
+            stack.cargo_holds[&overfilled_id].inventory,
+            CargoList::new(15, 5, 0, 0)
+        );
+        assert_eq!(stack.mass(), CargoHold::MASS + 20);
+    }
+
+    #[test]
+    fn test_normalize_cargo() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = Stack::new(
+            "Freighter",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let mut holds = (0..3)
+            .map(|_| CargoHold::new(&mut id_generator))
+            .collect::<Vec<_>>();
+        holds[0].inventory = CargoList::new(0, 3, 0, 1);
+        holds[1].inventory = CargoList::new(15, 0, 2, 0);
+        holds[2].inventory = CargoList::new(4, 0, 6, 0);
+        let ids = holds.iter().map(|hold| hold.id).collect::<Vec<_>>();
+        for hold in holds {
+            stack.cargo_holds_mut().insert(hold.id, hold);
+        }
+        let total = stack.cargo();
+
+        assert_eq!(stack.normalize_cargo(), CargoList::default());
+        assert_eq!(stack.cargo(), total);
+        assert_eq!(
+            stack.cargo_holds[&ids[0]].inventory,
+            CargoList::new(19, 1, 0, 0)
+        );
+        assert_eq!(
+            stack.cargo_holds[&ids[1]].inventory,
+            CargoList::new(0, 2, 8, 1)
+        );
+        assert_eq!(stack.cargo_holds[&ids[2]].inventory, CargoList::default());
+
+        let layout = ids
+            .iter()
+            .map(|id| stack.cargo_holds[id].inventory.clone())
+            .collect::<Vec<_>>();
+        stack.normalize_cargo();
+        for (id, inventory) in ids.iter().zip(layout) {
+            assert_eq!(stack.cargo_holds[id].inventory, inventory);
+        }
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw_cargo() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = Stack::new(
+            "Freighter",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let ids = (0..2)
+            .map(|_| {
+                let hold = CargoHold::new(&mut id_generator);
+                let id = hold.id;
+                stack.cargo_holds_mut().insert(id, hold);
+                id
+            })
+            .collect::<Vec<_>>();
+
+        stack.deposit_cargo(CargoList::new(12, 0, 10, 0)).unwrap();
+        assert_eq!(
+            stack.cargo_holds[&ids[0]].inventory,
+            CargoList::new(12, 0, 8, 0)
+        );
+        assert_eq!(
+            stack.cargo_holds[&ids[1]].inventory,
+            CargoList::new(0, 0, 2, 0)
+        );
+
+        let before = stack.clone();
+        assert_eq!(
+            stack.deposit_cargo(CargoList::new(0, 19, 0, 0)),
+            Err(OrderError::CapacityExceeded)
+        );
+        assert_eq!(
+            stack.withdraw_cargo(CargoList::new(13, 0, 0, 0)),
+            Err(OrderError::InsufficientCargo)
+        );
+        for id in &ids {
+            assert_eq!(
+                stack.cargo_holds[id].inventory,
+                before.cargo_holds[id].inventory
+            );
+        }
+
+        stack.withdraw_cargo(CargoList::new(2, 0, 9, 0)).unwrap();
+        assert_eq!(
+            stack.cargo_holds[&ids[0]].inventory,
+            CargoList::new(10, 0, 0, 0)
+        );
+        assert_eq!(
+            stack.cargo_holds[&ids[1]].inventory,
+            CargoList::new(0, 0, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_burn_cost_with_mass() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = Stack::new(
+            "Tug",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 0.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        for _ in 0..4 {
+            let engine = Engine::new(&mut id_generator);
+            stack.engines_mut().insert(engine.id, engine);
+        }
+        let tank = FuelTank::new(&mut id_generator);
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+        stack.add_fuel(20).unwrap();
+        assert_eq!(stack.mass(), 51);
+
+        let delta = vec2::Displacement::new(1, 0);
+        assert_eq!(stack.burn_cost(delta), Some(2));
+        assert_eq!(stack.burn_cost_with_mass(delta, 51), Some(2));
+        assert_eq!(stack.burn_cost_with_mass(delta, 71), Some(3));
+        assert_eq!(stack.burn_cost_with_mass(delta, 101), Some(4));
+        assert_eq!(stack.burn_cost_with_mass(delta, 200), None);
+    }
+
+    #[test]
+    fn test_visual_radius() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let mut radius = stack.visual_radius();
+        for _ in 0..5 {
+            let plate = ArmourPlate::new(&mut id_generator);
+            stack.armour_plates_mut().insert(plate.id, plate);
+            assert!(stack.visual_radius() > radius);
+            radius = stack.visual_radius();
+        }
+        let factory = Factory::new(&mut id_generator);
+        stack.factories_mut().insert(factory.id, factory);
+        assert!(stack.visual_radius() > radius);
+    }
+
+    #[test]
+    fn test_mobility_status() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        assert_eq!(stack.mobility_status(), MobilityStatus::OutOfFuel);
+
+        stack.add_fuel(10).unwrap();
+        let MobilityStatus::Mobile { max_delta } = stack.mobility_status() else {
+            panic!("fuelled warship not mobile");
+        };
+        assert_eq!(max_delta, stack.max_delta());
+
+        for _ in 0..20 {
+            let factory = Factory::new(&mut id_generator);
+            stack.factories_mut().insert(factory.id, factory);
+        }
+        assert_eq!(stack.mobility_status(), MobilityStatus::Overweight);
+
+        stack.engines_mut().clear();
+        assert_eq!(stack.mobility_status(), MobilityStatus::NoEngines);
+    }
+
+    #[test]
+    fn test_component_display() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut tank = FuelTank::new(&mut id_generator);
+        assert_eq!(FuelTank::NAME, "FuelTank");
+        assert_eq!(tank.to_string(), "FuelTank");
+        tank.damaged = true;
+        assert_eq!(tank.to_string(), "FuelTank (damaged)");
+    }
+
+    #[test]
+    fn test_silhouette() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = Stack::new(
+            "Gunboat",
+            &mut id_generator,
+            vec2::Position::new(1, 2),
+            vec2::Displacement::new(0, 1),
+            0.into(),
+        );
+        for _ in 0..2 {
+            let gun = Gun::new(&mut id_generator);
+            stack.guns_mut().insert(gun.id, gun);
+        }
+        let engine = Engine::new(&mut id_generator);
+        stack.engines_mut().insert(engine.id, engine);
+        let mut tank = FuelTank::new(&mut id_generator);
+        tank.fuel = 15;
+        stack.fuel_tanks_mut().insert(tank.id, tank);
+
+        let silhouette = stack.silhouette();
+        assert_eq!(silhouette.guns, 2);
+        assert_eq!(silhouette.engines, 1);
+        assert_eq!(silhouette.fuel_tanks, 1);
+        assert_eq!(silhouette.armour_plates, 0);
+        assert_eq!(silhouette.position, stack.position);
+        assert_eq!(silhouette.velocity, stack.velocity);
+    }
+
+    #[test]
+    fn test_overheat_factor() {
+        for guns_fired in 0..Gun::GUNS_PER_TURN {
+            assert_eq!(Gun::overheat_factor(guns_fired), 1.0);
+        }
+        assert_eq!(
+            Gun::overheat_factor(Gun::GUNS_PER_TURN),
+            Gun::OVERHEAT_PENALTY
+        );
+        assert_eq!(
+            Gun::overheat_factor(Gun::GUNS_PER_TURN + 1),
+            Gun::OVERHEAT_PENALTY * Gun::OVERHEAT_PENALTY
+        );
+    }
+
+    #[test]
+    fn test_apply_hit_deterministic() {
+        for seed in 0..16 {
+            let mut first = warship(&mut EntityIdGenerator::new());
+            let mut second = warship(&mut EntityIdGenerator::new());
+            let mut first_rng = ChaCha20Rng::seed_from_u64(seed);
+            let mut second_rng = ChaCha20Rng::seed_from_u64(seed);
+            for _ in 0..8 {
+                assert_eq!(
+                    first.apply_hit(&mut first_rng),
+                    second.apply_hit(&mut second_rng)
+                );
+            }
+            assert_eq!(first.component_ids(), second.component_ids());
+        }
+    }
+
+    #[test]
+    fn test_apply_hit_armour_first() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut stack = warship(&mut id_generator);
+        let plate = ArmourPlate::new(&mut id_generator);
+        let plate_id = plate.id;
+        stack.armour_plates_mut().insert(plate.id, plate);
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+
+        assert_eq!(
+            stack.apply_hit(&mut rng),
+            HitOutcome {
+                hit: true,
+                component: Some(plate_id),
+                destroyed: false,
+                draw: Some(0),
+            }
+        );
+        assert!(stack.armour_plates[&plate_id].damaged);
+    }
 }
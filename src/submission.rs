@@ -0,0 +1,143 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Turn submission
+//!
+//! Collects each player's orders for a phase until everyone is ready
+
+use std::collections::BTreeMap;
+
+use crate::{
+    order::{OrderError, PlayerTurn},
+    GameState, Phase, PlayerId,
+};
+
+/// The turns submitted so far for one phase
+///
+/// An empty turn is a pass: it counts as a submission like any other, so
+/// players with nothing to do don't hold up resolution
+#[derive(Debug, Clone)]
+pub struct SubmissionTracker {
+    round: u64,
+    phase: Phase,
+    turns: BTreeMap<PlayerId, PlayerTurn>,
+}
+impl SubmissionTracker {
+    /// Start collecting turns for the state's current phase
+    pub fn new(state: &GameState) -> Self {
+        Self {
+            round: state.round,
+            phase: state.phase,
+            turns: BTreeMap::new(),
+        }
+    }
+
+    /// Accept a player's turn, replacing any turn they submitted earlier
+    pub fn submit(
+        &mut self,
+        state: &GameState,
+        player: PlayerId,
+        turn: PlayerTurn,
+    ) -> Result<(), OrderError> {
+        if state.is_over() {
+            return Err(OrderError::GameOver);
+        }
+        if (state.round, state.phase) != (self.round, self.phase) {
+            return Err(OrderError::WrongPhase);
+        }
+        turn.validate_phase(self.phase, &state.rules)?;
+        self.turns.insert(player, turn);
+        Ok(())
+    }
+
+    pub fn has_submitted(&self, player: PlayerId) -> bool {
+        self.turns.contains_key(&player)
+    }
+
+    /// Has every player still in the game submitted a turn?
+    pub fn is_complete(&self, state: &GameState) -> bool {
+        state
+            .surviving_players()
+            .iter()
+            .all(|player| self.has_submitted(*player))
+    }
+
+    /// The submitted turns, in player order, ready to resolve
+    pub fn into_turns(self) -> Vec<(PlayerId, PlayerTurn)> {
+        self.turns.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+    use crate::{
+        stack::{Habitat, Stack},
+        vec2, EntityIdGenerator,
+    };
+
+    #[test]
+    fn test_pass() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        for player in [0, 1] {
+            let mut stack = Stack::new(
+                "Station",
+                &mut id_generator,
+                vec2::Position::new(0, 0),
+                vec2::Displacement::new(0, 0),
+                player.into(),
+            );
+            let habitat = Habitat::new(&mut id_generator, player.into());
+            stack.habitats_mut().insert(habitat.id, habitat);
+            state.stacks.insert(stack.id, stack);
+        }
+
+        let mut tracker = SubmissionTracker::new(&state);
+        assert_eq!(
+            tracker.submit(&state, 0.into(), PlayerTurn::Combat(Vec::new())),
+            Err(OrderError::WrongPhase)
+        );
+        assert!(!tracker.has_submitted(0.into()));
+
+        tracker
+            .submit(&state, 0.into(), PlayerTurn::Economic(Vec::new()))
+            .unwrap();
+        assert!(tracker.has_submitted(0.into()));
+        assert!(!tracker.is_complete(&state));
+        tracker
+            .submit(&state, 1.into(), PlayerTurn::Economic(Vec::new()))
+            .unwrap();
+        assert!(tracker.is_complete(&state));
+
+        let report = state.resolve_turn(
+            &tracker.into_turns(),
+            &mut id_generator,
+            &mut ChaCha20Rng::seed_from_u64(0),
+        );
+        assert_eq!(
+            report.orders,
+            vec![(0.into(), Vec::new()), (1.into(), Vec::new())]
+        );
+        assert_eq!(state.phase, Phase::Ordnance);
+    }
+}
@@ -19,6 +19,7 @@
 
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+#[cfg(any(feature = "client", feature = "server"))]
 use serde::{Deserialize, Serialize};
 
 /// An axial, hex-grid, point-up 2-d vector position
@@ -29,7 +30,8 @@ use serde::{Deserialize, Serialize};
     any(feature = "client", feature = "server"),
     derive(Serialize, Deserialize)
 )]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
     pub q: i64,
     pub r: i64,
@@ -38,16 +40,132 @@ impl Position {
     pub fn new(q: i64, r: i64) -> Self {
         Self { q, r }
     }
+    /// `[q, r]`
+    pub fn to_array(&self) -> [i64; 2] {
+        [self.q, self.r]
+    }
+    pub fn from_array([q, r]: [i64; 2]) -> Self {
+        Self::new(q, r)
+    }
+    /// `(q, r)`
+    pub fn to_tuple(&self) -> (i64, i64) {
+        (self.q, self.r)
+    }
+    pub fn from_tuple((q, r): (i64, i64)) -> Self {
+        Self::new(q, r)
+    }
+    /// The hex containing fractional axial coordinates
+    pub fn from_fractional(q: f64, r: f64) -> Self {
+        let (q, r) = hex_round(q, r);
+        Self::new(q, r)
+    }
+    /// The hex `distance` hexes from the origin whose bearing is closest to
+    /// `angle`, in radians clockwise from the +x axis on screen
+    pub fn from_polar(distance: u64, angle: f64) -> Self {
+        let (q, r) = rect_to_hex_fractional(angle.cos(), angle.sin(), Orientation::PointyTop);
+        let norm = q.abs().max(r.abs()).max((q + r).abs());
+        let scale = distance as f64 / norm;
+        Self::from_fractional(q * scale, r * scale)
+    }
+    /// Rectangular coordinates of the hex's centre, with hexes of the given
+    /// orientation
+    pub fn to_rect(&self, orientation: Orientation) -> (f64, f64) {
+        hex_to_rect(self.q, self.r, orientation)
+    }
+    /// The hex containing rectangular coordinates, with hexes of the given
+    /// orientation
+    pub fn from_rect(x: f64, y: f64, orientation: Orientation) -> Self {
+        let (q, r) = rect_to_hex(x, y, orientation);
+        Self::new(q, r)
+    }
+    /// Number of hexes between two positions
+    pub fn distance(&self, other: Position) -> u64 {
+        (*self - other).norm()
+    }
+    /// The displacement of this position from the origin
+    pub fn to_displacement(&self) -> Displacement {
+        (*self).into()
+    }
+    /// Every hex at most `radius` hexes away, including this one
+    pub fn hexes_within(&self, radius: u64) -> Vec<Position> {
+        let radius = radius as i64;
+        let mut hexes = Vec::new();
+        for q in -radius..=radius {
+            for r in (-radius).max(-q - radius)..=radius.min(-q + radius) {
+                hexes.push(*self + Displacement::new(q, r));
+            }
+        }
+        hexes
+    }
+    /// The `length` hexes in a straight line from this one, not including this
+    /// one
+    pub fn line(&self, direction: Direction, length: u64) -> Vec<Position> {
+        let step = Displacement::from(direction);
+        (1..=length as i64).map(|i| *self + step * i).collect()
+    }
+    /// The hexes on the straight line from this one to `other`, including both
+    /// ends
+    pub fn line_to(&self, other: Position) -> Vec<Position> {
+        let length = self.distance(other);
+        // nudge off hex edges so ties always round the same way
+        let (q_start, r_start) = (self.q as f64 + 1e-6, self.r as f64 + 1e-6);
+        let (q_end, r_end) = (other.q as f64 + 1e-6, other.r as f64 + 1e-6);
+        (0..=length)
+            .map(|step| {
+                let t = if length == 0 {
+                    0.0
+                } else {
+                    step as f64 / length as f64
+                };
+                let (q, r) = hex_round(
+                    q_start + (q_end - q_start) * t,
+                    r_start + (r_end - r_start) * t,
+                );
+                Position::new(q, r)
+            })
+            .collect()
+    }
+    /// The hexes in a 60-degree cone pointing in a direction, out to `length`
+    /// hexes away, not including this one
+    pub fn cone(&self, direction: Direction, length: u64) -> Vec<Position> {
+        let mut hexes = Vec::new();
+        for (distance, corner) in (1..).zip(self.line(direction, length)) {
+            hexes.push(corner);
+            for side in [direction.rotate_clockwise(2), direction.rotate_clockwise(4)] {
+                hexes.extend(corner.line(side, distance / 2));
+            }
+        }
+        hexes
+    }
+}
+/// The smallest and largest axial coordinates over a set of positions, as the
+/// (min, max) corners of the box enclosing them, if there are any
+pub fn bounding_box(positions: impl IntoIterator<Item = Position>) -> Option<(Position, Position)> {
+    positions.into_iter().fold(None, |corners, position| {
+        Some(match corners {
+            None => (position, position),
+            Some((min, max)) => (
+                Position::new(min.q.min(position.q), min.r.min(position.r)),
+                Position::new(max.q.max(position.q), max.r.max(position.r)),
+            ),
+        })
+    })
 }
 impl From<(f64, f64)> for Position {
     fn from(value: (f64, f64)) -> Self {
-        let coordinates = rect_to_hex(value.0, value.1);
+        let coordinates = rect_to_hex(value.0, value.1, Orientation::default());
         Self::new(coordinates.0, coordinates.1)
     }
 }
 impl From<Position> for (f64, f64) {
     fn from(value: Position) -> Self {
-        hex_to_rect(value.q, value.r)
+        value.to_rect(Orientation::default())
+    }
+}
+impl From<Displacement> for Position {
+    /// The position displaced from the origin
+    fn from(value: Displacement) -> Self {
+        Self::new(value.q, value.r)
     }
 }
 impl AddAssign<Displacement> for Position {
@@ -80,6 +198,13 @@ impl Sub<Displacement> for Position {
         value
     }
 }
+impl Sub<Position> for Position {
+    type Output = Displacement;
+
+    fn sub(self, rhs: Position) -> Self::Output {
+        Displacement::new(self.q - rhs.q, self.r - rhs.r)
+    }
+}
 
 /// An axial, hex-grid, point-up 2-d vector displacement
 ///
@@ -89,28 +214,115 @@ impl Sub<Displacement> for Position {
     any(feature = "client", feature = "server"),
     derive(Serialize, Deserialize)
 )]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Displacement {
     pub q: i64,
     pub r: i64,
 }
 impl Displacement {
+    pub const ZERO: Displacement = Displacement { q: 0, r: 0 };
+
     pub fn new(q: i64, r: i64) -> Self {
         Self { q, r }
     }
+    /// `[q, r]`
+    pub fn to_array(&self) -> [i64; 2] {
+        [self.q, self.r]
+    }
+    pub fn from_array([q, r]: [i64; 2]) -> Self {
+        Self::new(q, r)
+    }
+    /// `(q, r)`
+    pub fn to_tuple(&self) -> (i64, i64) {
+        (self.q, self.r)
+    }
+    pub fn from_tuple((q, r): (i64, i64)) -> Self {
+        Self::new(q, r)
+    }
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+    /// Rectangular equivalent, with hexes of the given orientation
+    pub fn to_rect(&self, orientation: Orientation) -> (f64, f64) {
+        hex_to_rect(self.q, self.r, orientation)
+    }
+    /// The displacement nearest to a rectangular one, with hexes of the given
+    /// orientation
+    pub fn from_rect(x: f64, y: f64, orientation: Orientation) -> Self {
+        let (q, r) = rect_to_hex(x, y, orientation);
+        Self::new(q, r)
+    }
+    /// Angle of the displacement in radians, from the +x axis towards the +y
+    /// axis of its rectangular equivalent (clockwise on screen)
+    pub fn bearing(&self) -> f64 {
+        let (x, y) = self.to_rect(Orientation::default());
+        y.atan2(x)
+    }
+    /// The unit direction closest to this displacement, if it isn't zero
+    pub fn nearest_direction(&self) -> Option<Direction> {
+        if self.is_zero() {
+            return None;
+        }
+        let sector = (self.bearing() / (std::f64::consts::PI / 3.0)).round() as i64;
+        Some(Direction::ALL[sector.rem_euclid(6) as usize])
+    }
     pub fn norm(&self) -> u64 {
         (self.q.unsigned_abs() + self.r.unsigned_abs() + (self.q + self.r).unsigned_abs()) / 2
     }
+    /// The norm, or `None` if the displacement is too large to measure
+    pub fn checked_norm(&self) -> Option<u64> {
+        let s = self.q.checked_add(self.r)?;
+        Some(
+            self.q
+                .unsigned_abs()
+                .max(self.r.unsigned_abs())
+                .max(s.unsigned_abs()),
+        )
+    }
+    /// The sum of two displacements, or `None` if it overflows
+    pub fn checked_add(&self, other: Displacement) -> Option<Displacement> {
+        Some(Displacement::new(
+            self.q.checked_add(other.q)?,
+            self.r.checked_add(other.r)?,
+        ))
+    }
+    /// The difference of two displacements, or `None` if it overflows
+    pub fn checked_sub(&self, other: Displacement) -> Option<Displacement> {
+        Some(Displacement::new(
+            self.q.checked_sub(other.q)?,
+            self.r.checked_sub(other.r)?,
+        ))
+    }
+    /// The displacement scaled by `factor`, or `None` if it overflows
+    pub fn checked_mul(&self, factor: i64) -> Option<Displacement> {
+        Some(Displacement::new(
+            self.q.checked_mul(factor)?,
+            self.r.checked_mul(factor)?,
+        ))
+    }
+    /// The displacement rotated `sixths` sixths of a turn clockwise
+    pub fn rotate_clockwise(&self, sixths: usize) -> Displacement {
+        (0..sixths % 6).fold(*self, |rotated, _| {
+            Displacement::new(-rotated.r, rotated.q + rotated.r)
+        })
+    }
 }
 impl From<(f64, f64)> for Displacement {
     fn from(value: (f64, f64)) -> Self {
-        let coordinates = rect_to_hex(value.0, value.1);
+        let coordinates = rect_to_hex(value.0, value.1, Orientation::default());
         Self::new(coordinates.0, coordinates.1)
     }
 }
 impl From<Displacement> for (f64, f64) {
     fn from(value: Displacement) -> Self {
-        hex_to_rect(value.q, value.r)
+        value.to_rect(Orientation::default())
+    }
+}
+impl From<Position> for Displacement {
+    /// The displacement of the position from the origin
+    fn from(value: Position) -> Self {
+        Self::new(value.q, value.r)
     }
 }
 impl Neg for Displacement {
@@ -200,17 +412,82 @@ impl Div<i64> for Displacement {
     }
 }
 
+/// One of the six directions to a neighbouring hex
+#[cfg_attr(
+    any(feature = "client", feature = "server"),
+    derive(Serialize, Deserialize)
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    East,
+    SouthEast,
+    SouthWest,
+    West,
+    NorthWest,
+    NorthEast,
+}
+impl Direction {
+    /// All directions, clockwise from east
+    pub const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+        Direction::NorthEast,
+    ];
+
+    /// The direction `sixths` sixths of a turn clockwise from this one
+    pub fn rotate_clockwise(&self, sixths: usize) -> Direction {
+        let index = Self::ALL.iter().position(|other| other == self).unwrap();
+        Self::ALL[(index + sixths) % Self::ALL.len()]
+    }
+}
+impl From<Direction> for Displacement {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::East => Displacement::new(1, 0),
+            Direction::SouthEast => Displacement::new(0, 1),
+            Direction::SouthWest => Displacement::new(-1, 1),
+            Direction::West => Displacement::new(-1, 0),
+            Direction::NorthWest => Displacement::new(0, -1),
+            Direction::NorthEast => Displacement::new(1, -1),
+        }
+    }
+}
+
+/// Which way up hexes are drawn when converting to rectangular coordinates
+///
+/// The game uses point-up hexes; flat-top is for tools that expect them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    #[default]
+    PointyTop,
+    FlatTop,
+}
+
 /// convert to rectangular coordinates
-fn hex_to_rect(q: i64, r: i64) -> (f64, f64) {
-    (
-        3.0_f64.sqrt() * q as f64 + 3.0_f64.sqrt() / 2.0 * r as f64,
-        3.0 / 2.0 * r as f64,
-    )
+fn hex_to_rect(q: i64, r: i64, orientation: Orientation) -> (f64, f64) {
+    let (q, r) = (q as f64, r as f64);
+    match orientation {
+        Orientation::PointyTop => (3.0_f64.sqrt() * q + 3.0_f64.sqrt() / 2.0 * r, 3.0 / 2.0 * r),
+        Orientation::FlatTop => (3.0 / 2.0 * q, 3.0_f64.sqrt() / 2.0 * q + 3.0_f64.sqrt() * r),
+    }
 }
 /// convert from rectangular coordinates
-fn rect_to_hex(x: f64, y: f64) -> (i64, i64) {
-    let q_frac = 3.0_f64.sqrt() * x - 1.0 / 3.0 * y;
-    let r_frac = 2.0 / 3.0 * y;
+fn rect_to_hex(x: f64, y: f64, orientation: Orientation) -> (i64, i64) {
+    let (q_frac, r_frac) = rect_to_hex_fractional(x, y, orientation);
+    hex_round(q_frac, r_frac)
+}
+/// convert from rectangular coordinates, without rounding to a hex
+fn rect_to_hex_fractional(x: f64, y: f64, orientation: Orientation) -> (f64, f64) {
+    match orientation {
+        Orientation::PointyTop => (3.0_f64.sqrt() / 3.0 * x - 1.0 / 3.0 * y, 2.0 / 3.0 * y),
+        Orientation::FlatTop => (2.0 / 3.0 * x, -1.0 / 3.0 * x + 3.0_f64.sqrt() / 3.0 * y),
+    }
+}
+/// round fractional hex coordinates to the hex containing them
+fn hex_round(q_frac: f64, r_frac: f64) -> (i64, i64) {
     let s_frac = -q_frac - r_frac;
 
     let q = q_frac.round();
@@ -267,6 +544,14 @@ mod tests {
         assert_eq!(pos.q, 5 - displacement.q);
         assert_eq!(pos.r, 6 - displacement.r);
 
+        let a = Position::new(5, 6);
+        let b = Position::new(1, 3);
+        let result = a - b;
+        assert_eq!(result.q, a.q - b.q);
+        assert_eq!(result.r, a.r - b.r);
+        assert_eq!(a.distance(b), 7);
+        assert_eq!(b.distance(a), 7);
+
         let a = Position::new(1, 2);
         let b = Position::new(1, 2);
         let c = Position::new(1, 3);
@@ -276,6 +561,105 @@ mod tests {
         assert!(a != d);
     }
 
+    #[test]
+    fn test_position_displacement_conversion() {
+        let position = Position::new(-4, 7);
+        let displacement = position.to_displacement();
+        assert_eq!(displacement, Displacement::new(-4, 7));
+        assert_eq!(Displacement::from(position), displacement);
+        assert_eq!(Position::from(displacement), position);
+        assert_eq!(Position::new(0, 0) + displacement, position);
+    }
+
+    #[test]
+    fn test_hexes_within() {
+        let centre = Position::new(3, -2);
+        assert_eq!(centre.hexes_within(0), vec![centre]);
+        for radius in 0..5 {
+            let hexes = centre.hexes_within(radius);
+            assert_eq!(hexes.len() as u64, 3 * radius * (radius + 1) + 1);
+            assert!(hexes.iter().all(|hex| hex.distance(centre) <= radius));
+        }
+    }
+
+    #[test]
+    fn test_cone() {
+        let origin = Position::new(1, 1);
+        assert_eq!(
+            origin.line(Direction::West, 2),
+            vec![Position::new(0, 1), Position::new(-1, 1)]
+        );
+        for direction in Direction::ALL {
+            for length in 0..6_u64 {
+                let cone = origin.cone(direction, length);
+                let expected = (1..=length).map(|k| 1 + 2 * (k / 2)).sum::<u64>();
+                assert_eq!(cone.len() as u64, expected);
+                assert!(cone
+                    .iter()
+                    .all(|hex| (1..=length).contains(&hex.distance(origin))));
+                assert!(origin
+                    .line(direction, length)
+                    .iter()
+                    .all(|hex| cone.contains(hex)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_array_and_tuple_conversions() {
+        let position = Position::new(3, -7);
+        assert_eq!(position.to_array(), [3, -7]);
+        assert_eq!(position.to_tuple(), (3, -7));
+        assert_eq!(Position::from_array(position.to_array()), position);
+        assert_eq!(Position::from_tuple(position.to_tuple()), position);
+
+        let displacement = Displacement::new(-2, 5);
+        assert_eq!(displacement.to_array(), [-2, 5]);
+        assert_eq!(displacement.to_tuple(), (-2, 5));
+        assert_eq!(
+            Displacement::from_array(displacement.to_array()),
+            displacement
+        );
+        assert_eq!(
+            Displacement::from_tuple(displacement.to_tuple()),
+            displacement
+        );
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        assert_eq!(bounding_box([]), None);
+        assert_eq!(
+            bounding_box([Position::new(1, -2)]),
+            Some((Position::new(1, -2), Position::new(1, -2)))
+        );
+        assert_eq!(
+            bounding_box([
+                Position::new(3, -1),
+                Position::new(-2, 4),
+                Position::new(0, -5)
+            ]),
+            Some((Position::new(-2, -5), Position::new(3, 4)))
+        );
+    }
+
+    #[test]
+    fn test_line_to() {
+        let origin = Position::new(1, 1);
+        assert_eq!(origin.line_to(origin), vec![origin]);
+        for direction in Direction::ALL {
+            let mut expected = vec![origin];
+            expected.extend(origin.line(direction, 3));
+            assert_eq!(origin.line_to(expected[3]), expected);
+        }
+
+        let end = Position::new(5, -2);
+        let line = origin.line_to(end);
+        assert_eq!(line.len() as u64, origin.distance(end) + 1);
+        assert_eq!((line[0], line[line.len() - 1]), (origin, end));
+        assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+    }
+
     #[test]
     fn test_displacement_construction() {
         let value = Displacement::new(5, 6);
@@ -353,4 +737,68 @@ mod tests {
         assert!(a != c);
         assert!(a != d);
     }
+
+    #[test]
+    fn test_displacement_rotation() {
+        for direction in Direction::ALL {
+            for sixths in 0..12 {
+                assert_eq!(
+                    Displacement::from(direction).rotate_clockwise(sixths),
+                    Displacement::from(direction.rotate_clockwise(sixths))
+                );
+            }
+        }
+        let displacement = Displacement::new(3, -1);
+        assert_eq!(displacement.rotate_clockwise(3), -displacement);
+        assert_eq!(displacement.rotate_clockwise(1).norm(), displacement.norm());
+    }
+
+    #[test]
+    fn test_orientation() {
+        let step = Displacement::new(1, 0);
+        let (x, y) = step.to_rect(Orientation::PointyTop);
+        assert!((x - 3.0_f64.sqrt()).abs() < 1e-9 && y.abs() < 1e-9);
+        let (x, y) = step.to_rect(Orientation::FlatTop);
+        assert!((x - 1.5).abs() < 1e-9 && (y - 3.0_f64.sqrt() / 2.0).abs() < 1e-9);
+        assert_eq!(
+            <(f64, f64)>::from(step),
+            step.to_rect(Orientation::PointyTop)
+        );
+
+        for orientation in [Orientation::PointyTop, Orientation::FlatTop] {
+            for position in Position::new(0, 0).hexes_within(3) {
+                let (x, y) = position.to_rect(orientation);
+                assert_eq!(Position::from_rect(x, y, orientation), position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_polar() {
+        assert_eq!(Position::from_polar(16, 0.0), Position::new(16, 0));
+        for step in 0..36 {
+            let angle = step as f64 / 36.0 * std::f64::consts::TAU;
+            let position = Position::from_polar(40, angle);
+            assert_eq!(position.to_displacement().norm(), 40);
+            let offset =
+                (position.to_displacement().bearing() - angle).rem_euclid(std::f64::consts::TAU);
+            assert!(offset.min(std::f64::consts::TAU - offset) < 0.03);
+        }
+    }
+
+    #[test]
+    fn test_nearest_direction() {
+        for direction in Direction::ALL {
+            let displacement = Displacement::from(direction);
+            assert_eq!(displacement.norm(), 1);
+            assert_eq!(displacement.nearest_direction(), Some(direction));
+            assert_eq!((displacement * 3).nearest_direction(), Some(direction));
+        }
+        assert_eq!(Displacement::new(1, 0).bearing(), 0.0);
+        assert_eq!(
+            Displacement::new(3, -1).nearest_direction(),
+            Some(Direction::East)
+        );
+        assert_eq!(Displacement::ZERO.nearest_direction(), None);
+    }
 }
@@ -0,0 +1,380 @@
+// Copyright 2024 Justin Hu
+//
+// This file is part of Solar Dawn.
+//
+// Solar Dawn is free software: you can redistribute it and/or modify it under
+// the terms of the GNU Affero General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// Solar Dawn is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+// for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with Solar Dawn. If not, see <https://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Client-facing views of the game state
+
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "client")]
+use serde::Deserialize;
+#[cfg(feature = "server")]
+use serde::Serialize;
+
+use crate::{
+    astronomical::{MajorBody, MinorBody},
+    rules::RuleSet,
+    sorted_by_id,
+    stack::{CargoList, MobilityStatus, Stack, StackSilhouette, Warhead},
+    vec2, EntityId, GameOutcome, GameState, Phase, PlayerId,
+};
+
+/// The game state as shown to one player
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub struct PlayerView {
+    pub player: PlayerId,
+    pub major_bodies: HashMap<EntityId, MajorBody>,
+    pub minor_bodies: HashMap<EntityId, MinorBody>,
+    pub stacks: HashMap<EntityId, StackView>,
+    #[cfg_attr(
+        all(
+            feature = "compact-warheads",
+            any(feature = "client", feature = "server")
+        ),
+        serde(with = "crate::compact")
+    )]
+    #[cfg_attr(
+        all(feature = "compact-warheads", feature = "schema"),
+        schemars(with = "crate::compact::CompactWarheads")
+    )]
+    pub warheads: HashMap<EntityId, Warhead>,
+    pub round: u64,
+    pub phase: Phase,
+    pub rules: RuleSet,
+    pub outcome: Option<GameOutcome>,
+}
+
+/// A stack as shown to a player
+///
+/// Players see the full details of stacks they control, but only the outsides
+/// of everyone else's; both include who actually commands the stack, which
+/// may differ from the stack's owner
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone)]
+pub enum StackView {
+    Full {
+        controller: Option<PlayerId>,
+        stack: Box<Stack>,
+    },
+    Silhouette {
+        controller: Option<PlayerId>,
+        silhouette: StackSilhouette,
+    },
+}
+impl StackView {
+    /// Produce the view of the stack to show to a player
+    pub fn new(stack: &Stack, player: PlayerId) -> Self {
+        let controller = stack.controller();
+        if controller == Some(player) {
+            Self::Full {
+                controller,
+                stack: Box::new(stack.clone()),
+            }
+        } else {
+            Self::Silhouette {
+                controller,
+                silhouette: stack.silhouette(),
+            }
+        }
+    }
+
+    pub fn controller(&self) -> Option<PlayerId> {
+        match self {
+            StackView::Full { controller, .. } | StackView::Silhouette { controller, .. } => {
+                *controller
+            }
+        }
+    }
+}
+
+impl GameState {
+    /// Produce the view of the game to send to a player
+    ///
+    /// Only the stacks and warheads the player can see are included
+    pub fn view_for(&self, player: PlayerId) -> PlayerView {
+        let visible = self
+            .visible_entities_for(player)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        PlayerView {
+            player,
+            major_bodies: self.major_bodies.clone(),
+            minor_bodies: self.minor_bodies.clone(),
+            stacks: self
+                .stacks
+                .iter()
+                .filter(|(id, _)| visible.contains(id))
+                .map(|(id, stack)| (*id, StackView::new(stack, player)))
+                .collect(),
+            warheads: self
+                .warheads
+                .iter()
+                .filter(|(id, _)| visible.contains(id))
+                .map(|(id, warhead)| (*id, warhead.clone()))
+                .collect(),
+            round: self.round,
+            phase: self.phase,
+            rules: self.rules.clone(),
+            outcome: self.outcome,
+        }
+    }
+}
+
+impl GameState {
+    /// The hexes within sensor range of the stacks the player controls, or
+    /// `None` if sensor range is unlimited and everything is visible
+    pub fn visible_hexes(&self, player: PlayerId) -> Option<HashSet<vec2::Position>> {
+        let range = self.rules.sensor_range?;
+        Some(
+            self.stacks
+                .values()
+                .filter(|stack| stack.controller() == Some(player))
+                .flat_map(|stack| stack.position.hexes_within(range))
+                .collect(),
+        )
+    }
+
+    /// The stacks and warheads the player knows about, in id order
+    ///
+    /// The player's own stacks and warheads are always included; everyone
+    /// else's only if they're in a visible hex
+    pub fn visible_entities_for(&self, player: PlayerId) -> Vec<EntityId> {
+        let visible = self.visible_hexes(player);
+        let in_sight = |position: vec2::Position| {
+            visible
+                .as_ref()
+                .is_none_or(|hexes| hexes.contains(&position))
+        };
+        let mut ids = sorted_by_id(&self.stacks)
+            .into_iter()
+            .filter(|(_, stack)| stack.controller() == Some(player) || in_sight(stack.position))
+            .map(|(id, _)| id)
+            .chain(
+                sorted_by_id(&self.warheads)
+                    .into_iter()
+                    .filter(|(_, warhead)| warhead.owner == player || in_sight(warhead.position))
+                    .map(|(id, _)| id),
+            )
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+}
+
+/// Everything about a stack, for the stack details panel
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackReport {
+    pub id: EntityId,
+    pub name: String,
+    pub owner: PlayerId,
+    pub controller: Option<PlayerId>,
+    pub mass: u64,
+    pub fuel: u64,
+    pub fuel_capacity: u64,
+    pub cargo: CargoList,
+    pub cargo_capacity: u64,
+    pub max_delta: u64,
+    pub mobility: MobilityStatus,
+    /// Every component, in id order
+    pub components: Vec<ComponentReport>,
+}
+
+/// One component in a [`StackReport`]
+#[cfg_attr(feature = "client", derive(Deserialize))]
+#[cfg_attr(feature = "server", derive(Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentReport {
+    pub id: EntityId,
+    pub name: String,
+    pub damaged: bool,
+}
+
+impl GameState {
+    /// Everything about a stack, or `None` if it doesn't exist
+    pub fn stack_report(&self, stack: EntityId) -> Option<StackReport> {
+        let stack = self.stacks.get(&stack)?;
+        Some(StackReport {
+            id: stack.id,
+            name: stack.name.clone(),
+            owner: stack.owner,
+            controller: stack.controller(),
+            mass: stack.mass(),
+            fuel: stack.fuel(),
+            fuel_capacity: stack.fuel_capacity(),
+            cargo: stack.cargo(),
+            cargo_capacity: stack.cargo_capacity(),
+            max_delta: stack.max_delta(),
+            mobility: stack.mobility_status(),
+            components: stack
+                .components_with_ids()
+                .map(|(id, component)| ComponentReport {
+                    id,
+                    name: component.name().into(),
+                    damaged: component.damaged(),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use super::*;
+    use crate::{
+        stack::{CargoHold, Habitat},
+        EntityIdGenerator,
+    };
+
+    #[test]
+    fn test_view_shows_controller() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = Stack::new(
+            "Captured",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            0.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 1.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let view = state.view_for(1.into());
+        let StackView::Full { controller, stack } = &view.stacks[&id] else {
+            panic!("controlled stack not shown in full");
+        };
+        assert_eq!(stack.owner, 0.into());
+        assert_eq!(*controller, Some(1.into()));
+
+        let view = state.view_for(0.into());
+        assert_eq!(view.stacks[&id].controller(), Some(1.into()));
+    }
+
+    #[test]
+    fn test_view_hides_enemy_internals() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        let mut stack = Stack::new(
+            "Enemy",
+            &mut id_generator,
+            vec2::Position::new(0, 0),
+            vec2::Displacement::new(0, 0),
+            1.into(),
+        );
+        let habitat = Habitat::new(&mut id_generator, 1.into());
+        stack.habitats_mut().insert(habitat.id, habitat);
+        let id = stack.id;
+        state.stacks.insert(stack.id, stack);
+
+        let view = state.view_for(0.into());
+        let StackView::Silhouette { silhouette, .. } = &view.stacks[&id] else {
+            panic!("enemy stack shown in full");
+        };
+        assert_eq!(silhouette.habitats, 1);
+    }
+
+    #[test]
+    fn test_visible_entities() {
+        let mut id_generator = EntityIdGenerator::new();
+        let mut state = GameState::empty();
+        state.rules.sensor_range = Some(3);
+        let mut ids = Vec::new();
+        for (position, player) in [
+            (vec2::Position::new(0, 0), 0),
+            (vec2::Position::new(20, 0), 0),
+            (vec2::Position::new(2, 1), 1),
+            (vec2::Position::new(10, 0), 1),
+        ] {
+            let mut stack = Stack::new(
+                "Stack",
+                &mut id_generator,
+                position,
+                vec2::Displacement::new(0, 0),
+                player.into(),
+            );
+            let habitat = Habitat::new(&mut id_generator, player.into());
+            stack.habitats_mut().insert(habitat.id, habitat);
+            ids.push(stack.id);
+            state.stacks.insert(stack.id, stack);
+        }
+        let warhead = Warhead::new(
+            &mut id_generator,
+            vec2::Position::new(-3, 0),
+            vec2::Displacement::new(0, 0),
+            1.into(),
+        );
+        ids.push(warhead.id);
+        state.warheads.insert(warhead.id, warhead);
+
+        // the far enemy stack is out of sight, but the far own stack isn't
+        assert_eq!(
+            state.visible_entities_for(0.into()),
+            vec![ids[0], ids[1], ids[2], ids[4]]
+        );
+
+        let view = state.view_for(0.into());
+        assert!(!view.stacks.contains_key(&ids[3]));
+        assert_eq!(view.stacks.len(), 3);
+        assert!(view.warheads.contains_key(&ids[4]));
+
+        state.rules.sensor_range = None;
+        assert_eq!(state.visible_hexes(0.into()), None);
+        assert_eq!(state.view_for(0.into()).stacks.len(), 4);
+        assert_eq!(state.visible_entities_for(0.into()), ids);
+    }
+
+    #[test]
+    fn test_stack_report() {
+        let state = GameState::new(&[0; 32], 2, &mut EntityIdGenerator::new());
+        let (id, _) = sorted_by_id(&state.stacks)
+            .into_iter()
+            .find(|(_, stack)| stack.owner == 0.into())
+            .unwrap();
+
+        let report = state.stack_report(id).unwrap();
+        assert_eq!(report.controller, Some(0.into()));
+        assert_eq!(report.mass, 165);
+        assert_eq!((report.fuel, report.fuel_capacity), (40, 40));
+        assert_eq!(report.cargo, CargoList::new(0, 0, 60, 0));
+        assert_eq!(report.cargo_capacity, 60);
+        assert_eq!(report.max_delta, 0);
+        assert_eq!(report.mobility, MobilityStatus::NoEngines);
+        assert_eq!(report.components.len(), 7);
+        assert!(report.components.iter().all(|component| !component.damaged));
+        assert_eq!(
+            report
+                .components
+                .iter()
+                .filter(|component| component.name == CargoHold::NAME)
+                .count(),
+            3
+        );
+        assert_eq!(state.stack_report(EntityId::from(9999)), None);
+    }
+}